@@ -0,0 +1,761 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use cpu::{Opcode, PROGRAM_START};
+
+/// How deeply include files may be nested, which also catches files that
+/// include themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+/// How deeply constants may refer to other constants, which also catches
+/// constants that are defined in terms of themselves
+const MAX_CONSTANT_DEPTH: usize = 64;
+
+/// A problem with the source of a program, along with where it was found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    /// The file the problem is in, or `<source>` for source given as a
+    /// string
+    pub file: String,
+    /// The line the problem is on, counting from 1, or 0 if the file could
+    /// not be read at all
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        }
+        else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// Where a line of source came from
+#[derive(Clone, Debug)]
+struct Location {
+    file: String,
+    line: usize,
+}
+
+impl Location {
+    fn error<S: Into<String>>(&self, message: S) -> AsmError {
+        AsmError {
+            file: self.file.clone(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Something a line of source asks to be placed in memory
+enum Emit {
+    Instruction { mnemonic: String, operands: Vec<String> },
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+}
+
+impl Emit {
+    /// Returns the number of bytes emitted, without needing to know the
+    /// value of any label
+    fn size(&self) -> usize {
+        match *self {
+            Emit::Bytes(ref values) => values.len(),
+            Emit::Words(ref values) => values.len() * 2,
+            Emit::Instruction { ref mnemonic, ref operands } => {
+                let long = operands.get(1).is_some_and(|operand| {
+                    split_word(operand).0.eq_ignore_ascii_case("long")
+                });
+                if mnemonic == "LD" && long {
+                    4
+                }
+                else {
+                    2
+                }
+            },
+        }
+    }
+}
+
+/// A line of source that emits something
+struct Statement {
+    location: Location,
+    emit: Emit,
+}
+
+/// The value of an operand, once names and numbers have been looked at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Register(u16),
+    Index,
+    IndexMemory,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Flags,
+    Long(u16),
+    Value(i64),
+}
+
+/// Operand names that cannot be used for labels and constants, besides
+/// the registers
+const RESERVED: [&str; 10] = [
+    "I", "[I]", "DT", "ST", "K", "F", "HF", "B", "R", "LONG",
+];
+
+/// Assembles a program through two passes over its source
+/// The first pass lays out the program and collects labels and constants,
+/// so that the second one can encode instructions that refer to labels
+/// further down
+struct Assembler {
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, (String, Location)>,
+    statements: Vec<Statement>,
+    addr: u32,
+    /// How many include files are being read at the moment
+    depth: usize,
+}
+
+/// Assembles a program written in Cowgod's mnemonics, which is the syntax
+/// the disassembler writes by default
+/// Returns the bytes to load at 0x200
+///
+/// Besides the instructions, the source may contain:
+///
+/// * `name:` - A label for the address of the next instruction or data
+/// * `name EQU value` - A constant
+/// * `db value, ...` and `dw value, ...` - Bytes and big-endian words
+/// * `include "file"` - The source of another file, relative to the
+///   current directory
+///
+/// Comments start with `;`. Values are decimal, hexadecimal with a `0x`,
+/// `#` or `$` prefix, or binary with a `0b` or `%` prefix, and may be
+/// added to and subtracted from labels and constants
+///
+/// # Arguments
+///
+/// * `source` - The text of the program
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::asm::assemble;
+///
+/// let source = "
+///     start:
+///         LD V0, 0x05
+///         JP start
+/// ";
+/// assert_eq!(assemble(source), Ok(vec![0x60, 0x05, 0x12, 0x00]));
+/// ```
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new();
+    assembler.read(source, "<source>", Path::new("."))?;
+    assembler.encode()
+}
+
+/// Assembles a program from a file, see assemble() for the syntax
+/// Include files are looked up relative to the file that includes them
+///
+/// # Arguments
+///
+/// * `path` - The path of the file that holds the program
+///
+pub fn assemble_file(path: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new();
+    assembler.include(Path::new(path), None)?;
+    assembler.encode()
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Assembler {
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            statements: vec![],
+            addr: PROGRAM_START as u32,
+            depth: 0,
+        }
+    }
+
+    /// Reads a file and runs the first pass over it
+    fn include(
+        &mut self,
+        path: &Path,
+        from: Option<&Location>
+    ) -> Result<(), AsmError> {
+        let name = path.display().to_string();
+        let fail = |message: String| match from {
+            Some(location) => location.error(message),
+            None => AsmError { file: name.clone(), line: 0, message },
+        };
+
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(fail(format!(
+                "Too many nested includes while including {}",
+                name
+            )));
+        }
+        let mut source = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut source))
+            .map_err(|err| fail(format!("Could not read {}: {}", name, err)))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        self.depth += 1;
+        let result = self.read(&source, &name, dir);
+        self.depth -= 1;
+        result
+    }
+
+    /// Runs the first pass over some source, laying out what it emits and
+    /// recording its labels and constants
+    fn read(
+        &mut self,
+        source: &str,
+        file: &str,
+        dir: &Path
+    ) -> Result<(), AsmError> {
+        for (number, text) in source.lines().enumerate() {
+            let location = Location {
+                file: file.to_string(),
+                line: number + 1,
+            };
+            let mut text = text.split(';').next().unwrap_or("").trim();
+
+            // Any number of labels may come before the rest of the line
+            while let Some(colon) = label_end(text) {
+                let name = &text[..colon];
+                self.check_name(name, &location)?;
+                if self.addr > u16::MAX as u32 {
+                    return Err(location.error(
+                        "The label is past the end of memory"
+                    ));
+                }
+                self.labels.insert(name.to_string(), self.addr as u16);
+                text = text[colon + 1..].trim();
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let (word, rest) = split_word(text);
+            let (second, value) = split_word(rest);
+            if second.eq_ignore_ascii_case("equ") {
+                self.check_name(word, &location)?;
+                self.constants.insert(
+                    word.to_string(),
+                    (value.to_string(), location)
+                );
+                continue;
+            }
+
+            let emit = match word.to_ascii_uppercase().as_str() {
+                "INCLUDE" => {
+                    let name = rest.trim_matches('"');
+                    if rest.len() < 2 || !rest.starts_with('"')
+                        || !rest.ends_with('"')
+                    {
+                        return Err(location.error(
+                            "Expected the name of a file in double quotes"
+                        ));
+                    }
+                    self.include(&dir.join(name), Some(&location))?;
+                    continue;
+                },
+                "DB" => Emit::Bytes(split_operands(rest)),
+                "DW" => Emit::Words(split_operands(rest)),
+                _ => Emit::Instruction {
+                    mnemonic: word.to_ascii_uppercase(),
+                    operands: split_operands(rest),
+                },
+            };
+            let size = emit.size();
+            if self.addr + size as u32 > 0x10000 {
+                return Err(location.error(
+                    "The program does not fit in memory"
+                ));
+            }
+            self.statements.push(Statement { location, emit });
+            self.addr += size as u32;
+        }
+        Ok(())
+    }
+
+    /// Runs the second pass, encoding every statement now that all the
+    /// labels are known
+    fn encode(&mut self) -> Result<Vec<u8>, AsmError> {
+        let mut program = vec![];
+        for statement in &self.statements {
+            let location = &statement.location;
+            match statement.emit {
+                Emit::Bytes(ref values) => {
+                    for value in values {
+                        let value = self.evaluate(value, location, 0)?;
+                        program.push(fit(value, 0xFF, location)? as u8);
+                    }
+                },
+                Emit::Words(ref values) => {
+                    for value in values {
+                        let value = self.evaluate(value, location, 0)?;
+                        let value = fit(value, 0xFFFF, location)?;
+                        program.push((value >> 8) as u8);
+                        program.push(value as u8);
+                    }
+                },
+                Emit::Instruction { ref mnemonic, ref operands } => {
+                    let operands = operands.iter()
+                        .map(|text| self.operand(text, location))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let opcode = opcode(mnemonic, &operands, location)?;
+                    program.extend(opcode.encode());
+                },
+            }
+        }
+        Ok(program)
+    }
+
+    /// Checks that a new label or constant can be given a name
+    fn check_name(
+        &self,
+        name: &str,
+        location: &Location
+    ) -> Result<(), AsmError> {
+        let upper = name.to_ascii_uppercase();
+        let reserved = register(&upper).is_some()
+            || RESERVED.contains(&upper.as_str());
+        if !is_name(name) || reserved {
+            return Err(location.error(format!(
+                "'{}' cannot be used as a name",
+                name
+            )));
+        }
+        let defined = self.labels.contains_key(name)
+            || self.constants.contains_key(name);
+        if defined {
+            return Err(location.error(format!(
+                "'{}' is already defined",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Works out what an operand refers to
+    fn operand(
+        &self,
+        text: &str,
+        location: &Location
+    ) -> Result<Operand, AsmError> {
+        let upper = text.to_ascii_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Operand::Index,
+            "[I]" => Operand::IndexMemory,
+            "DT" => Operand::DelayTimer,
+            "ST" => Operand::SoundTimer,
+            "K" => Operand::Key,
+            "F" => Operand::Font,
+            "HF" => Operand::BigFont,
+            "B" => Operand::Bcd,
+            "R" => Operand::Flags,
+            _ => {
+                if let Some(register) = register(&upper) {
+                    return Ok(Operand::Register(register));
+                }
+                let (word, rest) = split_word(text);
+                if word.eq_ignore_ascii_case("long") {
+                    let value = self.evaluate(rest, location, 0)?;
+                    return Ok(Operand::Long(
+                        fit(value, 0xFFFF, location)?
+                    ));
+                }
+                Operand::Value(self.evaluate(text, location, 0)?)
+            },
+        };
+        Ok(operand)
+    }
+
+    /// Evaluates a sum of numbers, labels and constants
+    fn evaluate(
+        &self,
+        expression: &str,
+        location: &Location,
+        depth: usize
+    ) -> Result<i64, AsmError> {
+        if depth > MAX_CONSTANT_DEPTH {
+            return Err(location.error(
+                "A constant is defined in terms of itself"
+            ));
+        }
+        if expression.trim().is_empty() {
+            return Err(location.error("Expected a value"));
+        }
+
+        let mut total: i64 = 0;
+        let mut negative = false;
+        let mut term = String::new();
+        for c in expression.chars().chain(Some('+')) {
+            match c {
+                '+' | '-' if !term.trim().is_empty() => {
+                    let value = self.term(term.trim(), location, depth)?;
+                    total = if negative {
+                        total.wrapping_sub(value)
+                    }
+                    else {
+                        total.wrapping_add(value)
+                    };
+                    negative = c == '-';
+                    term.clear();
+                },
+                '-' => negative = !negative,
+                '+' => {},
+                _ => term.push(c),
+            }
+        }
+        Ok(total)
+    }
+
+    /// Evaluates a single number, label or constant
+    fn term(
+        &self,
+        text: &str,
+        location: &Location,
+        depth: usize
+    ) -> Result<i64, AsmError> {
+        if let Some(value) = number(text) {
+            return Ok(value);
+        }
+        if let Some(&addr) = self.labels.get(text) {
+            return Ok(addr as i64);
+        }
+        if let Some((value, _)) = self.constants.get(text) {
+            return self.evaluate(value, location, depth + 1);
+        }
+        if is_name(text) {
+            Err(location.error(format!("'{}' is not defined", text)))
+        }
+        else {
+            Err(location.error(format!("'{}' is not a valid value", text)))
+        }
+    }
+}
+
+/// Returns the position of the colon that ends a label at the start of a
+/// line, if there is one
+fn label_end(text: &str) -> Option<usize> {
+    let colon = text.find(':')?;
+    if is_name(&text[..colon]) {
+        Some(colon)
+    }
+    else {
+        None
+    }
+}
+
+/// Checks whether some text can be used as the name of a label or a
+/// constant
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {},
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Splits the first word off some text, returning it and the trimmed rest
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], text[end..].trim()),
+        None => (text, ""),
+    }
+}
+
+/// Splits the comma separated operands of an instruction or directive
+fn split_operands(text: &str) -> Vec<String> {
+    if text.trim().is_empty() {
+        return vec![];
+    }
+    text.split(',').map(|operand| operand.trim().to_string()).collect()
+}
+
+/// Parses a register name such as V0 or VF
+fn register(upper: &str) -> Option<u16> {
+    if upper.len() != 2 || !upper.starts_with('V') {
+        return None;
+    }
+    u16::from_str_radix(&upper[1..], 16).ok()
+}
+
+/// Parses a number literal
+fn number(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+    let prefixes = [("0x", 16), ("0b", 2), ("#", 16), ("$", 16), ("%", 2)];
+    let prefixed = prefixes.iter().find_map(|&(prefix, radix)| {
+        lower.strip_prefix(prefix).map(|digits| (digits, radix))
+    });
+    let (digits, radix) = match prefixed {
+        Some(found) => found,
+        None if lower.starts_with(|c: char| c.is_ascii_digit()) => {
+            (&lower[..], 10)
+        },
+        None => return None,
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+/// Checks that a value fits in a field of an instruction or directive
+/// Negative values down to -(max + 1) / 2 are stored as two's complement
+fn fit(value: i64, max: i64, location: &Location) -> Result<u16, AsmError> {
+    if value < -(max + 1) / 2 || value > max {
+        return Err(location.error(format!(
+            "{} does not fit in {} bits",
+            value,
+            64 - max.leading_zeros()
+        )));
+    }
+    Ok((value & max) as u16)
+}
+
+/// Builds the instruction a mnemonic and its operands stand for
+fn opcode(
+    mnemonic: &str,
+    operands: &[Operand],
+    location: &Location
+) -> Result<Opcode, AsmError> {
+    use self::Operand::*;
+
+    let addr = |value: i64| fit(value, 0xFFF, location);
+    let byte = |value: i64| fit(value, 0xFF, location);
+    let nibble = |value: i64| {
+        if (0..=0xF).contains(&value) {
+            Ok(value as u16)
+        }
+        else {
+            Err(location.error(format!("{} does not fit in 4 bits", value)))
+        }
+    };
+
+    let opcode = match (mnemonic, operands) {
+        ("CLS", &[]) => Opcode::ClearDisplay,
+        ("RET", &[]) => Opcode::ReturnFromSubroutine,
+        ("SYS", &[Value(a)]) => Opcode::CallRCAProgram(addr(a)?),
+        ("JP", &[Value(a)]) => Opcode::JumpToAddr(addr(a)?),
+        ("JP", &[Register(0), Value(a)]) => {
+            Opcode::SetProgramCounter(addr(a)?)
+        },
+        ("CALL", &[Value(a)]) => Opcode::CallSubroutine(addr(a)?),
+        ("SE", &[Register(x), Value(v)]) => {
+            Opcode::SkipIfRegisterSet { addr: x, value: byte(v)? }
+        },
+        ("SE", &[Register(x), Register(y)]) => {
+            Opcode::SkipIfRegistersEqual { first: x, second: y }
+        },
+        ("SNE", &[Register(x), Value(v)]) => {
+            Opcode::SkipIfRegisterNotSet { addr: x, value: byte(v)? }
+        },
+        ("SNE", &[Register(x), Register(y)]) => {
+            Opcode::SkipIfRegistersNotEqual { first: x, second: y }
+        },
+        ("LD", &[Register(x), Value(v)]) => {
+            Opcode::SetRegister { addr: x, value: byte(v)? }
+        },
+        ("LD", &[Register(x), Register(y)]) => {
+            Opcode::AssignRegister { first: x, second: y }
+        },
+        ("LD", &[Index, Value(a)]) => Opcode::SetIndexRegister(addr(a)?),
+        ("LD", &[Index, Long(a)]) => Opcode::SetIndexRegisterLong(a),
+        ("LD", &[Register(x), DelayTimer]) => Opcode::AssignToDelayTime(x),
+        ("LD", &[Register(x), Key]) => Opcode::AssignOnKeyPress(x),
+        ("LD", &[DelayTimer, Register(x)]) => Opcode::SetDelayTimer(x),
+        ("LD", &[SoundTimer, Register(x)]) => Opcode::SetSoundTimer(x),
+        ("LD", &[Font, Register(x)]) => {
+            Opcode::SetIndexRegisterToSpriteAddr(x)
+        },
+        ("LD", &[BigFont, Register(x)]) => {
+            Opcode::SetIndexRegisterToBigSpriteAddr(x)
+        },
+        ("LD", &[Bcd, Register(x)]) => Opcode::SetBCD(x),
+        ("LD", &[IndexMemory, Register(x)]) => Opcode::DumpRegister(x),
+        ("LD", &[Register(x), IndexMemory]) => Opcode::LoadRegister(x),
+        ("LD", &[Flags, Register(x)]) => Opcode::SaveFlags(x),
+        ("LD", &[Register(x), Flags]) => Opcode::LoadFlags(x),
+        ("ADD", &[Register(x), Value(v)]) => {
+            Opcode::AddToRegister { addr: x, value: byte(v)? }
+        },
+        ("ADD", &[Register(x), Register(y)]) => {
+            Opcode::AddRegisters { first: x, second: y }
+        },
+        ("ADD", &[Index, Register(x)]) => Opcode::AddToIndexRegister(x),
+        ("OR", &[Register(x), Register(y)]) => {
+            Opcode::AssignRegisterBitwiseOr { first: x, second: y }
+        },
+        ("AND", &[Register(x), Register(y)]) => {
+            Opcode::AssignRegisterBitwiseAnd { first: x, second: y }
+        },
+        ("XOR", &[Register(x), Register(y)]) => {
+            Opcode::AssignRegisterBitwiseXor { first: x, second: y }
+        },
+        ("SUB", &[Register(x), Register(y)]) => {
+            Opcode::SubtractRegisters { first: x, second: y }
+        },
+        ("SUBN", &[Register(x), Register(y)]) => {
+            Opcode::SubtractFirstRegister { first: x, second: y }
+        },
+        // Shifting a register by itself behaves the same whether or not
+        // the shift_uses_vy quirk is on
+        ("SHR", &[Register(x)]) => {
+            Opcode::AssignRegisterBitshiftRight { first: x, second: x }
+        },
+        ("SHR", &[Register(x), Register(y)]) => {
+            Opcode::AssignRegisterBitshiftRight { first: x, second: y }
+        },
+        ("SHL", &[Register(x)]) => {
+            Opcode::AssignRegistersBitshiftLeft { first: x, second: x }
+        },
+        ("SHL", &[Register(x), Register(y)]) => {
+            Opcode::AssignRegistersBitshiftLeft { first: x, second: y }
+        },
+        ("RND", &[Register(x), Value(v)]) => {
+            Opcode::AssignRandomValue { addr: x, value: byte(v)? }
+        },
+        ("DRW", &[Register(x), Register(y), Value(n)]) => {
+            Opcode::Draw { x, y, height: nibble(n)? }
+        },
+        ("SKP", &[Register(x)]) => Opcode::SkipIfKeyPressed(x),
+        ("SKNP", &[Register(x)]) => Opcode::SkipIfKeyNotPressed(x),
+        ("SCD", &[Value(n)]) => Opcode::ScrollDown(nibble(n)?),
+        ("SCU", &[Value(n)]) => Opcode::ScrollUp(nibble(n)?),
+        ("SCR", &[]) => Opcode::ScrollRight,
+        ("SCL", &[]) => Opcode::ScrollLeft,
+        ("EXIT", &[]) => Opcode::Exit,
+        ("LOW", &[]) => Opcode::DisableHighRes,
+        ("HIGH", &[]) => Opcode::EnableHighRes,
+        ("SAVE", &[Register(x), Register(y)]) => {
+            Opcode::SaveRegisterRange { first: x, second: y }
+        },
+        ("LOAD", &[Register(x), Register(y)]) => {
+            Opcode::LoadRegisterRange { first: x, second: y }
+        },
+        ("PLANE", &[Value(n)]) => Opcode::SelectPlanes(nibble(n)?),
+        ("AUDIO", &[]) => Opcode::LoadAudioPattern,
+        ("PITCH", &[Register(x)]) => Opcode::SetPitch(x),
+        _ => {
+            return Err(location.error(format!(
+                "Unknown instruction or wrong operands for '{}'",
+                mnemonic
+            )));
+        },
+    };
+    Ok(opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::Cpu;
+    use disasm::{disassemble, Syntax};
+    use platform::Platform;
+    use rand::prng::XorShiftRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn decode_inverts_encode() {
+        for word in 0..=0xFFFF {
+            let opcode = match Cpu::decode(word) {
+                Ok(opcode) => opcode,
+                Err(_) => continue,
+            };
+            let bytes = opcode.encode();
+            assert_eq!(bytes, vec![(word >> 8) as u8, word as u8]);
+            let encoded = (bytes[0] as u16) << 8 | bytes[1] as u16;
+            assert_eq!(Cpu::decode(encoded), Ok(opcode));
+        }
+        let long = Opcode::SetIndexRegisterLong(0xBEEF);
+        assert_eq!(long.encode(), vec![0xF0, 0x00, 0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn mnemonics_assemble_to_their_opcode() {
+        for word in 0..=0xFFFF {
+            let opcode = match Cpu::decode(word) {
+                Ok(opcode) => opcode,
+                Err(_) => continue,
+            };
+            let source = format!("{}", opcode);
+            assert_eq!(assemble(&source), Ok(opcode.encode()), "{}", source);
+        }
+        let long = Opcode::SetIndexRegisterLong(0xBEEF);
+        assert_eq!(assemble(&format!("{}", long)), Ok(long.encode()));
+    }
+
+    #[test]
+    fn labels_constants_and_data() {
+        let source = "
+            SPEED equ 3
+            start:  LD V0, SPEED + 1   ; comment
+                    LD I, sprite
+                    JP start
+            sprite: db 0xF0, %10010000, $90, 144, -1
+                    dw sprite - 2
+        ";
+        assert_eq!(assemble(source), Ok(vec![
+            0x60, 0x04,
+            0xA2, 0x06,
+            0x12, 0x00,
+            0xF0, 0x90, 0x90, 0x90, 0xFF,
+            0x02, 0x04,
+        ]));
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let error = assemble("CLS\nJP nowhere").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(error.message.contains("nowhere"));
+
+        assert_eq!(assemble("LD V0, 256").unwrap_err().line, 1);
+        assert_eq!(assemble("a: CLS\na: CLS").unwrap_err().line, 2);
+        assert_eq!(assemble("X equ Y\nY equ X\ndb X").unwrap_err().line, 3);
+        assert_eq!(assemble("FROB V0").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn programs_and_labels_end_with_memory() {
+        // Fills memory from 0x200 up to its very end
+        let full = "CLS\n".repeat(0x7F00);
+        assert_eq!(assemble(&(full.clone() + "last:")).unwrap_err().line,
+            0x7F01);
+        assert_eq!(assemble(&(full + "CLS")).unwrap_err().line, 0x7F01);
+
+        let source = "CLS\n".repeat(0x7EFF) + "last: dw last";
+        let program = assemble(&source).unwrap();
+        assert_eq!(program.len(), 0xFE00);
+        assert_eq!(program[0xFDFE..], [0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn disassembly_reassembles_to_the_same_rom() {
+        let mut rng = XorShiftRng::from_seed([8; 16]);
+        for platform in &[Platform::Chip8, Platform::XoChip] {
+            for _ in 0..200 {
+                let mut rom = vec![0; rng.gen_range(1, 128)];
+                rng.fill(&mut rom[..]);
+                let listing = disassemble(&rom, *platform, Syntax::Cowgod);
+                assert_eq!(assemble(&listing), Ok(rom), "{}", listing);
+            }
+        }
+    }
+}
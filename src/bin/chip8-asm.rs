@@ -0,0 +1,53 @@
+extern crate chip8_rust;
+
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
+
+use chip8_rust::asm;
+
+/// Prints how the assembler is meant to be invoked and exits
+fn usage() -> ! {
+    eprintln!("Usage: chip8-asm [-o <rom>] <source>");
+    process::exit(2);
+}
+
+fn main() {
+    let mut output = None;
+    let mut source = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                output = Some(args.next().unwrap_or_else(|| usage()));
+            },
+            _ if source.is_none() && !arg.starts_with('-') => {
+                source = Some(arg)
+            },
+            _ => usage(),
+        }
+    }
+    let source = source.unwrap_or_else(|| usage());
+    // The rom is written next to the source unless told otherwise
+    let output = output.unwrap_or_else(|| {
+        Path::new(&source).with_extension("ch8").display().to_string()
+    });
+
+    let rom = match asm::assemble_file(&source) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        },
+    };
+    let written = File::create(&output).and_then(|mut file| {
+        file.write_all(&rom)
+    });
+    if let Err(err) = written {
+        eprintln!("Error: could not write {}: {}", output, err);
+        process::exit(1);
+    }
+}
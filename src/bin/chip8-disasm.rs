@@ -0,0 +1,58 @@
+extern crate chip8_rust;
+
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::process;
+
+use chip8_rust::disasm::{self, Syntax};
+use chip8_rust::platform::Platform;
+
+/// Prints how the disassembler is meant to be invoked and exits
+fn usage() -> ! {
+    eprintln!(
+        "Usage: chip8-disasm [--platform chip8|schip|xochip] \
+         [--syntax cowgod|octo] <rom>"
+    );
+    process::exit(2);
+}
+
+fn main() {
+    let mut platform = Platform::default();
+    let mut syntax = Syntax::default();
+    let mut rom = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                platform = name.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                });
+            },
+            "--syntax" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                syntax = name.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                });
+            },
+            _ if rom.is_none() && !arg.starts_with("--") => rom = Some(arg),
+            _ => usage(),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+
+    let mut buffer = vec![];
+    let read = File::open(&rom).and_then(|mut file| {
+        file.read_to_end(&mut buffer)
+    });
+    if let Err(err) = read {
+        eprintln!("Error: could not read {}: {}", rom, err);
+        process::exit(1);
+    }
+
+    print!("{}", disasm::disassemble(&buffer, platform, syntax));
+}
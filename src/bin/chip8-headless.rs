@@ -0,0 +1,158 @@
+extern crate chip8_rust;
+
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::process;
+
+use chip8_rust::cpu::CpuError;
+use chip8_rust::headless::{self, Condition, ScriptedKey};
+use chip8_rust::image;
+use chip8_rust::options::MachineOptions;
+use chip8_rust::settings::RomSettings;
+
+/// The number of frames run when --frames is not given, ten seconds
+const DEFAULT_FRAMES: u64 = 600;
+
+/// Prints how the headless runner is meant to be invoked and exits
+fn usage() -> ! {
+    eprintln!(
+        "Usage: chip8-headless [--platform chip8|schip|xochip] \
+         [--quirks vip|chip48|schip|modern] [--speed <n>] [--seed <n>] \
+         [--load-address <addr>] [--frames <n>] \
+         [--until pc=<addr>|v<x>=<value>|self-jump]... \
+         [--key <frame>:<key>:down|up]... [--keys <script>] \
+         [--image <file.png|file.pbm>] [--json <file>|-] <rom>|-"
+    );
+    process::exit(2);
+}
+
+/// Prints an error and exits with a failure
+fn fail(message: String) -> ! {
+    eprintln!("Error: {}", message);
+    process::exit(1);
+}
+
+/// Prints an error raised by the rom while running and exits with a
+/// status of its own, so that scripts can tell it from a bad invocation
+fn crash(err: CpuError) -> ! {
+    eprintln!("Error: {}", err);
+    process::exit(3);
+}
+
+fn main() {
+    let mut options = MachineOptions {
+        // Runs are repeatable unless asked otherwise
+        seed: Some(0),
+        ..MachineOptions::default()
+    };
+    let mut frames = DEFAULT_FRAMES;
+    let mut conditions: Vec<Condition> = vec![];
+    let mut keys: Vec<ScriptedKey> = vec![];
+    let mut image_path = None;
+    let mut json_path = None;
+    let mut rom = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match options.parse_arg(&arg, &mut args) {
+            Ok(true) => continue,
+            Ok(false) => (),
+            Err(err) => {
+                eprintln!("{}", err);
+                usage()
+            },
+        }
+        match arg.as_str() {
+            "--frames" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                frames = value.parse().unwrap_or_else(|err| {
+                    eprintln!("Invalid number of frames '{}': {}", value, err);
+                    usage()
+                });
+            },
+            "--until" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                conditions.push(value.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                }));
+            },
+            "--key" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                keys.push(value.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                }));
+            },
+            "--keys" => {
+                let path = args.next().unwrap_or_else(|| usage());
+                let mut text = String::new();
+                File::open(&path)
+                    .and_then(|mut file| file.read_to_string(&mut text))
+                    .unwrap_or_else(|err| {
+                        fail(format!("could not read {}: {}", path, err))
+                    });
+                let script = headless::parse_key_script(&text)
+                    .unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+                keys.extend(script);
+            },
+            "--image" => {
+                image_path = Some(args.next().unwrap_or_else(|| usage()));
+            },
+            "--json" => {
+                json_path = Some(args.next().unwrap_or_else(|| usage()));
+            },
+            _ if rom.is_none() && !arg.starts_with("--") => rom = Some(arg),
+            _ => usage(),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+    // Keys given on the command line and in scripts may be mixed
+    keys.sort_by_key(|key| key.frame);
+
+    let settings = RomSettings::load(&rom).unwrap_or_else(|err| fail(err));
+    let mut cpu = options.cpu(&settings);
+    cpu.load_fontset();
+    if let Err(err) = cpu.load_rom(&rom) {
+        fail(format!("could not load {}: {}", rom, err));
+    }
+
+    let outcome = headless::run(&mut cpu, frames, &conditions, &keys)
+        .unwrap_or_else(|err| crash(err));
+    eprintln!(
+        "Stopped after {} frames ({}) at {:#05x}",
+        outcome.frames,
+        outcome.stop,
+        cpu.pc()
+    );
+
+    if let Some(path) = image_path {
+        let data = if path.ends_with(".pbm") {
+            image::to_pbm(&cpu)
+        }
+        else {
+            image::to_png(&cpu)
+        };
+        File::create(&path)
+            .and_then(|mut file| file.write_all(&data))
+            .unwrap_or_else(|err| {
+                fail(format!("could not write {}: {}", path, err))
+            });
+    }
+    if let Some(path) = json_path {
+        let json = headless::dump_json(&cpu, &outcome);
+        let written = if path == "-" {
+            io::stdout().write_all(json.as_bytes())
+        }
+        else {
+            File::create(&path).and_then(|mut file| {
+                file.write_all(json.as_bytes())
+            })
+        };
+        if let Err(err) = written {
+            fail(format!("could not write {}: {}", path, err));
+        }
+    }
+}
@@ -0,0 +1,88 @@
+extern crate chip8_rust;
+
+use std::env;
+use std::process;
+use std::time::Duration;
+
+use chip8_rust::core::{self, Rendering, Terminal};
+use chip8_rust::options::MachineOptions;
+use chip8_rust::settings::RomSettings;
+
+/// Prints how the terminal frontend is meant to be invoked and exits
+fn usage() -> ! {
+    eprintln!(
+        "Usage: chip8-tui [--platform chip8|schip|xochip] \
+         [--quirks vip|chip48|schip|modern] [--speed <n>] [--seed <n>] \
+         [--load-address <addr>] [--render half|braille] \
+         [--release-timeout <ms>] <rom>"
+    );
+    process::exit(2);
+}
+
+fn main() {
+    let mut options = MachineOptions::default();
+    let mut rendering = Rendering::default();
+    let mut release_timeout = core::DEFAULT_RELEASE_TIMEOUT;
+    let mut rom = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match options.parse_arg(&arg, &mut args) {
+            Ok(true) => continue,
+            Ok(false) => (),
+            Err(err) => {
+                eprintln!("{}", err);
+                usage()
+            },
+        }
+        match arg.as_str() {
+            "--render" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                rendering = name.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                });
+            },
+            "--release-timeout" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                let millis = value.parse().unwrap_or_else(|err| {
+                    eprintln!("Invalid timeout '{}': {}", value, err);
+                    usage()
+                });
+                release_timeout = Duration::from_millis(millis);
+            },
+            _ if rom.is_none() && !arg.starts_with("--") => rom = Some(arg),
+            _ => usage(),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+    if rom == "-" {
+        // Standard input is where the keys come from
+        eprintln!("The rom cannot be read from standard input");
+        usage();
+    }
+
+    let settings = RomSettings::load(&rom).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    });
+    let mut cpu = options.cpu(&settings);
+    cpu.load_fontset();
+    if let Err(err) = cpu.load_rom(&rom) {
+        eprintln!("Error: could not load {}: {}", rom, err);
+        process::exit(1);
+    }
+
+    let result = match Terminal::new(rendering, release_timeout) {
+        // The terminal has to be restored before any error is printed
+        Ok(mut terminal) => cpu.run(&mut terminal),
+        Err(err) => {
+            eprintln!("Error: the terminal cannot be used: {}", err);
+            process::exit(1);
+        },
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        process::exit(3);
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(feature = "sdl")]
+mod sdl;
+#[cfg(feature = "tui")]
+mod tui;
+
+#[cfg(feature = "sdl")]
+pub use self::sdl::{Core, Scaling, DEFAULT_SCALE};
+#[cfg(feature = "tui")]
+pub use self::tui::{Rendering, Terminal, DEFAULT_RELEASE_TIMEOUT};
+
+pub const DISPLAY_HEIGHT: u32 = 32;
+pub const DISPLAY_WIDTH: u32 = 64;
+/// The size of the display in the SUPER-CHIP high resolution mode
+pub const HIRES_HEIGHT: u32 = 64;
+pub const HIRES_WIDTH: u32 = 128;
+
+/// A pixel of the display
+/// Chip-8 and SUPER-CHIP pixels are either Black or White, while XO-CHIP
+/// draws on two bitplanes that give each pixel one of four colors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pixel {
+    /// No plane is set
+    Black,
+    /// Only the first plane is set
+    White,
+    /// Only the second plane is set
+    SecondPlane,
+    /// Both planes are set
+    BothPlanes,
+}
+
+impl Pixel {
+    /// Builds a pixel from a bitmask of the planes that are set
+    ///
+    /// # Arguments
+    ///
+    /// * `planes` - Bit 0 is the first plane and bit 1 the second one
+    ///
+    pub fn from_planes(planes: u8) -> Self {
+        match planes & 0b11 {
+            0b00 => Pixel::Black,
+            0b01 => Pixel::White,
+            0b10 => Pixel::SecondPlane,
+            _ => Pixel::BothPlanes,
+        }
+    }
+
+    /// Returns the bitmask of the planes that are set, which doubles as the
+    /// index of the color of the pixel
+    pub fn planes(self) -> u8 {
+        match self {
+            Pixel::Black => 0b00,
+            Pixel::White => 0b01,
+            Pixel::SecondPlane => 0b10,
+            Pixel::BothPlanes => 0b11,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyState {
+    Pressed,
+    Released,
+}
@@ -0,0 +1,857 @@
+use sdl2::pixels::Color;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use sdl2::keyboard::{LCTRLMOD, LSHIFTMOD, NOMOD, RCTRLMOD, RSHIFTMOD};
+use sdl2::rect::Rect;
+use sdl2::video::{FullscreenType, Window};
+use sdl2::render::Canvas;
+use sdl2::audio::{AudioCallback, AudioSpecDesired, AudioDevice};
+use sdl2::{EventPump, GameControllerSubsystem, Sdl};
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use cpu::Cpu;
+use frontend::{self, Command, Frontend};
+use keymap::{self, Action, Chord, Keymap, Mode};
+use palette::Palette;
+use phosphor::{Persistence, Phosphor};
+use scheduler::Pace;
+use super::{DISPLAY_HEIGHT, DISPLAY_WIDTH, KeyState};
+
+/// The size of the window at first, in window pixels to a pixel of the low
+/// resolution display, unless another scale is asked for
+pub const DEFAULT_SCALE: u32 = 12;
+/// The size in window pixels of a pixel of the speed indicator
+const INDICATOR_SCALE: u32 = 4;
+/// How far a stick or trigger has to be pushed to count as a press, out
+/// of 32767
+const AXIS_THRESHOLD: i16 = 16384;
+
+/// Returns the 3x5 pixel glyph used by the speed indicator for a
+/// character, one row per byte with the leftmost pixel in bit 2
+fn indicator_glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        'x' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        '|' => [0b010, 0b010, 0b010, 0b010, 0b010],
+        '>' => [0b100, 0b110, 0b111, 0b110, 0b100],
+        _ => [0; 5],
+    }
+}
+
+/// How the display is fitted into a window of any size, which is filled
+/// with black where the display does not reach
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scaling {
+    /// The largest whole number of window pixels to a pixel, which keeps
+    /// every pixel the same size
+    #[default]
+    Integer,
+    /// As large as the window allows while keeping the shape of the
+    /// display, which leaves some pixels a window pixel larger than others
+    Fit,
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "integer" => Ok(Scaling::Integer),
+            "fit" => Ok(Scaling::Fit),
+            _ => Err(format!(
+                "Unknown scaling '{}', expected integer or fit",
+                s
+            )),
+        }
+    }
+}
+
+/// A key of the host keyboard or an input of a game controller, as the
+/// keymap matches it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum HostKey {
+    Code(Keycode),
+    Scan(Scancode),
+    /// A button of the game controller of that number
+    Button(u8, Button),
+    /// A stick or trigger of the game controller of that number, pushed
+    /// the positive way or the negative way
+    Axis(u8, Axis, bool),
+}
+
+impl HostKey {
+    /// Looks up a key by the name SDL gives it
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Whether the name is that of a keycode or of a scancode
+    /// * `chord` - The key, whose name may be in any case
+    ///
+    fn from_chord(mode: Mode, chord: &Chord) -> Option<Self> {
+        if let Some(pad) = chord.pad {
+            let input = chord.key.to_lowercase();
+            if input.ends_with('+') || input.ends_with('-') {
+                let (name, sign) = input.split_at(input.len() - 1);
+                return Axis::from_string(name).map(|axis| {
+                    HostKey::Axis(pad, axis, sign == "+")
+                });
+            }
+            // Triggers are only pushed one way
+            if let Some(axis) = Axis::from_string(&input) {
+                return Some(HostKey::Axis(pad, axis, true));
+            }
+            return Button::from_string(&input).map(|button| {
+                HostKey::Button(pad, button)
+            });
+        }
+        // SDL only knows the keycodes of letters by their lower case
+        let name = if chord.key.chars().count() == 1 {
+            chord.key.to_lowercase()
+        }
+        else {
+            chord.key.clone()
+        };
+        match mode {
+            Mode::Keycode => Keycode::from_name(&name).map(HostKey::Code),
+            Mode::Scancode => Scancode::from_name(&name).map(HostKey::Scan),
+        }
+    }
+
+    /// Returns the number of the game controller the input belongs to
+    fn pad(self) -> Option<u8> {
+        match self {
+            HostKey::Button(pad, _) | HostKey::Axis(pad, _, _) => Some(pad),
+            _ => None,
+        }
+    }
+}
+
+/// Plays back a looping pattern of 128 1-bit samples
+struct PatternWave {
+    pattern: [u8; 16],
+    freq: f32,
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+}
+
+impl PatternWave {
+    /// Switches to a new pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - 128 samples of 1-bit audio, most significant bit first
+    /// * `rate` - The number of samples of the pattern to play each second
+    ///
+    fn set_pattern(&mut self, pattern: &[u8; 16], rate: f32) {
+        self.pattern = *pattern;
+        self.phase_inc = rate / self.freq;
+    }
+}
+
+impl AudioCallback for PatternWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            let bit = self.phase as usize;
+            let sample = self.pattern[bit / 8] & (0b1000_0000 >> (bit % 8));
+            *x = if sample != 0 {
+                self.volume
+            }
+            else {
+                -self.volume
+            };
+            self.phase = (self.phase + self.phase_inc) % 128.0;
+        }
+    }
+}
+
+/// The SDL implementation of a Frontend
+pub struct Core {
+    event_pump: EventPump,
+    canvas: Canvas<Window>,
+    audio_device: AudioDevice<PatternWave>,
+    /// How the host keys of the keymap are matched
+    mode: Mode,
+    /// What every bound host key does, by the key and whether shift and
+    /// control are held
+    bindings: HashMap<(HostKey, bool, bool), Action>,
+    /// The host keys holding down keys of the keypad
+    held: Vec<(HostKey, u8)>,
+    /// Opens game controllers as they are plugged in, unless SDL cannot
+    /// read them on this host
+    controllers: Option<GameControllerSubsystem>,
+    /// The game controllers played with, by their number less one
+    pads: Vec<Option<GameController>>,
+    /// The sticks and triggers pushed far enough to count as pressed
+    pushed: Vec<HostKey>,
+    /// Whether the rewind key is held down
+    rewinding: bool,
+    /// Whether presenting the canvas waits for the vertical blank
+    vsync: bool,
+    /// How the display is fitted into the window
+    scaling: Scaling,
+    /// The palettes the palette hotkey goes through
+    palettes: Vec<Palette>,
+    /// The palette the display is drawn in, as an index into palettes
+    palette: usize,
+    /// The afterglow of pixels the rom clears
+    phosphor: Phosphor,
+    /// The speed shown in the corner of the window unless it is normal
+    pace: Pace,
+}
+
+impl Core {
+    /// Creates a new Core object that keeps time with the clock of the host
+    pub fn new(sdl_context: &Sdl) -> Self {
+        Core::with_vsync(sdl_context, false)
+    }
+
+    /// Creates a new Core object
+    ///
+    /// # Arguments
+    ///
+    /// * `sdl_context` - The initialized Sdl library
+    /// * `vsync` - Whether to pace the emulator by the vertical blank of
+    ///   the display, which is smoother but only keeps the right speed on
+    ///   60 Hz displays
+    ///
+    pub fn with_vsync(sdl_context: &Sdl, vsync: bool) -> Self {
+        Core::with_scale(sdl_context, vsync, DEFAULT_SCALE)
+    }
+
+    /// Creates a new Core object with a window of a chosen size, which
+    /// can be resized afterwards
+    ///
+    /// # Arguments
+    ///
+    /// * `sdl_context` - The initialized Sdl library
+    /// * `vsync` - Whether to pace the emulator by the vertical blank of
+    ///   the display
+    /// * `scale` - The number of window pixels to a pixel of the low
+    ///   resolution display
+    ///
+    pub fn with_scale(sdl_context: &Sdl, vsync: bool, scale: u32) -> Self {
+        let video_subsystem = sdl_context.video().unwrap();
+        let mut window = video_subsystem.window(
+            "Chip8-Rust",
+            DISPLAY_WIDTH*scale,
+            DISPLAY_HEIGHT*scale
+        )
+                                    .position_centered()
+                                    .resizable()
+                                    .opengl()
+                                    .build()
+                                    .unwrap();
+        let _ = window.set_minimum_size(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        let builder = window.into_canvas();
+        let builder = if vsync {
+            builder.present_vsync()
+        }
+        else {
+            builder
+        };
+        let mut canvas = builder.build().unwrap();
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        canvas.present();
+
+        let audio_subsystem = sdl_context.audio().unwrap();
+        let desired_spec = AudioSpecDesired {
+            freq: Some(44000),
+            channels: Some(1),
+            samples: None,
+        };
+        let audio_device = audio_subsystem.open_playback(
+            None,
+            &desired_spec,
+            |spec| {
+                // Stays silent until the Cpu hands over its pattern
+                PatternWave {
+                    pattern: [0; 16],
+                    freq: spec.freq as f32,
+                    phase_inc: 0.0,
+                    phase: 0.0,
+                    volume: 0.25,
+                }
+            }
+        ).unwrap();
+
+        let mut core = Core {
+            event_pump: sdl_context.event_pump().unwrap(),
+            canvas,
+            audio_device,
+            mode: Mode::Scancode,
+            bindings: HashMap::new(),
+            held: vec![],
+            // Game controllers that are already plugged in are announced
+            // by events too, so they are all opened by poll_input()
+            controllers: sdl_context.game_controller().ok(),
+            pads: (0..keymap::PADS).map(|_| None).collect(),
+            pushed: vec![],
+            rewinding: false,
+            vsync,
+            scaling: Scaling::default(),
+            palettes: Palette::builtin(),
+            palette: 0,
+            phosphor: Phosphor::new(Persistence::Off),
+            pace: Pace::default(),
+        };
+        core.set_keymap(&Keymap::default()).unwrap();
+        core
+    }
+
+    /// Changes how the display is fitted into the window
+    pub fn set_scaling(&mut self, scaling: Scaling) {
+        self.scaling = scaling;
+    }
+
+    /// Changes the palettes the palette hotkey goes through, starting
+    /// over from the first one
+    ///
+    /// # Arguments
+    ///
+    /// * `palettes` - The palettes, of which there has to be at least one
+    ///
+    pub fn set_palettes(&mut self, palettes: Vec<Palette>) {
+        if !palettes.is_empty() {
+            self.palettes = palettes;
+            self.palette = 0;
+        }
+    }
+
+    /// Draws the display in a palette from now on
+    /// A palette the hotkey does not go through yet is added after the
+    /// others
+    ///
+    /// # Arguments
+    ///
+    /// * `palette` - The palette to draw in
+    ///
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = match self.palettes.iter().position(|p| *p == palette) {
+            Some(i) => i,
+            None => {
+                self.palettes.push(palette);
+                self.palettes.len() - 1
+            },
+        };
+    }
+
+    /// Changes how long pixels stay lit on screen after the rom clears
+    /// them, which hides the flicker of roms that erase and redraw their
+    /// sprites without touching the display of the Cpu
+    pub fn set_persistence(&mut self, persistence: Persistence) {
+        self.phosphor = Phosphor::new(persistence);
+    }
+
+    /// Switches to the next palette, going back to the first after the last
+    fn next_palette(&mut self) {
+        self.palette = (self.palette + 1) % self.palettes.len();
+        eprintln!("Palette: {}", self.palettes[self.palette].name);
+    }
+
+    /// Switches the window between fullscreen, at the resolution of the
+    /// desktop, and windowed
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        if let Err(err) = window.set_fullscreen(fullscreen) {
+            eprintln!("Could not switch to fullscreen: {}", err);
+        }
+    }
+
+    /// Returns where the display goes in the window, as the position of
+    /// its top left corner and its size in window pixels
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the display, in pixels
+    /// * `height` - The height of the display, in pixels
+    ///
+    fn viewport(&self, width: u32, height: u32) -> Rect {
+        let (window_width, window_height) = self.canvas.output_size()
+            .unwrap_or((DISPLAY_WIDTH, DISPLAY_HEIGHT));
+        let scale = (window_width / width).min(window_height / height);
+        // A window smaller than the display gets the fitted scale too,
+        // since no whole number of window pixels fits
+        let (view_width, view_height) = match self.scaling {
+            Scaling::Integer if scale > 0 => (width*scale, height*scale),
+            _ => {
+                if window_width*height <= window_height*width {
+                    (window_width, window_width*height / width)
+                }
+                else {
+                    (window_height*width / height, window_height)
+                }
+            },
+        };
+        Rect::new(
+            ((window_width - view_width) / 2) as i32,
+            ((window_height - view_height) / 2) as i32,
+            view_width.max(1),
+            view_height.max(1)
+        )
+    }
+
+    /// Switches to another keymap
+    /// The keymap is refused if it names a key SDL does not know, and the
+    /// one in use is kept
+    ///
+    /// # Arguments
+    ///
+    /// * `keymap` - The host keys of the keypad and of the hotkeys
+    ///
+    pub fn set_keymap(&mut self, keymap: &Keymap) -> Result<(), String> {
+        let mut bindings = HashMap::new();
+        for &(ref chord, action) in keymap.bindings() {
+            let key = HostKey::from_chord(keymap.mode(), chord)
+                .ok_or_else(|| format!("Unknown key '{}'", chord))?;
+            bindings.insert((key, chord.shift, chord.ctrl), action);
+        }
+        self.mode = keymap.mode();
+        self.bindings = bindings;
+        self.held.clear();
+        Ok(())
+    }
+
+    /// Returns the host key of a key event, as the keymap matches it
+    fn host_key(
+        &self,
+        keycode: Option<Keycode>,
+        scancode: Option<Scancode>
+    ) -> Option<HostKey> {
+        match self.mode {
+            Mode::Keycode => keycode.map(HostKey::Code),
+            Mode::Scancode => scancode.map(HostKey::Scan),
+        }
+    }
+
+    /// Returns the number of a game controller that is played with
+    ///
+    /// # Arguments
+    ///
+    /// * `which` - The id SDL gives the game controller in its events
+    ///
+    fn pad_number(&self, which: i32) -> Option<u8> {
+        self.pads.iter()
+            .position(|pad| {
+                pad.as_ref().is_some_and(|pad| pad.instance_id() == which)
+            })
+            .map(|i| i as u8 + 1)
+    }
+
+    /// Starts playing with a game controller that was plugged in, as the
+    /// first one with no controller yet
+    /// Controllers beyond the last one are left alone
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index SDL gives the game controller among the
+    ///   joysticks
+    ///
+    fn open_pad(&mut self, index: u32) {
+        let controller = match self.controllers {
+            Some(ref controllers) => controllers.open(index),
+            None => return,
+        };
+        let controller = match controller {
+            Ok(controller) => controller,
+            Err(err) => {
+                eprintln!("Could not open game controller: {}", err);
+                return;
+            },
+        };
+        if self.pad_number(controller.instance_id()).is_some() {
+            return;
+        }
+        if let Some(i) = self.pads.iter().position(|pad| pad.is_none()) {
+            eprintln!("Game controller {}: {}", i + 1, controller.name());
+            self.pads[i] = Some(controller);
+        }
+    }
+
+    /// Stops playing with a game controller that was unplugged, letting go
+    /// of everything it held down
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    /// * `which` - The id SDL gives the game controller in its events
+    ///
+    fn close_pad(&mut self, cpu: &mut Cpu, which: i32) {
+        let pad = match self.pad_number(which) {
+            Some(pad) => pad,
+            None => return,
+        };
+        let keys: Vec<HostKey> = self.held.iter()
+            .map(|&(key, _)| key)
+            .filter(|key| key.pad() == Some(pad))
+            .collect();
+        for key in keys {
+            self.release(cpu, key);
+        }
+        self.pushed.retain(|key| key.pad() != Some(pad));
+        self.pads[pad as usize - 1] = None;
+        eprintln!("Game controller {} unplugged", pad);
+    }
+
+    /// Returns what a host key does with the modifiers held along with it
+    /// A key bound with other modifiers than those held does what it does
+    /// on its own, so that e.g. holding shift does not stop the keypad
+    fn action(&self, key: HostKey, keymod: Mod) -> Option<Action> {
+        let shift = keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+        let ctrl = keymod.intersects(LCTRLMOD | RCTRLMOD);
+        self.bindings.get(&(key, shift, ctrl))
+            .or_else(|| self.bindings.get(&(key, false, false)))
+            .cloned()
+    }
+
+    /// Shows the current speed in the top left corner of the window, in
+    /// white on a black box so that it stands out from any rom
+    fn draw_indicator(&mut self) {
+        let text = self.pace.to_string();
+        let width = (text.len() as u32 * 4 + 1) * INDICATOR_SCALE;
+        let height = 7 * INDICATOR_SCALE;
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        let _ = self.canvas.fill_rect(Rect::new(0, 0, width, height));
+
+        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        for (i, c) in text.chars().enumerate() {
+            for (row, bits) in indicator_glyph(c).iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) == 0 {
+                        continue;
+                    }
+                    let x = (i as u32 * 4 + column + 1) * INDICATOR_SCALE;
+                    let y = (row as u32 + 1) * INDICATOR_SCALE;
+                    let _ = self.canvas.fill_rect(Rect::new(
+                        x as i32,
+                        y as i32,
+                        INDICATOR_SCALE,
+                        INDICATOR_SCALE
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Signals to the Cpu when keys are pressed
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    /// * `key` - The host key that was pressed
+    /// * `chip8_key` - The key of the keypad it is bound to
+    ///
+    fn press(&mut self, cpu: &mut Cpu, key: HostKey, chip8_key: u8) {
+        if !self.held.contains(&(key, chip8_key)) {
+            self.held.push((key, chip8_key));
+        }
+        cpu.keys[chip8_key as usize] = KeyState::Pressed;
+    }
+
+    /// Signals to the Cpu when keys are released
+    /// A key of the keypad bound to several host keys stays pressed until
+    /// all of them are released
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    /// * `key` - The host key that was released
+    ///
+    fn release(&mut self, cpu: &mut Cpu, key: HostKey) {
+        let released: Vec<u8> = self.held.iter()
+            .filter(|&&(held, _)| held == key)
+            .map(|&(_, chip8_key)| chip8_key)
+            .collect();
+        self.held.retain(|&(held, _)| held != key);
+        for chip8_key in released {
+            if self.held.iter().all(|&(_, held)| held != chip8_key) {
+                cpu.keys[chip8_key as usize] = KeyState::Released;
+            }
+        }
+    }
+
+    /// Does what a host key is bound to when it is pressed
+    /// Returns the command of a hotkey, unless the press only repeats
+    /// because the key is held
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    /// * `key` - The host key that was pressed
+    /// * `keymod` - The modifiers held along with it
+    /// * `repeat` - Whether the key was already held
+    ///
+    fn key_down(
+        &mut self,
+        cpu: &mut Cpu,
+        key: HostKey,
+        keymod: Mod,
+        repeat: bool
+    ) -> Option<Command> {
+        match self.action(key, keymod) {
+            Some(Action::Keypad(chip8_key)) => self.press(cpu, key, chip8_key),
+            Some(Action::Hotkey(Command::Rewind)) => self.rewinding = true,
+            Some(Action::Hotkey(command)) if !repeat => return Some(command),
+            Some(Action::Fullscreen) if !repeat => {
+                self.toggle_fullscreen();
+                cpu.draw_screen = true;
+            },
+            Some(Action::NextPalette) if !repeat => {
+                self.next_palette();
+                cpu.draw_screen = true;
+            },
+            _ => {},
+        }
+        None
+    }
+
+    /// Stops what a host key is bound to when it is released
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    /// * `key` - The host key that was released
+    /// * `keymod` - The modifiers held at the time
+    ///
+    fn key_up(&mut self, cpu: &mut Cpu, key: HostKey, keymod: Mod) {
+        self.release(cpu, key);
+        if self.action(key, keymod) == Some(Action::Hotkey(Command::Rewind)) {
+            self.rewinding = false;
+        }
+    }
+
+    /// Turns the motion of a stick or trigger into presses and releases of
+    /// the two ways it can be pushed
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    /// * `which` - The id SDL gives the game controller in its events
+    /// * `axis` - The stick or trigger that moved
+    /// * `value` - How far it is pushed, from -32768 to 32767
+    ///
+    fn move_axis(
+        &mut self,
+        cpu: &mut Cpu,
+        which: i32,
+        axis: Axis,
+        value: i16
+    ) -> Option<Command> {
+        let pad = self.pad_number(which)?;
+        let mut command = None;
+        for &positive in &[false, true] {
+            let key = HostKey::Axis(pad, axis, positive);
+            let pushed = if positive {
+                value >= AXIS_THRESHOLD
+            }
+            else {
+                value <= -AXIS_THRESHOLD
+            };
+            let was_pushed = self.pushed.contains(&key);
+            if pushed && !was_pushed {
+                self.pushed.push(key);
+                command = command.or(self.key_down(cpu, key, NOMOD, false));
+            }
+            else if !pushed && was_pushed {
+                self.pushed.retain(|&pushed| pushed != key);
+                self.key_up(cpu, key, NOMOD);
+            }
+        }
+        command
+    }
+
+    /// Handles a single event, returning a command if it asked for one
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    /// * `event` - The event polled from the event pump
+    ///
+    fn handle_event(
+        &mut self,
+        cpu: &mut Cpu,
+        event: Event
+    ) -> Option<Command> {
+        match event {
+            Event::Quit {..} => Some(Command::Quit),
+            // The display is redrawn to fit the window whenever its
+            // size changes or it is uncovered
+            Event::Window {
+                win_event: WindowEvent::SizeChanged(..),
+                ..
+            } |
+            Event::Window { win_event: WindowEvent::Exposed, .. } => {
+                cpu.draw_screen = true;
+                None
+            },
+            Event::KeyDown {
+                keycode,
+                scancode,
+                keymod,
+                repeat,
+                ..
+            } => {
+                self.host_key(keycode, scancode).and_then(|key| {
+                    self.key_down(cpu, key, keymod, repeat)
+                })
+            },
+            Event::KeyUp { keycode, scancode, keymod, .. } => {
+                if let Some(key) = self.host_key(keycode, scancode) {
+                    self.key_up(cpu, key, keymod);
+                }
+                None
+            },
+            Event::ControllerDeviceAdded { which, .. } => {
+                self.open_pad(which);
+                None
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.close_pad(cpu, which);
+                None
+            },
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.pad_number(which).and_then(|pad| {
+                    let key = HostKey::Button(pad, button);
+                    self.key_down(cpu, key, NOMOD, false)
+                })
+            },
+            Event::ControllerButtonUp { which, button, .. } => {
+                if let Some(pad) = self.pad_number(which) {
+                    self.key_up(cpu, HostKey::Button(pad, button), NOMOD);
+                }
+                None
+            },
+            Event::ControllerAxisMotion { which, axis, value, .. } => {
+                self.move_axis(cpu, which, axis, value)
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Frontend for Core {
+    /// Polls the Sdl event pump
+    /// Closing the window asks to quit, keys and the inputs of game
+    /// controllers are looked up in the keymap to press keys of the keypad
+    /// or send hotkeys, and game controllers are opened as they are
+    /// plugged in
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    ///
+    fn poll_input(&mut self, cpu: &mut Cpu) -> Option<Command> {
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        let command = frontend::handle_batch(events, |event| {
+            self.handle_event(cpu, event)
+        });
+        // Fading pixels change every frame, whether the rom draws or not
+        if !self.phosphor.settled() {
+            cpu.draw_screen = true;
+        }
+        if command.is_none() && self.rewinding {
+            return Some(Command::Rewind);
+        }
+        command
+    }
+
+    /// Redraws the screen
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   read from the display values
+    ///   This tells the core which values to color black and which to color
+    ///   white
+    ///
+    fn draw(&mut self, cpu: &Cpu) {
+        // The window is cleared first so that whatever the display does
+        // not cover is black, then the display is filled with the color
+        // of the pixels with no plane set
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        let view = self.viewport(cpu.width(), cpu.height());
+        let palette = &self.palettes[self.palette];
+        let background = palette.colors[0];
+        let colors = self.phosphor.apply(&cpu.display, palette);
+        let [r, g, b] = background;
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        let _ = self.canvas.fill_rect(view);
+        let width = cpu.width() as usize;
+        let height = cpu.height() as usize;
+        // The edges of each pixel are worked out on their own, so that a
+        // fitted scale spreads the window pixels left over evenly
+        let column = |x: usize| {
+            view.x() + (x * view.width() as usize / width) as i32
+        };
+        let row = |y: usize| {
+            view.y() + (y * view.height() as usize / height) as i32
+        };
+        for (i, &color) in colors.iter().enumerate() {
+            if color == background {
+                continue;
+            }
+            let (x, y) = (i % width, i / width);
+
+            let [r, g, b] = color;
+            self.canvas.set_draw_color(Color::RGB(r, g, b));
+            let _ = self.canvas.fill_rect(Rect::new(
+                column(x),
+                row(y),
+                (column(x + 1) - column(x)) as u32,
+                (row(y + 1) - row(y)) as u32
+            ));
+        }
+        if !self.pace.is_normal() {
+            self.draw_indicator();
+        }
+        self.canvas.present();
+    }
+
+    fn set_pace(&mut self, pace: Pace) {
+        self.pace = pace;
+    }
+
+    /// With vsync enabled, presenting the canvas waits for the display
+    fn syncs_to_vblank(&self) -> bool {
+        self.vsync
+    }
+
+    /// Plays a basic sound byte using the Sdl AudioDevice
+    fn play_sound(&mut self) {
+        self.audio_device.resume();
+    }
+
+    /// Stops a basic sound byte using the Sdl AudioDevice
+    fn stop_sound(&mut self) {
+        self.audio_device.pause();
+    }
+
+    /// Switches the Sdl AudioDevice over to a new waveform
+    fn set_audio_pattern(&mut self, pattern: &[u8; 16], rate: f32) {
+        self.audio_device.lock().set_pattern(pattern, rate);
+    }
+}
@@ -0,0 +1,379 @@
+use std::fmt::Write as FmtWrite;
+use std::io;
+use std::io::prelude::*;
+use std::mem;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use libc;
+
+use cpu::Cpu;
+use frontend::{Command, Frontend};
+use scheduler::Pace;
+use super::{KeyState, Pixel};
+
+/// How long a key stays pressed after the terminal last sent it, by default
+/// Terminals only report key presses, and repeat them while a key is held,
+/// so a key counts as released once it has not been repeated for this long
+/// The first repeat comes after the key repeat delay, which is 500 to 660
+/// ms on most systems, so a shorter timeout would release held keys once
+pub const DEFAULT_RELEASE_TIMEOUT: Duration = Duration::from_millis(700);
+
+/// The byte terminals send for the escape key, which also starts the
+/// sequences sent for function and arrow keys
+const ESCAPE: u8 = 0x1b;
+/// The byte sent for Ctrl-C once the terminal is in raw mode
+const CTRL_C: u8 = 0x03;
+/// The byte most terminals send for the backspace key
+const BACKSPACE: u8 = 0x7f;
+
+/// The characters used to draw the display in a terminal
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rendering {
+    /// ▀ and ▄ blocks, two pixels to a character, in color
+    #[default]
+    HalfBlocks,
+    /// Braille patterns, eight pixels to a character, in black and white
+    /// This fits the high resolution display into small terminals
+    Braille,
+}
+
+impl FromStr for Rendering {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half" => Ok(Rendering::HalfBlocks),
+            "braille" => Ok(Rendering::Braille),
+            _ => Err(format!(
+                "Unknown rendering '{}', expected half or braille",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the key of the keypad a typed character stands for, using the
+/// same layout as the SDL frontend
+fn keypad_key(byte: u8) -> Option<usize> {
+    let key = match byte.to_ascii_lowercase() {
+        b'1' => 0x1,
+        b'2' => 0x2,
+        b'3' => 0x3,
+        b'4' => 0xC,
+        b'q' => 0x4,
+        b'w' => 0x5,
+        b'e' => 0x6,
+        b'r' => 0xD,
+        b'a' => 0x7,
+        b's' => 0x8,
+        b'd' => 0x9,
+        b'f' => 0xE,
+        b'z' => 0xA,
+        b'x' => 0x0,
+        b'c' => 0xB,
+        b'v' => 0xF,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Returns the command a speed hotkey stands for
+fn speed_command(byte: u8) -> Option<Command> {
+    let command = match byte {
+        b'p' | b'P' => Command::Pause,
+        b'.' => Command::FrameAdvance,
+        b'\t' => Command::Turbo,
+        b'-' => Command::SlowDown,
+        b'=' => Command::SpeedUp,
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// Returns the ANSI color number of a pixel, in the shades of grey of the
+/// classic palette whatever palette is chosen, since terminals pick the
+/// actual colors of their ANSI colors themselves
+fn ansi_color(pixel: Pixel) -> u8 {
+    match pixel {
+        Pixel::Black => 0,
+        Pixel::White => 15,
+        Pixel::SecondPlane => 8,
+        Pixel::BothPlanes => 7,
+    }
+}
+
+/// A frontend that draws in the terminal it was started from and reads
+/// the keypad from the keyboard, for machines where no window can be
+/// opened, e.g. over SSH
+/// The terminal is put into raw mode for as long as the frontend exists,
+/// and restored when it is dropped
+pub struct Terminal {
+    rendering: Rendering,
+    release_timeout: Duration,
+    /// The settings of the terminal before raw mode, restored on drop
+    original: libc::termios,
+    /// When each key of the keypad was last sent, if it is held
+    pressed: [Option<Instant>; 16],
+    /// When backspace was last sent, if it is held
+    rewinding: Option<Instant>,
+    /// Whether the bell has already rung for the current sound
+    sounding: bool,
+    /// The size of the display at the last draw, to clear the terminal
+    /// when the resolution changes
+    size: (u32, u32),
+    pace: Pace,
+}
+
+impl Terminal {
+    /// Puts the terminal into raw mode and clears it
+    /// Fails if standard input is not a terminal
+    ///
+    /// # Arguments
+    ///
+    /// * `rendering` - The characters to draw the display with
+    /// * `release_timeout` - How long a key stays pressed after it was last
+    ///   sent; it should be longer than the delay before the terminal starts
+    ///   repeating a held key
+    ///
+    pub fn new(
+        rendering: Rendering,
+        release_timeout: Duration
+    ) -> io::Result<Self> {
+        let original = unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            termios
+        };
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        // Reads return straight away, with whatever has been typed
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        unsafe {
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        // Switches to the alternate screen and hides the cursor
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        stdout.flush()?;
+
+        Ok(Terminal {
+            rendering,
+            release_timeout,
+            original,
+            pressed: [None; 16],
+            rewinding: None,
+            sounding: false,
+            size: (0, 0),
+            pace: Pace::default(),
+        })
+    }
+
+    /// Reads everything typed since the last call, without waiting
+    fn read_input(&self) -> Vec<u8> {
+        let mut input = vec![];
+        let mut buffer = [0u8; 64];
+        loop {
+            let read = unsafe {
+                libc::read(
+                    libc::STDIN_FILENO,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len()
+                )
+            };
+            if read <= 0 {
+                break;
+            }
+            input.extend_from_slice(&buffer[..read as usize]);
+        }
+        input
+    }
+
+    /// Draws the display with half blocks, the upper pixel in the
+    /// foreground color and the lower one in the background color
+    fn render_half_blocks(&self, cpu: &Cpu, out: &mut String) {
+        let width = cpu.width() as usize;
+        let pixel = |x: usize, y: usize| cpu.display[y * width + x];
+        let mut colors = None;
+        for y in (0..cpu.height() as usize).step_by(2) {
+            for x in 0..width {
+                let top = ansi_color(pixel(x, y));
+                let bottom = ansi_color(pixel(x, y + 1));
+                if colors != Some((top, bottom)) {
+                    let _ = write!(out, "\x1b[38;5;{};48;5;{}m", top, bottom);
+                    colors = Some((top, bottom));
+                }
+                out.push('▀');
+            }
+            out.push_str("\x1b[0m\r\n");
+            colors = None;
+        }
+    }
+
+    /// Draws the display with braille patterns, each covering a block of
+    /// 2x4 pixels, with every pixel that is not black shown as a dot
+    fn render_braille(&self, cpu: &Cpu, out: &mut String) {
+        // The bit of the pattern for each pixel of the block, by row
+        const DOTS: [[u32; 2]; 4] = [
+            [0x01, 0x08],
+            [0x02, 0x10],
+            [0x04, 0x20],
+            [0x40, 0x80],
+        ];
+        let width = cpu.width() as usize;
+        let height = cpu.height() as usize;
+        for y in (0..height).step_by(4) {
+            for x in (0..width).step_by(2) {
+                let mut pattern = 0;
+                for (row, dots) in DOTS.iter().enumerate() {
+                    for (column, dot) in dots.iter().enumerate() {
+                        let i = (y + row) * width + x + column;
+                        let lit = y + row < height
+                            && cpu.display[i] != Pixel::Black;
+                        if lit {
+                            pattern |= dot;
+                        }
+                    }
+                }
+                out.push(char::from_u32(0x2800 + pattern).unwrap_or(' '));
+            }
+            out.push_str("\r\n");
+        }
+    }
+}
+
+impl Drop for Terminal {
+    /// Leaves the alternate screen and restores the settings of the
+    /// terminal, so that the shell works again afterwards
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        unsafe {
+            let original = &self.original;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+        }
+    }
+}
+
+impl Frontend for Terminal {
+    /// Reads whatever was typed since the last call
+    /// Escape or Ctrl-C asks to quit, and keys of the keypad stay pressed
+    /// until they have not been sent for the release timeout
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    ///
+    fn poll_input(&mut self, cpu: &mut Cpu) -> Option<Command> {
+        let now = Instant::now();
+        let input = self.read_input();
+        let mut command = None;
+        let mut i = 0;
+        while i < input.len() {
+            let byte = input[i];
+            i += 1;
+            match byte {
+                CTRL_C => return Some(Command::Quit),
+                // A lone escape is the escape key, while anything after it
+                // belongs to a sequence for another key, which is skipped
+                ESCAPE if i == input.len() => return Some(Command::Quit),
+                ESCAPE => {
+                    if input[i] == b'[' || input[i] == b'O' {
+                        i += 1;
+                    }
+                    while i < input.len() {
+                        let byte = input[i];
+                        i += 1;
+                        if byte.is_ascii_alphabetic() || byte == b'~' {
+                            break;
+                        }
+                    }
+                },
+                BACKSPACE => self.rewinding = Some(now),
+                _ => {
+                    if let Some(key) = keypad_key(byte) {
+                        self.pressed[key] = Some(now);
+                    }
+                    else if let Some(speed) = speed_command(byte) {
+                        command = Some(speed);
+                    }
+                },
+            }
+        }
+
+        let timeout = self.release_timeout;
+        let held = |since: Option<Instant>| {
+            since.is_some_and(|since| now.duration_since(since) < timeout)
+        };
+        for (key, since) in self.pressed.iter_mut().enumerate() {
+            if held(*since) {
+                cpu.keys[key] = KeyState::Pressed;
+            }
+            else {
+                cpu.keys[key] = KeyState::Released;
+                *since = None;
+            }
+        }
+        if command.is_none() && held(self.rewinding) {
+            command = Some(Command::Rewind);
+        }
+        command
+    }
+
+    /// Redraws the display in place, followed by the speed unless it is
+    /// normal
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   read from the display values
+    ///
+    fn draw(&mut self, cpu: &Cpu) {
+        let mut out = String::from("\x1b[H");
+        if self.size != (cpu.width(), cpu.height()) {
+            out.insert_str(0, "\x1b[2J");
+            self.size = (cpu.width(), cpu.height());
+        }
+        match self.rendering {
+            Rendering::HalfBlocks => self.render_half_blocks(cpu, &mut out),
+            Rendering::Braille => self.render_braille(cpu, &mut out),
+        }
+        if !self.pace.is_normal() {
+            out.push_str(&self.pace.to_string());
+        }
+        out.push_str("\x1b[K");
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = stdout.write_all(out.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    fn set_pace(&mut self, pace: Pace) {
+        self.pace = pace;
+    }
+
+    /// Rings the bell of the terminal once whenever a sound starts, since
+    /// the bell cannot be held for as long as the sound timer runs
+    fn play_sound(&mut self) {
+        if !self.sounding {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(b"\x07");
+            let _ = stdout.flush();
+            self.sounding = true;
+        }
+    }
+
+    fn stop_sound(&mut self) {
+        self.sounding = false;
+    }
+}
@@ -1,15 +1,35 @@
 use std;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
-use std::time::Duration;
-use rand::{thread_rng, Rng};
-use sdl2::keyboard::Keycode;
-use sdl2::event::Event;
-use sdl2::Sdl;
 
 use core;
-use core::Core;
+use debugger::Debugger;
+use frontend::{Command, Frontend};
+use loader;
+use platform::Platform;
+use quirks::{LoadStoreIndex, Quirks};
+use rewind::Rewind;
+use rng::Random;
+use scheduler::{FrameClock, Pace};
+use savestate::{self, StateError, StateReader, StateWriter};
 
+/// Where roms are loaded into memory and start executing
+pub const PROGRAM_START: u16 = 0x200;
+/// Where the 5-byte font for the hex digits is stored in memory
+const FONT_ADDR: u16 = 0x0;
+/// Where the 10-byte SUPER-CHIP font for the hex digits is stored in memory
+const BIG_FONT_ADDR: u16 = 0x50;
+/// The waveform played by the sound timer until a rom loads its own with
+/// F002, a square wave with a period of 8 samples
+const DEFAULT_AUDIO_PATTERN: [u8; 16] = [0xF0; 16];
+/// The XO-CHIP pitch register value at which the audio pattern is played
+/// back at 4000 samples per second
+const DEFAULT_PITCH: u8 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Opcode {
     CallRCAProgram(u16),
     ClearDisplay,
@@ -46,10 +66,240 @@ pub enum Opcode {
     SetBCD(u16),
     DumpRegister(u16),
     LoadRegister(u16),
+    ScrollDown(u16),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    DisableHighRes,
+    EnableHighRes,
+    SetIndexRegisterToBigSpriteAddr(u16),
+    SaveFlags(u16),
+    LoadFlags(u16),
+    ScrollUp(u16),
+    SaveRegisterRange { first: u16, second: u16 },
+    LoadRegisterRange { first: u16, second: u16 },
+    SetIndexRegisterLong(u16),
+    SelectPlanes(u16),
+    LoadAudioPattern,
+    SetPitch(u16),
+}
+
+impl Opcode {
+    /// Returns the number of bytes the instruction takes up in memory
+    /// Every instruction takes 2 bytes, except for the XO-CHIP long load
+    pub fn size(&self) -> u16 {
+        match *self {
+            Opcode::SetIndexRegisterLong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Returns the bytes the instruction is stored as in memory, which is
+    /// the inverse of Cpu::decode
+    /// Fields wider than the instruction leaves room for are truncated
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::cpu::{Cpu, Opcode};
+    ///
+    /// let opcode = Opcode::SetRegister { addr: 0xA, value: 0x42 };
+    /// assert_eq!(opcode.encode(), vec![0x6A, 0x42]);
+    /// assert_eq!(Cpu::decode(0x6A42), Ok(opcode));
+    /// ```
+    pub fn encode(&self) -> Vec<u8> {
+        let nnn = |base: u16, addr: u16| base | (addr & 0x0FFF);
+        let xnn = |base: u16, x: u16, value: u16| {
+            base | ((x & 0xF) << 8) | (value & 0xFF)
+        };
+        let xyn = |base: u16, x: u16, y: u16, n: u16| {
+            base | ((x & 0xF) << 8) | ((y & 0xF) << 4) | (n & 0xF)
+        };
+
+        let word = match *self {
+            Opcode::CallRCAProgram(addr) => nnn(0x0000, addr),
+            Opcode::ClearDisplay => 0x00E0,
+            Opcode::ReturnFromSubroutine => 0x00EE,
+            Opcode::JumpToAddr(addr) => nnn(0x1000, addr),
+            Opcode::CallSubroutine(addr) => nnn(0x2000, addr),
+            Opcode::SkipIfRegisterSet { addr, value } => {
+                xnn(0x3000, addr, value)
+            },
+            Opcode::SkipIfRegisterNotSet { addr, value } => {
+                xnn(0x4000, addr, value)
+            },
+            Opcode::SkipIfRegistersEqual { first, second } => {
+                xyn(0x5000, first, second, 0x0)
+            },
+            Opcode::SetRegister { addr, value } => xnn(0x6000, addr, value),
+            Opcode::AddToRegister { addr, value } => xnn(0x7000, addr, value),
+            Opcode::AssignRegister { first, second } => {
+                xyn(0x8000, first, second, 0x0)
+            },
+            Opcode::AssignRegisterBitwiseOr { first, second } => {
+                xyn(0x8000, first, second, 0x1)
+            },
+            Opcode::AssignRegisterBitwiseAnd { first, second } => {
+                xyn(0x8000, first, second, 0x2)
+            },
+            Opcode::AssignRegisterBitwiseXor { first, second } => {
+                xyn(0x8000, first, second, 0x3)
+            },
+            Opcode::AddRegisters { first, second } => {
+                xyn(0x8000, first, second, 0x4)
+            },
+            Opcode::SubtractRegisters { first, second } => {
+                xyn(0x8000, first, second, 0x5)
+            },
+            Opcode::AssignRegisterBitshiftRight { first, second } => {
+                xyn(0x8000, first, second, 0x6)
+            },
+            Opcode::SubtractFirstRegister { first, second } => {
+                xyn(0x8000, first, second, 0x7)
+            },
+            Opcode::AssignRegistersBitshiftLeft { first, second } => {
+                xyn(0x8000, first, second, 0xE)
+            },
+            Opcode::SkipIfRegistersNotEqual { first, second } => {
+                xyn(0x9000, first, second, 0x0)
+            },
+            Opcode::SetIndexRegister(addr) => nnn(0xA000, addr),
+            Opcode::SetProgramCounter(addr) => nnn(0xB000, addr),
+            Opcode::AssignRandomValue { addr, value } => {
+                xnn(0xC000, addr, value)
+            },
+            Opcode::Draw { x, y, height } => xyn(0xD000, x, y, height),
+            Opcode::SkipIfKeyPressed(addr) => xnn(0xE000, addr, 0x9E),
+            Opcode::SkipIfKeyNotPressed(addr) => xnn(0xE000, addr, 0xA1),
+            Opcode::AssignToDelayTime(addr) => xnn(0xF000, addr, 0x07),
+            Opcode::AssignOnKeyPress(addr) => xnn(0xF000, addr, 0x0A),
+            Opcode::SetDelayTimer(addr) => xnn(0xF000, addr, 0x15),
+            Opcode::SetSoundTimer(addr) => xnn(0xF000, addr, 0x18),
+            Opcode::AddToIndexRegister(addr) => xnn(0xF000, addr, 0x1E),
+            Opcode::SetIndexRegisterToSpriteAddr(addr) => {
+                xnn(0xF000, addr, 0x29)
+            },
+            Opcode::SetBCD(addr) => xnn(0xF000, addr, 0x33),
+            Opcode::DumpRegister(addr) => xnn(0xF000, addr, 0x55),
+            Opcode::LoadRegister(addr) => xnn(0xF000, addr, 0x65),
+            Opcode::ScrollDown(rows) => xyn(0x0000, 0x0, 0xC, rows),
+            Opcode::ScrollRight => 0x00FB,
+            Opcode::ScrollLeft => 0x00FC,
+            Opcode::Exit => 0x00FD,
+            Opcode::DisableHighRes => 0x00FE,
+            Opcode::EnableHighRes => 0x00FF,
+            Opcode::SetIndexRegisterToBigSpriteAddr(addr) => {
+                xnn(0xF000, addr, 0x30)
+            },
+            Opcode::SaveFlags(addr) => xnn(0xF000, addr, 0x75),
+            Opcode::LoadFlags(addr) => xnn(0xF000, addr, 0x85),
+            Opcode::ScrollUp(rows) => xyn(0x0000, 0x0, 0xD, rows),
+            Opcode::SaveRegisterRange { first, second } => {
+                xyn(0x5000, first, second, 0x2)
+            },
+            Opcode::LoadRegisterRange { first, second } => {
+                xyn(0x5000, first, second, 0x3)
+            },
+            Opcode::SetIndexRegisterLong(addr) => {
+                return vec![0xF0, 0x00, (addr >> 8) as u8, addr as u8];
+            },
+            Opcode::SelectPlanes(planes) => xnn(0xF000, planes, 0x01),
+            Opcode::LoadAudioPattern => 0xF002,
+            Opcode::SetPitch(addr) => xnn(0xF000, addr, 0x3A),
+        };
+        vec![(word >> 8) as u8, word as u8]
+    }
 }
 
+/// The reasons the Cpu can refuse to go on running a rom
+/// Every error raised by an instruction carries the address the
+/// instruction was fetched from and its opcode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CpuError {
-    IllegalInstruction(u16),
+    /// The opcode is not an instruction, or not one of the platform
+    IllegalInstruction { pc: u16, opcode: u16 },
+    /// 2NNN was executed with all 16 levels of the stack in use
+    StackOverflow { pc: u16, opcode: u16 },
+    /// 00EE was executed with nothing on the stack
+    StackUnderflow { pc: u16, opcode: u16 },
+    /// The program counter left memory, so that no instruction can be
+    /// fetched from it
+    PcOutOfBounds { pc: u16 },
+    /// An instruction reading or writing memory from I would go past the
+    /// end of memory
+    IndexOutOfBounds { pc: u16, opcode: u16, index: u16 },
+    /// EX9E or EXA1 asked for a key above 0xF
+    InvalidKey { pc: u16, opcode: u16, key: u8 },
+    /// The rom does not fit in memory after the address it is loaded at
+    RomTooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CpuError::IllegalInstruction { pc, opcode } => write!(
+                f,
+                "Illegal instruction {:04x} at {:#05x}",
+                opcode,
+                pc
+            ),
+            CpuError::StackOverflow { pc, opcode } => write!(
+                f,
+                "Stack overflow: {:04x} at {:#05x} calls a subroutine more \
+                 than 16 levels deep",
+                opcode,
+                pc
+            ),
+            CpuError::StackUnderflow { pc, opcode } => write!(
+                f,
+                "Stack underflow: {:04x} at {:#05x} returns from outside of \
+                 any subroutine",
+                opcode,
+                pc
+            ),
+            CpuError::PcOutOfBounds { pc } => {
+                write!(f, "The program counter ran off memory at {:#05x}", pc)
+            },
+            CpuError::IndexOutOfBounds { pc, opcode, index } => write!(
+                f,
+                "{:04x} at {:#05x} accesses memory past the end from \
+                 I = {:#05x}",
+                opcode,
+                pc,
+                index
+            ),
+            CpuError::InvalidKey { pc, opcode, key } => write!(
+                f,
+                "{:04x} at {:#05x} checks key {:#04x}, which does not exist",
+                opcode,
+                pc,
+                key
+            ),
+            CpuError::RomTooLarge { size, max } => write!(
+                f,
+                "The rom is {} bytes, but at most {} bytes fit in memory",
+                size,
+                max
+            ),
+        }
+    }
+}
+
+impl Error for CpuError {}
+
+/// Whether an instruction read from or wrote to memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A single byte of memory touched by an instruction, not counting the
+/// fetch of the instruction itself
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub addr: u16,
+    pub kind: AccessKind,
 }
 
 pub struct Cpu {
@@ -59,39 +309,256 @@ pub struct Cpu {
     pub keys: Vec<core::KeyState>,
     delay_timer: u8,
     sound_timer: u8,
-    counter: u8,
     pub display: Vec<core::Pixel>,
+    display_width: u32,
+    display_height: u32,
     pub draw_screen: bool,
     stack: Vec<u16>,
     sp: u16,
     memory: Vec<u8>,
+    platform: Platform,
+    quirks: Quirks,
+    vblank: bool,
+    flags: Vec<u8>,
+    halted: bool,
+    planes: u8,
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    audio_changed: bool,
+    trace_memory: bool,
+    memory_accesses: Vec<MemoryAccess>,
+    rom_hash: u64,
+    rom_path: Option<String>,
+    load_addr: u16,
+    rng: Random,
+    cycles: u64,
+    rewind: Option<Rewind>,
+    speed: u32,
 }
 
 impl Cpu {
-    /// Creates a new Cpu
+    /// Creates a new Cpu with the default quirks
     /// The program counter starts at 0x200
     /// Vectors are used instead of arrays for flexibility
     pub fn new() -> Self {
+        Cpu::with_quirks(Quirks::default())
+    }
+
+    /// Creates a new Cpu that follows the given quirks
+    ///
+    /// # Arguments
+    ///
+    /// * `quirks` - The behaviors of the interpreter the rom was written for
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::cpu::Cpu;
+    /// use chip8_rust::quirks::Profile;
+    ///
+    /// let cpu = Cpu::with_quirks(Profile::SuperChip.quirks());
+    /// assert!(cpu.quirks().jump_uses_vx);
+    /// ```
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Cpu::with_platform(Platform::Chip8, quirks)
+    }
+
+    /// Creates a new Cpu for the given platform that follows the given
+    /// quirks
+    ///
+    /// # Arguments
+    ///
+    /// * `platform` - The instruction set the rom was written for
+    /// * `quirks` - The behaviors of the interpreter the rom was written for
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::cpu::Cpu;
+    /// use chip8_rust::platform::Platform;
+    ///
+    /// let platform = Platform::SuperChip;
+    /// let quirks = platform.default_profile().quirks();
+    /// let cpu = Cpu::with_platform(platform, quirks);
+    /// assert_eq!(cpu.width(), 64);
+    /// ```
+    pub fn with_platform(platform: Platform, quirks: Quirks) -> Self {
         Cpu {
-            pc: 0x200,
+            pc: PROGRAM_START,
             index_reg: 0,
             registers: vec![0; 16],
             keys: vec![core::KeyState::Released; 16],
             delay_timer: 0,
             sound_timer: 0,
-            counter: 10,
             display: vec![
                 core::Pixel::Black;
                 (core::DISPLAY_WIDTH*core::DISPLAY_HEIGHT) as usize
             ],
+            display_width: core::DISPLAY_WIDTH,
+            display_height: core::DISPLAY_HEIGHT,
             draw_screen: false,
             stack: vec![0; 16],
             sp: 0,
-            memory: vec![0; 4096],
+            memory: vec![0; platform.memory_size()],
+            platform,
+            quirks,
+            vblank: true,
+            flags: vec![0; 16],
+            halted: false,
+            planes: 0b01,
+            audio_pattern: DEFAULT_AUDIO_PATTERN,
+            pitch: DEFAULT_PITCH,
+            audio_changed: true,
+            trace_memory: false,
+            memory_accesses: vec![],
+            rom_hash: savestate::hash_rom(&[]),
+            rom_path: None,
+            load_addr: PROGRAM_START,
+            rng: Random::from_entropy(),
+            cycles: 0,
+            rewind: None,
+            speed: platform.default_speed(),
+        }
+    }
+
+    /// Returns the platform the Cpu is emulating
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Returns the address of the next instruction to execute
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Returns the value of the index register I
+    pub fn index_register(&self) -> u16 {
+        self.index_reg
+    }
+
+    /// Returns the values of V0 through VF
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+
+    /// Returns the return addresses currently on the stack, oldest first
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    /// Returns the value of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Returns the value of the sound timer
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Returns the whole addressable memory
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Sets the program counter, the address of the next instruction to
+    /// execute
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address of the first byte of the instruction
+    ///
+    pub fn set_pc(&mut self, addr: u16) {
+        self.pc = addr;
+    }
+
+    /// Sets the value of the index register I
+    pub fn set_index_register(&mut self, value: u16) {
+        self.index_reg = value;
+    }
+
+    /// Sets the value of one of the registers V0 through VF
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The index of the register, from 0x0 to 0xF
+    /// * `value` - The value to store in it
+    ///
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.registers[x] = value;
+    }
+
+    /// Copies bytes into memory, without going through an instruction
+    /// Together with the other setters, this puts the Cpu in any state
+    /// without needing a rom
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to copy the first byte to
+    /// * `bytes` - The bytes to copy
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::cpu::Cpu;
+    ///
+    /// // 8014 adds V1 to V0
+    /// let mut cpu = Cpu::new();
+    /// cpu.set_memory(0x200, &[0x80, 0x14]);
+    /// cpu.set_register(0x0, 2);
+    /// cpu.set_register(0x1, 3);
+    /// cpu.step().unwrap();
+    /// assert_eq!(cpu.registers()[0x0], 5);
+    /// ```
+    pub fn set_memory(&mut self, addr: u16, bytes: &[u8]) {
+        let start = addr as usize;
+        self.memory[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Returns the instruction stored at an address in memory, without
+    /// executing it
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address of the first byte of the instruction
+    ///
+    pub fn instruction_at(&self, addr: u16) -> Result<Opcode, CpuError> {
+        let fits = |len: usize| addr as usize + len <= self.memory.len();
+        if !fits(2) {
+            return Err(CpuError::PcOutOfBounds { pc: addr });
         }
+        let opcode = self.fetch(addr);
+        if opcode == 0xF000 && self.platform == Platform::XoChip {
+            if !fits(4) {
+                return Err(CpuError::PcOutOfBounds { pc: addr });
+            }
+            // XO-CHIP stores the address to load into I in the next word
+            return Ok(Opcode::SetIndexRegisterLong(self.fetch(addr + 2)));
+        }
+        Cpu::decode(opcode)
+            .map_err(|_| CpuError::IllegalInstruction { pc: addr, opcode })
+    }
+
+    /// Turns the recording of memory accesses on or off
+    /// While on, memory_accesses() lists what the last instruction touched
+    pub fn set_memory_tracing(&mut self, enabled: bool) {
+        self.trace_memory = enabled;
+        self.memory_accesses.clear();
+    }
+
+    /// Returns the memory accessed by the last instruction, if memory
+    /// tracing is enabled
+    pub fn memory_accesses(&self) -> &[MemoryAccess] {
+        &self.memory_accesses
+    }
+
+    /// Returns the quirks the Cpu is following
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
     }
 
-    /// Loads the Chip-8 fontset into Cpu memory
+    /// Loads the Chip-8 fontset into Cpu memory, followed by the larger
+    /// SUPER-CHIP fontset
     pub fn load_fontset(&mut self) {
         let fontset: [u8; 80] = [
             0xF0, 0x90, 0x90, 0x90, 0xF0,
@@ -111,19 +578,121 @@ impl Cpu {
             0xF0, 0x80, 0xF0, 0x80, 0xF0,
             0xF0, 0x80, 0xF0, 0x80, 0x80,
         ];
-        for i in 0..80 {
-            self.memory[i] = fontset[i];
+        let big_fontset: [u8; 160] = [
+            0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C,
+            0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C,
+            0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF,
+            0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C,
+            0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C,
+            0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C,
+            0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60,
+            0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C,
+            0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C,
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3,
+            0xFE, 0xFF, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFF, 0xFE,
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C,
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0,
+        ];
+        let font_addr = FONT_ADDR as usize;
+        let big_font_addr = BIG_FONT_ADDR as usize;
+        self.memory[font_addr..font_addr + 80].copy_from_slice(&fontset);
+        self.memory[big_font_addr..big_font_addr + 160]
+            .copy_from_slice(&big_fontset);
+    }
+
+    /// Returns the address roms are loaded at and start executing from
+    pub fn load_address(&self) -> u16 {
+        self.load_addr
+    }
+
+    /// Changes the address roms are loaded at and start executing from,
+    /// and moves the program counter there
+    /// Nearly every rom expects 0x200, but ETI-660 roms start at 0x600
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address of the first byte of the rom
+    ///
+    pub fn set_load_address(&mut self, addr: u16) {
+        self.load_addr = addr;
+        self.pc = addr;
+    }
+
+    /// Loads the selected Chip-8 rom into Cpu memory, from a file or from
+    /// standard input if the path is `-`
+    /// Roms compressed with gzip or packed in a zip archive are unpacked
+    /// first; see loader::unpack()
+    /// A rom too large for the memory of the platform is refused with an
+    /// error of kind InvalidData wrapping CpuError::RomTooLarge
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the rom or archive, or `-`
+    ///
+    pub fn load_rom(&mut self, path: &str) -> io::Result<()> {
+        let rom = loader::read_rom(path)?;
+        self.load_rom_bytes(&rom)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if path != "-" {
+            self.rom_path = Some(path.to_string());
         }
+        Ok(())
     }
 
-    /// Loads the contents of the selected Chip-8 rom into Cpu memory
-    pub fn load_rom(&mut self, path: &str) -> std::io::Result<()> {
-        let mut rom = File::open(path)?;
-        let mut buffer = vec![];
-        rom.read_to_end(&mut buffer)?;
-        for i in 0..buffer.len() {
-            self.memory[i + 512] = buffer[i];
+    /// Loads a rom from a stream into Cpu memory, unpacking it if it is an
+    /// archive
+    /// Errors are reported as by load_rom()
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A file, standard input or anything else that reads
+    ///
+    pub fn load_rom_from<R: Read + ?Sized>(
+        &mut self,
+        reader: &mut R
+    ) -> io::Result<()> {
+        let rom = loader::read_rom_from(reader)?;
+        self.load_rom_bytes(&rom)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Copies a rom into Cpu memory at the load address and points the
+    /// program counter at its start
+    /// The rom is taken as it is, without unpacking it
+    ///
+    /// # Arguments
+    ///
+    /// * `rom` - The bytes of the rom
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::cpu::{Cpu, CpuError};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.set_load_address(0x600);
+    /// cpu.load_rom_bytes(&[0x16, 0x00]).unwrap();
+    /// assert_eq!(cpu.pc(), 0x600);
+    /// assert_eq!(cpu.memory()[0x600], 0x16);
+    ///
+    /// // A Chip-8 has 4 KiB of memory, 0xA00 bytes of which follow 0x600
+    /// assert_eq!(
+    ///     cpu.load_rom_bytes(&[0; 0xA01]),
+    ///     Err(CpuError::RomTooLarge { size: 0xA01, max: 0xA00 })
+    /// );
+    /// ```
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), CpuError> {
+        let start = self.load_addr as usize;
+        let max = self.memory.len().saturating_sub(start);
+        if rom.len() > max {
+            return Err(CpuError::RomTooLarge { size: rom.len(), max });
         }
+        self.memory[start..start + rom.len()].copy_from_slice(rom);
+        self.rom_hash = savestate::hash_rom(rom);
+        self.pc = self.load_addr;
         Ok(())
     }
 
@@ -132,83 +701,112 @@ impl Cpu {
     /// the Cpu to execute
     /// If the opcode is illegal, an error is returned and the program is
     /// aborted
+    /// The instructions of every platform are decoded, so it is up to the
+    /// caller to check Platform::supports()
+    /// The 4-byte XO-CHIP instruction F000 NNNN cannot be decoded from its
+    /// first word alone; see instruction_at()
+    /// The opcode is not tied to an address, so an error reports the PC as
+    /// 0; instruction_at() reports the real one
     ///
     /// # Arguments
     ///
     /// * `opcode` - An unsigned 16-bit integer that is to be verified by
-    /// the function
+    ///   the function
     ///
     /// # Example 1: Legal opcode
     ///
     /// ```
-    /// let cpu = new Cpu::new();
-    /// match cpu.decode(0x00E0) {
-    ///     Ok(cpu::Opcode::ClearDisplay) => {
+    /// use chip8_rust::cpu::{Cpu, CpuError, Opcode};
+    ///
+    /// match Cpu::decode(0x00E0) {
+    ///     Ok(Opcode::ClearDisplay) => {
     ///         // 0x00E0 is a legal opcode and corresponds to this function
     ///         // Therefore this code will be executed
     ///     },
-    ///     Err(cpu::CpuError::IllegalInstruction(opcode)) => {
-    ///         panic!("Illegal CPU instruction: {:x}", opcode)
-    ///    },
+    ///     Ok(_) => unreachable!(),
+    ///     Err(err) => panic!("{}", err),
     /// }
     /// ```
     ///
     /// # Example 2: Illegal opcode
     ///
-    /// ```
-    /// let cpu = new Cpu::new();
-    /// match cpu.decode(0xE100) {
-    ///     Ok(cpu::Opcode::ClearDisplay) => {},
-    ///     Err(cpu::CpuError::IllegalInstruction(opcode)) => {
+    /// ```should_panic
+    /// use chip8_rust::cpu::{Cpu, CpuError};
+    ///
+    /// match Cpu::decode(0xE100) {
+    ///     Ok(_) => {},
+    ///     Err(CpuError::IllegalInstruction { opcode, .. }) => {
     ///         // 0xE100 is an illegal opcode
     ///         // Therefore the program will terminate
     ///         panic!("Illegal CPU instruction: {:x}", opcode)
     ///    },
+    ///     Err(_) => unreachable!(),
     /// }
     /// ```
     ///
     /// NOTE: Other opcodes not included in match statement for brevity
-    /// NOTE: This example uses the function as a public method, but the
-    /// function is in fact private and should be used within the context
-    /// of the Cpu object
-    fn decode(&mut self, opcode: u16) -> Result<Opcode, CpuError> {
+    pub fn decode(opcode: u16) -> Result<Opcode, CpuError> {
         match opcode {
             0x00E0 => Ok(Opcode::ClearDisplay),
             0x00EE => Ok(Opcode::ReturnFromSubroutine),
-            0x0000...0x0FFF => Ok(Opcode::CallRCAProgram(opcode & 0x0FFF)),
-            0x1000...0x1FFF => Ok(Opcode::JumpToAddr(opcode & 0x0FFF)),
-            0x2000...0x2FFF => Ok(Opcode::CallSubroutine(opcode & 0x0FFF)),
-            0x3000...0x3FFF => {
+            0x00C0..=0x00CF => Ok(Opcode::ScrollDown(opcode & 0x000F)),
+            0x00D0..=0x00DF => Ok(Opcode::ScrollUp(opcode & 0x000F)),
+            0x00FB => Ok(Opcode::ScrollRight),
+            0x00FC => Ok(Opcode::ScrollLeft),
+            0x00FD => Ok(Opcode::Exit),
+            0x00FE => Ok(Opcode::DisableHighRes),
+            0x00FF => Ok(Opcode::EnableHighRes),
+            0x0000..=0x0FFF => Ok(Opcode::CallRCAProgram(opcode & 0x0FFF)),
+            0x1000..=0x1FFF => Ok(Opcode::JumpToAddr(opcode & 0x0FFF)),
+            0x2000..=0x2FFF => Ok(Opcode::CallSubroutine(opcode & 0x0FFF)),
+            0x3000..=0x3FFF => {
                 Ok(Opcode::SkipIfRegisterSet {
                     addr: (opcode & 0x0F00) >> 8,
                     value: opcode & 0x00FF,
                 })
             },
-            0x4000...0x4FFF => {
+            0x4000..=0x4FFF => {
                 Ok(Opcode::SkipIfRegisterNotSet {
                     addr: (opcode & 0x0F00) >> 8,
                     value: opcode & 0x00FF,
                 })
             },
-            0x5000...0x5FFF => {
-                Ok(Opcode::SkipIfRegistersEqual {
-                    first: (opcode & 0x0F00) >> 8,
-                    second: (opcode & 0x00F0) >> 4,
-                })
+            0x5000..=0x5FFF => {
+                match opcode & 0x000F {
+                    0x0000 => {
+                        Ok(Opcode::SkipIfRegistersEqual {
+                            first: (opcode & 0x0F00) >> 8,
+                            second: (opcode & 0x00F0) >> 4,
+                        })
+                    },
+                    0x0002 => {
+                        Ok(Opcode::SaveRegisterRange {
+                            first: (opcode & 0x0F00) >> 8,
+                            second: (opcode & 0x00F0) >> 4,
+                        })
+                    },
+                    0x0003 => {
+                        Ok(Opcode::LoadRegisterRange {
+                            first: (opcode & 0x0F00) >> 8,
+                            second: (opcode & 0x00F0) >> 4,
+                        })
+                    },
+                    _ => Err(CpuError::IllegalInstruction { pc: 0, opcode }),
+                }
             }
-            0x6000...0x6FFF => {
+            0x6000..=0x6FFF => {
                 Ok(Opcode::SetRegister {
                     addr: (opcode & 0x0F00) >> 8,
                     value: opcode & 0x00FF,
                 })
             },
-            0x7000...0x7FFF => {
+            0x7000..=0x7FFF => {
                 Ok(Opcode::AddToRegister {
                     addr: (opcode & 0x0F00) >> 8,
                     value: opcode & 0x00FF,
                 })
             },
-            0x8000...0x8FFF => {
+            0x8000..=0x8FFF => {
                 match opcode & 0x000F {
                     0x0000 => {
                         Ok(Opcode::AssignRegister {
@@ -264,31 +862,36 @@ impl Cpu {
                             second: (opcode & 0x00F0) >> 4,
                         })
                     },
-                    _ => Err(CpuError::IllegalInstruction(opcode)),
+                    _ => Err(CpuError::IllegalInstruction { pc: 0, opcode }),
                 }
             },
-            0x9000...0x9FFF => {
-                Ok(Opcode::SkipIfRegistersNotEqual {
-                    first: (opcode & 0x0F00) >> 8,
-                    second: (opcode & 0x00F0) >> 4,
-                })
+            0x9000..=0x9FFF => {
+                match opcode & 0x000F {
+                    0x0000 => {
+                        Ok(Opcode::SkipIfRegistersNotEqual {
+                            first: (opcode & 0x0F00) >> 8,
+                            second: (opcode & 0x00F0) >> 4,
+                        })
+                    },
+                    _ => Err(CpuError::IllegalInstruction { pc: 0, opcode }),
+                }
             },
-            0xA000...0xAFFF => Ok(Opcode::SetIndexRegister(opcode & 0x0FFF)),
-            0xB000...0xBFFF => Ok(Opcode::SetProgramCounter(opcode & 0x0FFF)),
-            0xC000...0xCFFF => {
+            0xA000..=0xAFFF => Ok(Opcode::SetIndexRegister(opcode & 0x0FFF)),
+            0xB000..=0xBFFF => Ok(Opcode::SetProgramCounter(opcode & 0x0FFF)),
+            0xC000..=0xCFFF => {
                 Ok(Opcode::AssignRandomValue {
                     addr: (opcode & 0x0F00) >> 8,
                     value: opcode & 0x00FF,
                 })
             },
-            0xD000...0xDFFF => {
+            0xD000..=0xDFFF => {
                 Ok(Opcode::Draw {
                     x: (opcode & 0x0F00) >> 8,
                     y: (opcode & 0x00F0) >> 4,
                     height: opcode & 0x000F,
                 })
             },
-            0xE000...0xEFFF => {
+            0xE000..=0xEFFF => {
                 match opcode & 0x00FF {
                     0x009E => {
                         Ok(Opcode::SkipIfKeyPressed((opcode & 0x0F00) >> 8))
@@ -296,11 +899,17 @@ impl Cpu {
                     0x00A1 => {
                         Ok(Opcode::SkipIfKeyNotPressed((opcode & 0x0F00) >> 8))
                     },
-                    _ => Err(CpuError::IllegalInstruction(opcode)),
+                    _ => Err(CpuError::IllegalInstruction { pc: 0, opcode }),
                 }
             },
-            0xF000...0xFFFF => {
+            0xF000..=0xFFFF => {
                 match opcode & 0x00FF {
+                    0x0001 => {
+                        Ok(Opcode::SelectPlanes((opcode & 0x0F00) >> 8))
+                    },
+                    0x0002 if opcode == 0xF002 => {
+                        Ok(Opcode::LoadAudioPattern)
+                    },
                     0x0007 => {
                         Ok(Opcode::AssignToDelayTime((opcode & 0x0F00) >> 8))
                     },
@@ -321,19 +930,32 @@ impl Cpu {
                             (opcode & 0x0F00) >> 8)
                         )
                     },
+                    0x0030 => {
+                        Ok(Opcode::SetIndexRegisterToBigSpriteAddr(
+                            (opcode & 0x0F00) >> 8)
+                        )
+                    },
                     0x0033 => {
                         Ok(Opcode::SetBCD((opcode & 0x0F00) >> 8))
                     },
+                    0x003A => {
+                        Ok(Opcode::SetPitch((opcode & 0x0F00) >> 8))
+                    },
                     0x0055 => {
                         Ok(Opcode::DumpRegister((opcode & 0x0F00) >> 8))
                     },
                     0x0065 => {
                         Ok(Opcode::LoadRegister((opcode & 0x0F00) >> 8))
                     },
-                    _ => Err(CpuError::IllegalInstruction(opcode)),
+                    0x0075 => {
+                        Ok(Opcode::SaveFlags((opcode & 0x0F00) >> 8))
+                    },
+                    0x0085 => {
+                        Ok(Opcode::LoadFlags((opcode & 0x0F00) >> 8))
+                    },
+                    _ => Err(CpuError::IllegalInstruction { pc: 0, opcode }),
                 }
             },
-            _ => Err(CpuError::IllegalInstruction(opcode)),
         }
     }
 
@@ -345,36 +967,52 @@ impl Cpu {
     /// # Example
     ///
     /// ```
-    /// let cpu = new Cpu::new();
+    /// use chip8_rust::cpu::{Cpu, CpuError};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // E100 is not an instruction
+    /// cpu.set_memory(0x200, &[0xE1, 0x00]);
     /// match cpu.step() {
-    ///     Ok(()) => {
-    ///         // Opcode was legal
-    ///     },
-    ///     Err(cpu::CpuError::IllegalInstruction(opcode)) => {
-    ///         // Opcode was illegal
-    ///         // Abort program
-    ///         panic!("Illegal CPU instruction: {:x}", opcode)
+    ///     Ok(()) => unreachable!(),
+    ///     Err(CpuError::IllegalInstruction { pc, opcode }) => {
+    ///         assert_eq!((pc, opcode), (0x200, 0xE100));
     ///    },
+    ///     Err(err) => panic!("{}", err),
     /// }
     /// ```
-    fn step(&mut self) -> Result<(), CpuError> {
-        let opcode_high = self.memory[self.pc as usize];
-        let opcode_low = self.memory[self.pc as usize + 1];
-        let opcode = (opcode_high as u16) << 8 | opcode_low as u16;
-        // println!("Executing: {:x}", opcode);
-        match self.decode(opcode)? {
-            Opcode::CallRCAProgram(addr) => {
-                // This will likely never be run
-                println!("Call RCA Program at {:x}", addr);
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        if self.halted {
+            return Ok(());
+        }
+        self.memory_accesses.clear();
+        let instruction = self.instruction_at(self.pc)?;
+        let opcode = self.fetch(self.pc);
+        if !self.platform.supports(&instruction) {
+            return Err(CpuError::IllegalInstruction { pc: self.pc, opcode });
+        }
+        self.cycles += 1;
+        match instruction {
+            Opcode::CallRCAProgram(_) => {
+                // Machine code routines of the COSMAC VIP cannot be run,
+                // and a rom that runs into zeroed memory lands here too
+                return Err(CpuError::IllegalInstruction {
+                    pc: self.pc,
+                    opcode,
+                });
             },
             Opcode::ClearDisplay => {
+                let planes = self.planes;
                 for pixel in self.display.iter_mut() {
-                    *pixel = core::Pixel::Black;
+                    *pixel = core::Pixel::from_planes(pixel.planes() & !planes);
                 }
                 self.draw_screen = true;
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::ReturnFromSubroutine => {
+                if self.sp == 0 {
+                    let pc = self.pc;
+                    return Err(CpuError::StackUnderflow { pc, opcode });
+                }
                 self.sp -= 1;
                 self.pc = self.stack[self.sp as usize];
             },
@@ -382,356 +1020,1875 @@ impl Cpu {
                 self.pc = addr;
             },
             Opcode::CallSubroutine(addr) => {
-                self.stack[self.sp as usize] = self.pc + 2;
+                if self.sp as usize == self.stack.len() {
+                    let pc = self.pc;
+                    return Err(CpuError::StackOverflow { pc, opcode });
+                }
+                self.stack[self.sp as usize] = self.pc.wrapping_add(2);
                 self.sp += 1;
                 self.pc = addr;
             },
             Opcode::SkipIfRegisterSet { addr, value } => {
                 if self.registers[addr as usize] == value as u8 {
-                    self.pc += 4;
+                    self.skip_next();
                 }
                 else {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             },
             Opcode::SkipIfRegisterNotSet { addr, value } => {
                 if self.registers[addr as usize] != value as u8 {
-                    self.pc += 4;
+                    self.skip_next();
                 }
                 else {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             },
             Opcode::SkipIfRegistersEqual { first, second } => {
                 let initial = self.registers[first as usize];
                 let other = self.registers[second as usize];
                 if initial == other {
-                    self.pc += 4;
+                    self.skip_next();
                 }
                 else {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             }
             Opcode::SetRegister { addr, value } => {
                 self.registers[addr as usize] = value as u8;
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AddToRegister { addr, value } => {
                 let initial_value = self.registers[addr as usize] as u16;
-                let sum = initial_value + value as u16;
+                let sum = initial_value + value;
                 self.registers[addr as usize] = sum as u8;
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AssignRegister { first, second } => {
                 let other = self.registers[second as usize];
                 self.registers[first as usize] = other;
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AssignRegisterBitwiseOr { first, second } => {
                 let initial = self.registers[first as usize];
                 let other = self.registers[second as usize];
                 self.registers[first as usize] = initial | other;
-                self.pc += 2;
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xF] = 0;
+                }
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AssignRegisterBitwiseAnd { first, second } => {
                 let initial = self.registers[first as usize];
                 let other = self.registers[second as usize];
                 self.registers[first as usize] = initial & other;
-                self.pc += 2;
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xF] = 0;
+                }
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AssignRegisterBitwiseXor { first, second } => {
                 let initial = self.registers[first as usize];
                 let other = self.registers[second as usize];
                 self.registers[first as usize] = initial ^ other;
-                self.pc += 2;
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xF] = 0;
+                }
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AddRegisters { first, second } => {
                 let initial = self.registers[first as usize];
                 let other = self.registers[second as usize];
                 let (sum, carry) = initial.overflowing_add(other);
-                if carry {
-                    self.registers[0xF] = 1;
-                }
-                else {
-                    self.registers[0xF] = 0;
-                }
-                self.registers[first as usize] = sum as u8;
-                self.pc += 2;
+                // VF is written last so that the flag wins when X is VF
+                self.registers[first as usize] = sum;
+                self.registers[0xF] = carry as u8;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::SubtractRegisters { first, second } => {
                 let initial = self.registers[first as usize];
                 let other = self.registers[second as usize];
                 let (diff, borrowed) = initial.overflowing_sub(other);
-                if borrowed {
-                    self.registers[0xF] = 0;
-                }
-                else {
-                    self.registers[0xF] = 1;
-                }
-                self.registers[first as usize] = diff as u8;
-                self.pc += 2;
+                self.registers[first as usize] = diff;
+                self.registers[0xF] = !borrowed as u8;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AssignRegisterBitshiftRight { first, second } => {
-                let other = self.registers[second as usize];
+                let source = if self.quirks.shift_uses_vy {
+                    second
+                }
+                else {
+                    first
+                };
+                let other = self.registers[source as usize];
                 let lsb = other & 0b0000_0001;
-                self.registers[0xF] = lsb;
                 self.registers[first as usize] = other >> 1;
-                self.pc += 2;
+                self.registers[0xF] = lsb;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::SubtractFirstRegister { first, second } => {
                 let initial = self.registers[first as usize];
                 let other = self.registers[second as usize];
                 let (diff, borrowed) = other.overflowing_sub(initial);
-                if borrowed {
-                    self.registers[0xF] = 0;
-                }
-                else {
-                    self.registers[0xF] = 1;
-                }
-                self.registers[first as usize] = diff as u8;
+                self.registers[first as usize] = diff;
+                self.registers[0xF] = !borrowed as u8;
 
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AssignRegistersBitshiftLeft { first, second } => {
-                let msb = (self.registers[second as usize] & 0b1000_0000) >> 7;
+                let source = if self.quirks.shift_uses_vy {
+                    second
+                }
+                else {
+                    first
+                };
+                let other = self.registers[source as usize];
+                let msb = (other & 0b1000_0000) >> 7;
+                self.registers[first as usize] = other << 1;
                 self.registers[0xF] = msb;
-                let result = self.registers[second as usize] << 1;
-                self.registers[first as usize] = result;
-                self.registers[second as usize] = result;
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::SkipIfRegistersNotEqual { first, second } => {
                 let initial = self.registers[first as usize];
                 let other = self.registers[second as usize];
                 if initial != other {
-                    self.pc += 4;
+                    self.skip_next();
                 }
                 else {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             },
             Opcode::SetIndexRegister(addr) => {
                 self.index_reg = addr;
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::SetProgramCounter(addr) => {
-                self.pc = self.registers[0x0] as u16 + addr;
-                self.pc += 2;
+                let offset = if self.quirks.jump_uses_vx {
+                    self.registers[((addr & 0x0F00) >> 8) as usize]
+                }
+                else {
+                    self.registers[0x0]
+                };
+                self.pc = offset as u16 + addr;
             },
             Opcode::AssignRandomValue { addr, value } => {
-                let result = thread_rng().gen_range(0, 255) & value;
-                self.registers[addr as usize] = result as u8;
-                self.pc += 2;
+                let result = self.rng.next_u8() & value as u8;
+                self.registers[addr as usize] = result;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::Draw { x, y, height } => {
-                let loc_x = self.registers[x as usize];
-                let loc_y = self.registers[y as usize];
-                self.registers[0xF] = 0;
-
-                for col in 0..height {
-                    let cell = self.memory[(self.index_reg + col) as usize];
-                    for row in 0..8 {
-                        if cell & (0b1000_0000 >> row) != 0 {
-                            let actual_pos = {
-                                (row+loc_x as u16) +
-                                (col+loc_y as u16)*core::DISPLAY_WIDTH as u16
-                            };
-                            let relative_pos = actual_pos % (
-                                core::DISPLAY_WIDTH*core::DISPLAY_HEIGHT
-                            ) as u16;
-
-                            let new_pixel;
-                            match self.display[relative_pos as usize] {
-                                core::Pixel::White => {
-                                    self.registers[0xF] = 1;
-                                    new_pixel = core::Pixel::Black;
-                                },
-                                _ => {
-                                    new_pixel = core::Pixel:: White;
-                                },
-                            }
-                            self.display[relative_pos as usize] = new_pixel;
-                        }
+                if self.quirks.display_wait {
+                    if !self.vblank {
+                        // Try again once the next frame has started
+                        return Ok(());
                     }
+                    self.vblank = false;
                 }
 
-                self.draw_screen = true;
-                self.pc += 2;
-            },
-            Opcode::SkipIfKeyPressed(addr) => {
-                let key = self.registers[addr as usize];
-                match self.keys[key as usize] {
-                    core::KeyState::Pressed => {
-                        self.pc += 4;
+                // SUPER-CHIP draws a 16x16 sprite when the height is 0
+                let (sprite_width, sprite_height) = {
+                    if height == 0 && self.platform != Platform::Chip8 {
+                        (16, 16)
+                    }
+                    else {
+                        (8, height)
+                    }
+                };
+                let planes = self.planes.count_ones() as usize;
+                let bytes_per_row = sprite_width as usize / 8;
+                let len = planes * sprite_height as usize * bytes_per_row;
+                self.check_index(len, opcode)?;
+
+                let width = self.display_width as u16;
+                let display_height = self.display_height as u16;
+                let loc_x = self.registers[x as usize] as u16 % width;
+                let loc_y = self.registers[y as usize] as u16 % display_height;
+                self.registers[0xF] = 0;
+
+                // Each selected plane has its own sprite, stored one after
+                // the other starting at I
+                let mut addr = self.index_reg as usize;
+                for plane in 0..2 {
+                    let plane_bit = 1 << plane;
+                    if self.planes & plane_bit == 0 {
+                        continue;
+                    }
+                    for col in 0..sprite_height {
+                        let cell = if sprite_width == 16 {
+                            let cell = (self.read_memory(addr) as u16) << 8 |
+                                self.read_memory(addr + 1) as u16;
+                            addr += 2;
+                            cell
+                        }
+                        else {
+                            let cell = (self.read_memory(addr) as u16) << 8;
+                            addr += 1;
+                            cell
+                        };
+                        for row in 0..sprite_width {
+                            if cell & (0x8000 >> row) == 0 {
+                                continue;
+                            }
+                            let mut pos_x = row + loc_x;
+                            let mut pos_y = col + loc_y;
+                            if pos_x >= width || pos_y >= display_height {
+                                if self.quirks.clip_sprites {
+                                    continue;
+                                }
+                                pos_x %= width;
+                                pos_y %= display_height;
+                            }
+                            let relative_pos = (pos_x + pos_y*width) as usize;
+
+                            let pixel = self.display[relative_pos].planes();
+                            if pixel & plane_bit != 0 {
+                                self.registers[0xF] = 1;
+                            }
+                            self.display[relative_pos] =
+                                core::Pixel::from_planes(pixel ^ plane_bit);
+                        }
+                    }
+                }
+
+                self.draw_screen = true;
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::SkipIfKeyPressed(addr) => {
+                let key = self.registers[addr as usize];
+                if key as usize >= self.keys.len() {
+                    let pc = self.pc;
+                    return Err(CpuError::InvalidKey { pc, opcode, key });
+                }
+                match self.keys[key as usize] {
+                    core::KeyState::Pressed => {
+                        self.skip_next();
                     },
                     _ => {
-                        self.pc += 2;
+                        self.pc = self.pc.wrapping_add(2);
                     },
                 }
             },
             Opcode::SkipIfKeyNotPressed(addr) => {
                 let key = self.registers[addr as usize];
+                if key as usize >= self.keys.len() {
+                    let pc = self.pc;
+                    return Err(CpuError::InvalidKey { pc, opcode, key });
+                }
                 match self.keys[key as usize] {
                     core::KeyState::Released => {
-                        self.pc += 4;
+                        self.skip_next();
                     },
                     _ => {
-                        self.pc += 2;
+                        self.pc = self.pc.wrapping_add(2);
                     },
                 }
             },
             Opcode::AssignToDelayTime(addr) => {
                 self.registers[addr as usize] = self.delay_timer;
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AssignOnKeyPress(addr) => {
                 for (i, key) in self.keys.iter().enumerate() {
-                    match *key {
-                        core::KeyState::Pressed => {
-                            self.registers[addr as usize] = i as u8;
-                            self.pc += 2;
-                            break;
-                        },
-                        _ => {},
+                    if *key == core::KeyState::Pressed {
+                        self.registers[addr as usize] = i as u8;
+                        self.pc = self.pc.wrapping_add(2);
+                        break;
                     }
                 }
             },
             Opcode::SetDelayTimer(addr) => {
                 self.delay_timer = self.registers[addr as usize];
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::SetSoundTimer(addr) => {
                 self.sound_timer = self.registers[addr as usize];
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::AddToIndexRegister(addr) => {
-                self.index_reg += self.registers[addr as usize] as u16;
-                self.pc += 2;
+                // VF is left alone, as on the VIP, even when I overflows
+                let value = self.registers[addr as usize] as u16;
+                self.index_reg = self.index_reg.wrapping_add(value);
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::SetIndexRegisterToSpriteAddr(addr) => {
-                self.index_reg = (self.registers[addr as usize] * 0x5) as u16;
-                self.pc += 2;
+                let digit = (self.registers[addr as usize] & 0xF) as u16;
+                self.index_reg = FONT_ADDR + digit * 5;
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::SetIndexRegisterToBigSpriteAddr(addr) => {
+                let digit = (self.registers[addr as usize] & 0xF) as u16;
+                self.index_reg = BIG_FONT_ADDR + digit * 10;
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::SetBCD(addr) => {
+                self.check_index(3, opcode)?;
                 let reg = self.registers[addr as usize];
-                self.memory[self.index_reg as usize] = reg / 100;
-                self.memory[(self.index_reg+1) as usize] = (reg / 10) % 10;
-                self.memory[(self.index_reg+2) as usize] = (reg % 100) % 10;
-                self.pc += 2;
+                let index = self.index_reg as usize;
+                self.write_memory(index, reg / 100);
+                self.write_memory(index + 1, (reg / 10) % 10);
+                self.write_memory(index + 2, (reg % 100) % 10);
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::DumpRegister(addr) => {
+                self.check_index(addr as usize + 1, opcode)?;
                 for i in 0..addr+1 {
                     let value = self.registers[i as usize];
-                    self.memory[(self.index_reg + i) as usize] = value;
+                    let index = self.index_reg as usize + i as usize;
+                    self.write_memory(index, value);
                 }
-                self.pc += 2;
+                self.move_index_after_load_store(addr);
+                self.pc = self.pc.wrapping_add(2);
             },
             Opcode::LoadRegister(addr) => {
+                self.check_index(addr as usize + 1, opcode)?;
                 for i in 0..addr+1 {
-                    let value = self.memory[(self.index_reg + i) as usize];
+                    let index = self.index_reg as usize + i as usize;
+                    let value = self.read_memory(index);
                     self.registers[i as usize] = value;
                 }
-                self.pc += 2;
+                self.move_index_after_load_store(addr);
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::ScrollDown(rows) => {
+                self.scroll(0, rows as i32);
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::ScrollUp(rows) => {
+                self.scroll(0, -(rows as i32));
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::ScrollRight => {
+                self.scroll(4, 0);
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::ScrollLeft => {
+                self.scroll(-4, 0);
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::Exit => {
+                self.halted = true;
+            },
+            Opcode::DisableHighRes => {
+                self.set_resolution(core::DISPLAY_WIDTH, core::DISPLAY_HEIGHT);
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::EnableHighRes => {
+                self.set_resolution(core::HIRES_WIDTH, core::HIRES_HEIGHT);
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::SaveFlags(addr) => {
+                for i in 0..addr+1 {
+                    self.flags[i as usize] = self.registers[i as usize];
+                }
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::LoadFlags(addr) => {
+                for i in 0..addr+1 {
+                    self.registers[i as usize] = self.flags[i as usize];
+                }
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::SaveRegisterRange { first, second } => {
+                let len = register_range(first, second).count();
+                self.check_index(len, opcode)?;
+                for (i, reg) in register_range(first, second).enumerate() {
+                    let value = self.registers[reg];
+                    self.write_memory(self.index_reg as usize + i, value);
+                }
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::LoadRegisterRange { first, second } => {
+                let len = register_range(first, second).count();
+                self.check_index(len, opcode)?;
+                for (i, reg) in register_range(first, second).enumerate() {
+                    let value = self.read_memory(self.index_reg as usize + i);
+                    self.registers[reg] = value;
+                }
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::SetIndexRegisterLong(addr) => {
+                self.index_reg = addr;
+                self.pc = self.pc.wrapping_add(4);
+            },
+            Opcode::SelectPlanes(planes) => {
+                // There are only two planes, so the other bits of N are
+                // ignored
+                self.planes = (planes & 0b11) as u8;
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::LoadAudioPattern => {
+                self.check_index(16, opcode)?;
+                let start = self.index_reg as usize;
+                for i in 0..16 {
+                    self.audio_pattern[i] = self.read_memory(start + i);
+                }
+                self.audio_changed = true;
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Opcode::SetPitch(addr) => {
+                self.pitch = self.registers[addr as usize];
+                self.audio_changed = true;
+                self.pc = self.pc.wrapping_add(2);
             },
         }
         Ok(())
     }
 
-    /// Executes the next cycle of the Cpu in an infinite loop
-    /// First, checks to see if a key was pressed
-    /// Second, verifies and executes the next opcode in memory
-    /// Third, increments or resets the Cpu timers
-    /// Redraws the screen if necessary
+    /// Reads a byte of memory on behalf of an instruction
+    /// The access is recorded while memory tracing is enabled
+    fn read_memory(&mut self, addr: usize) -> u8 {
+        if self.trace_memory {
+            self.memory_accesses.push(MemoryAccess {
+                addr: addr as u16,
+                kind: AccessKind::Read,
+            });
+        }
+        self.memory[addr]
+    }
+
+    /// Writes a byte of memory on behalf of an instruction
+    /// The access is recorded while memory tracing is enabled
+    fn write_memory(&mut self, addr: usize, value: u8) {
+        if self.trace_memory {
+            self.memory_accesses.push(MemoryAccess {
+                addr: addr as u16,
+                kind: AccessKind::Write,
+            });
+        }
+        self.memory[addr] = value;
+    }
+
+    /// Checks that an instruction can access a number of bytes of memory
+    /// starting at I
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The number of bytes the instruction reads or writes
+    /// * `opcode` - The instruction, for the error
+    ///
+    fn check_index(&self, len: usize, opcode: u16) -> Result<(), CpuError> {
+        if self.index_reg as usize + len > self.memory.len() {
+            return Err(CpuError::IndexOutOfBounds {
+                pc: self.pc,
+                opcode,
+                index: self.index_reg,
+            });
+        }
+        Ok(())
+    }
+
+    /// Moves I the way the quirks say once FX55 or FX65 is done
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The last register that was stored or loaded
+    ///
+    fn move_index_after_load_store(&mut self, x: u16) {
+        let step = match self.quirks.load_store_index {
+            LoadStoreIndex::IncrementByXPlusOne => x + 1,
+            LoadStoreIndex::IncrementByX => x,
+            LoadStoreIndex::Unchanged => 0,
+        };
+        self.index_reg = self.index_reg.wrapping_add(step);
+    }
+
+    /// Reads the big-endian word stored at an address in memory
+    fn fetch(&self, addr: u16) -> u16 {
+        let high = self.memory[addr as usize];
+        let low = self.memory[addr as usize + 1];
+        (high as u16) << 8 | low as u16
+    }
+
+    /// Moves the program counter past the next instruction
+    /// XO-CHIP has a single instruction that takes up 4 bytes, which is
+    /// skipped as a whole
+    fn skip_next(&mut self) {
+        let next = self.instruction_at(self.pc.wrapping_add(2));
+        if let Ok(Opcode::SetIndexRegisterLong(_)) = next {
+            self.pc = self.pc.wrapping_add(6);
+        }
+        else {
+            self.pc = self.pc.wrapping_add(4);
+        }
+    }
+
+    /// Shifts the selected planes of the display, filling the pixels that
+    /// scroll into view with Black
+    ///
+    /// # Arguments
+    ///
+    /// * `dx` - The number of pixels to move to the right, or to the left
+    ///   when negative
+    /// * `dy` - The number of pixels to move down, or up when negative
+    ///
+    fn scroll(&mut self, dx: i32, dy: i32) {
+        let width = self.display_width as i32;
+        let height = self.display_height as i32;
+        let planes = self.planes;
+        let old = self.display.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = (x - dx, y - dy);
+                let moved = if src_x >= 0 && src_x < width &&
+                               src_y >= 0 && src_y < height {
+                    old[(src_x + src_y*width) as usize].planes() & planes
+                }
+                else {
+                    0
+                };
+                let pos = (x + y*width) as usize;
+                let kept = old[pos].planes() & !planes;
+                self.display[pos] = core::Pixel::from_planes(kept | moved);
+            }
+        }
+        self.draw_screen = true;
+    }
+
+    /// Switches the display to a new resolution and clears it
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The new number of pixels in each row
+    /// * `height` - The new number of rows
+    ///
+    fn set_resolution(&mut self, width: u32, height: u32) {
+        self.display_width = width;
+        self.display_height = height;
+        self.display = vec![core::Pixel::Black; (width*height) as usize];
+        self.draw_screen = true;
+    }
+
+    /// Executes a fixed number of instructions without any frontend
+    /// Execution stops early if an illegal instruction is encountered
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - The number of instructions to execute
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.load_fontset();
+    /// // ADD V0, 1 and JP 0x200, forever
+    /// cpu.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+    /// cpu.execute(10).unwrap();
+    /// ```
+    pub fn execute(&mut self, cycles: usize) -> Result<(), CpuError> {
+        for _ in 0..cycles {
+            if self.halted {
+                break;
+            }
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of instructions executed in every 60 Hz frame
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    /// Changes the number of instructions executed in every 60 Hz frame
+    /// Roms are written for a particular speed, and many of them misbehave
+    /// if run much faster or slower than that
+    ///
+    /// # Arguments
+    ///
+    /// * `speed` - The number of instructions per frame, so 10 runs the
+    ///   Cpu at 600 Hz
+    ///
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed = speed;
+    }
+
+    /// Runs a single 60 Hz frame without any frontend: executes as many
+    /// instructions as the speed asks for, then ticks the timers once
+    /// Execution stops early if an illegal instruction is encountered
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // ADD V0, 1 and JP 0x200, forever
+    /// cpu.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+    /// cpu.set_speed(20);
+    /// cpu.run_frame().unwrap();
+    /// assert_eq!(cpu.cycles(), 20);
+    /// ```
+    pub fn run_frame(&mut self) -> Result<(), CpuError> {
+        self.execute(self.speed as usize)?;
+        self.tick_timers();
+        Ok(())
+    }
+
+    /// Decrements the delay and sound timers by one
+    /// This is meant to be called at a rate of 60 Hz, independently of how
+    /// many instructions are executed in between
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 { self.delay_timer -= 1; }
+        if self.sound_timer > 0 { self.sound_timer -= 1; }
+        self.vblank = true;
+    }
+
+    /// Returns true while the sound timer is active and a tone should play
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Returns the current contents of the display, row by row
+    pub fn framebuffer(&self) -> &[core::Pixel] {
+        &self.display
+    }
+
+    /// Returns the number of pixels in each row of the display
+    /// This changes when a SUPER-CHIP rom switches resolution
+    pub fn width(&self) -> u32 {
+        self.display_width
+    }
+
+    /// Returns the number of rows of the display
+    pub fn height(&self) -> u32 {
+        self.display_height
+    }
+
+    /// Returns true once the rom has executed the SUPER-CHIP exit
+    /// instruction
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Returns the number of samples of the audio pattern played each
+    /// second, as set by the XO-CHIP pitch register
+    pub fn playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Sets the state of one of the 16 keys on the Chip-8 keypad
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The index of the key on the keypad, from 0x0 to 0xF
+    /// * `state` - Whether the key is now pressed or released
+    pub fn set_key(&mut self, key: usize, state: core::KeyState) {
+        self.keys[key] = state;
+    }
+
+    /// Restarts the random number generator behind CXNN from a seed, so
+    /// that a run can be repeated exactly
+    /// Unless seeded, the Cpu picks a different seed every time
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - Any number, including 0
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::cpu::Cpu;
+    ///
+    /// let mut first = Cpu::new();
+    /// let mut second = Cpu::new();
+    /// first.seed_rng(7);
+    /// second.seed_rng(7);
+    /// assert_eq!(first.save_state(), second.save_state());
+    /// ```
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Random::with_seed(seed);
+    }
+
+    /// Returns the random number generator behind CXNN, as it is now
+    pub fn rng(&self) -> Random {
+        self.rng
+    }
+
+    /// Returns the number of instructions executed so far
+    /// Instructions that wait, such as FX0A and DXYN waiting for the next
+    /// frame, count once for every time they are attempted
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Turns rewinding on or off while running
+    /// While on, the state at the start of every frame is kept, and the
+    /// frontend can step back through them with Command::Rewind
     ///
     /// # Arguments
     ///
-    /// * `sdl_context` - A reference to an Sdl object, which is used to
-    /// initialize the event loop as well as core functions, such as playing
-    /// sounds and rendering the display
+    /// * `rewind` - The history to keep the states in, or None to stop
+    ///   keeping them
+    ///
+    pub fn set_rewind(&mut self, rewind: Option<Rewind>) {
+        self.rewind = rewind;
+    }
+
+    /// Returns the hash of the loaded rom, which save states are checked
+    /// against
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// Captures the whole state of the machine
+    /// The state records the format version, the hash of the loaded rom,
+    /// the platform and the quirks, followed by the number of instructions
+    /// executed so far, the registers, the stack, the timers, the display,
+    /// the random number generator and the memory
+    /// The keypad is not saved, since it belongs to whoever is playing
     ///
     /// # Example
     ///
     /// ```
-    /// let cpu = new Cpu::new();
-    /// let sdl_context = sdl2::init().unwrap();
-    /// match cpu.run(&sdl_context) {
+    /// use chip8_rust::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.load_fontset();
+    /// // ADD V0, 1 and JP 0x200, forever
+    /// cpu.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+    /// let state = cpu.save_state();
+    /// cpu.execute(100).unwrap();
+    /// cpu.load_state(&state).unwrap();
+    /// assert_eq!(cpu.pc(), 0x200);
+    /// ```
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::default();
+        state.bytes(savestate::MAGIC);
+        state.u16(savestate::VERSION);
+        state.u64(self.rom_hash);
+        state.u8(savestate::platform_to_byte(self.platform));
+        state.u8(savestate::quirks_to_bits(self.quirks));
+
+        state.u64(self.cycles);
+        state.u16(self.pc);
+        state.u16(self.index_reg);
+        state.bytes(&self.registers);
+        state.u8(self.delay_timer);
+        state.u8(self.sound_timer);
+        state.u16(self.sp);
+        for &addr in &self.stack {
+            state.u16(addr);
+        }
+        state.bytes(&self.flags);
+        state.bool(self.vblank);
+        state.bool(self.halted);
+
+        state.u16(self.display_width as u16);
+        state.u16(self.display_height as u16);
+        for pixel in &self.display {
+            state.u8(pixel.planes());
+        }
+        state.u8(self.planes);
+        state.bytes(&self.audio_pattern);
+        state.u8(self.pitch);
+        state.u64(self.rng.state());
+        state.bytes(&self.memory);
+        state.finish()
+    }
+
+    /// Restores a state captured by save_state()
+    /// The state is only accepted if it was taken with the same rom loaded,
+    /// and the Cpu is left untouched if it is refused
+    /// The platform and quirks are restored along with the rest of the
+    /// machine
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The bytes returned by save_state()
+    ///
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut state = StateReader::new(data);
+        if state.bytes(savestate::MAGIC.len()) != Ok(&savestate::MAGIC[..]) {
+            return Err(StateError::BadMagic);
+        }
+        let version = state.u16()?;
+        if version != savestate::VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let found = state.u64()?;
+        if found != self.rom_hash {
+            return Err(StateError::RomMismatch {
+                expected: self.rom_hash,
+                found,
+            });
+        }
+
+        let platform = savestate::platform_from_byte(state.u8()?)?;
+        let mut cpu = Cpu::with_platform(
+            platform,
+            savestate::quirks_from_bits(state.u8()?)?
+        );
+        cpu.cycles = state.u64()?;
+        cpu.pc = state.u16()?;
+        cpu.index_reg = state.u16()?;
+        cpu.registers.copy_from_slice(state.bytes(16)?);
+        cpu.delay_timer = state.u8()?;
+        cpu.sound_timer = state.u8()?;
+        cpu.sp = state.u16()?;
+        if cpu.sp as usize > cpu.stack.len() {
+            return Err(StateError::Invalid("stack pointer"));
+        }
+        for addr in cpu.stack.iter_mut() {
+            *addr = state.u16()?;
+        }
+        cpu.flags.copy_from_slice(state.bytes(16)?);
+        cpu.vblank = state.bool()?;
+        cpu.halted = state.bool()?;
+
+        let width = state.u16()? as u32;
+        let height = state.u16()? as u32;
+        let lores = (width, height)
+            == (core::DISPLAY_WIDTH, core::DISPLAY_HEIGHT);
+        let hires = (width, height)
+            == (core::HIRES_WIDTH, core::HIRES_HEIGHT);
+        if !lores && !hires {
+            return Err(StateError::Invalid("display size"));
+        }
+        cpu.set_resolution(width, height);
+        let pixels = state.bytes((width * height) as usize)?;
+        for (pixel, &planes) in cpu.display.iter_mut().zip(pixels) {
+            *pixel = core::Pixel::from_planes(planes);
+        }
+        cpu.planes = state.u8()?;
+        cpu.audio_pattern.copy_from_slice(state.bytes(16)?);
+        cpu.pitch = state.u8()?;
+        cpu.rng = Random::with_seed(state.u64()?);
+        let memory_size = cpu.memory.len();
+        cpu.memory.copy_from_slice(state.bytes(memory_size)?);
+        state.finish()?;
+
+        // Whatever belongs to the host rather than the machine carries over
+        cpu.keys = self.keys.clone();
+        cpu.trace_memory = self.trace_memory;
+        cpu.rom_hash = self.rom_hash;
+        cpu.rom_path = self.rom_path.take();
+        cpu.load_addr = self.load_addr;
+        cpu.rewind = self.rewind.take();
+        cpu.speed = self.speed;
+        cpu.draw_screen = true;
+        *self = cpu;
+        Ok(())
+    }
+
+    /// Returns the file a numbered save state slot is kept in, which sits
+    /// next to the loaded rom
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` - The number of the slot
+    ///
+    pub fn state_path(&self, slot: u8) -> String {
+        let rom = self.rom_path.as_ref().map_or("chip8", |path| path);
+        format!("{}.state{}", rom, slot)
+    }
+
+    /// Writes the state of the machine to a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create or overwrite
+    ///
+    pub fn save_state_file(&self, path: &str) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(&self.save_state())
+    }
+
+    /// Restores the state of the machine from a file written by
+    /// save_state_file()
+    /// A state that is refused is reported as invalid data
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to read the state from
+    ///
+    pub fn load_state_file(&mut self, path: &str) -> std::io::Result<()> {
+        let mut data = vec![];
+        File::open(path)?.read_to_end(&mut data)?;
+        self.load_state(&data).map_err(|err| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+        })
+    }
+
+    /// Runs the Cpu one 60 Hz frame at a time until the user quits or the
+    /// rom exits
+    /// First, checks to see if a key was pressed
+    /// Second, executes as many instructions as the speed asks for
+    /// Third, ticks the Cpu timers once
+    /// Redraws the screen if necessary, then waits for the next frame
+    ///
+    /// # Arguments
+    ///
+    /// * `frontend` - A reference to a Frontend object, which is used to
+    ///   read the keypad as well as to play sounds and render the display
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use chip8_rust::cpu::{Cpu, CpuError};
+    /// use chip8_rust::frontend::NullFrontend;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// let mut frontend = NullFrontend;
+    /// match cpu.run(&mut frontend) {
     ///     Ok(()) => {
-    ///         // Opcode was legal
+    ///         // The user asked to quit or the rom exited
     ///     },
-    ///     Err(cpu::CpuError::IllegalInstruction(opcode)) => {
-    ///         // Opcode was illegal
-    ///         // Abort program
-    ///         panic!("Illegal CPU instruction: {:x}", opcode)
+    ///     Err(err) => {
+    ///         // The rom did something the Cpu cannot carry out
+    ///         panic!("{}", err)
     ///    },
     /// }
     /// ```
-    pub fn run(&mut self, sdl_context: &Sdl) -> Result<(), CpuError> {
-        let mut event_pump = sdl_context.event_pump().unwrap();
-        let mut core = core::Core::new(&sdl_context);
-        'running: loop {
-            for event in event_pump.poll_iter() {
-                match event {
-                    Event::Quit {..} |
-                    Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                        break 'running
-                    },
-                    Event::KeyDown { keycode: Some(keycode), .. } => {
-                        core.handle_key_down(self, keycode)
-                    },
-                    Event::KeyUp { keycode: Some(keycode), .. } => {
-                        core.handle_key_up(self, keycode)
-                    },
-                    _ => {},
+    pub fn run<F>(&mut self, frontend: &mut F) -> Result<(), CpuError>
+        where F: Frontend + ?Sized
+    {
+        self.run_loop(frontend, None)
+    }
+
+    /// Works like run(), except that the debugger is consulted before and
+    /// after every instruction, and may pause the Cpu to take commands
+    ///
+    /// # Arguments
+    ///
+    /// * `frontend` - A reference to a Frontend object, which is used to
+    ///   read the keypad as well as to play sounds and render the display
+    /// * `debugger` - A reference to a Debugger object, which holds the
+    ///   breakpoints and watchpoints
+    ///
+    pub fn run_with_debugger<F>(
+        &mut self,
+        frontend: &mut F,
+        debugger: &mut Debugger
+    ) -> Result<(), CpuError>
+        where F: Frontend + ?Sized
+    {
+        self.set_memory_tracing(true);
+        let result = self.run_loop(frontend, Some(debugger));
+        self.set_memory_tracing(false);
+        result
+    }
+
+    /// The loop shared by run() and run_with_debugger()
+    fn run_loop<F>(
+        &mut self,
+        frontend: &mut F,
+        mut debugger: Option<&mut Debugger>
+    ) -> Result<(), CpuError>
+        where F: Frontend + ?Sized
+    {
+        let mut clock = FrameClock::new();
+        let mut pace = Pace::default();
+        let start = self.save_state();
+        frontend.set_pace(pace);
+        'frames: loop {
+            let mut advance = false;
+            let previous = pace;
+            match frontend.poll_input(self) {
+                Some(Command::Quit) => break,
+                Some(Command::SaveState(slot)) => {
+                    let path = self.state_path(slot);
+                    match self.save_state_file(&path) {
+                        Ok(()) => eprintln!("Saved state to {}", path),
+                        Err(err) => {
+                            eprintln!("Could not save {}: {}", path, err)
+                        },
+                    }
+                },
+                Some(Command::LoadState(slot)) => {
+                    let path = self.state_path(slot);
+                    match self.load_state_file(&path) {
+                        Ok(()) => eprintln!("Loaded state from {}", path),
+                        Err(err) => {
+                            eprintln!("Could not load {}: {}", path, err)
+                        },
+                    }
+                },
+                Some(Command::Reset) => {
+                    if let Err(err) = self.load_state(&start) {
+                        eprintln!("Could not reset: {}", err);
+                    }
+                    frontend.stop_sound();
+                    self.draw_screen = true;
+                },
+                Some(Command::Rewind) => {
+                    if let Some(mut rewind) = self.rewind.take() {
+                        frontend.stop_sound();
+                        rewind.step_back(self);
+                        self.rewind = Some(rewind);
+                        // Step back one frame for every frame that passes
+                        self.present(frontend, &mut clock, pace);
+                        continue;
+                    }
+                },
+                Some(Command::Pause) => pace.toggle_pause(),
+                Some(Command::FrameAdvance) => {
+                    if pace.is_paused() {
+                        advance = true;
+                    }
+                    else {
+                        pace.toggle_pause();
+                    }
+                },
+                Some(Command::Turbo) => pace.toggle_turbo(),
+                Some(Command::SlowDown) => pace.slow_down(),
+                Some(Command::SpeedUp) => pace.speed_up(),
+                None => {},
+            }
+            if pace != previous {
+                frontend.set_pace(pace);
+                if pace.is_paused() || pace.is_turbo() {
+                    frontend.stop_sound();
                 }
+                clock.set_slowdown(pace.slowdown());
+                clock.reset();
+                self.draw_screen = true;
             }
-            ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
-            self.step()?;
-            self.update_timers(&mut core);
-            if self.draw_screen {
-                core.draw(self);
+            if self.halted {
+                break;
+            }
+            if pace.is_paused() && !advance {
+                self.present(frontend, &mut clock, pace);
+                continue;
+            }
+
+            for _ in 0..self.speed {
+                if self.halted {
+                    break;
+                }
+                if let Some(ref mut debugger) = debugger {
+                    if let Some(Command::Quit) = debugger.before_step(self) {
+                        break 'frames;
+                    }
+                }
+                self.step()?;
+                if let Some(ref mut debugger) = debugger {
+                    debugger.after_step(self);
+                }
+            }
+            self.update_timers(frontend, pace.is_turbo() || advance);
+            if let Some(mut rewind) = self.rewind.take() {
+                rewind.push(self);
+                self.rewind = Some(rewind);
             }
+            self.present(frontend, &mut clock, pace);
         }
         Ok(())
     }
 
-    /// Increments or resets the sound and delay timers
-    /// The function checks to see if the Cpu counter is equal to 10 before
-    /// changing the timers
-    /// This value was chosen in order to maintain a cpu clock rate of 600 Hz
-    /// Without the counter, the timers would execute at a rate of 60 Hz, so
-    /// the timers must wait by a factor of 10 in order to maintain speed with
-    /// the cpu
+    /// Ends a frame by ticking the sound and delay timers once
+    /// Hands the audio pattern to the frontend first if the rom changed it,
+    /// and keeps the tone playing for every frame the sound timer is active
     ///
     /// # Arguments
     ///
-    /// * `core` - A reference to a Core object, which here is used to
-    /// play and stop sounds
+    /// * `frontend` - A reference to a Frontend object, which here is used to
+    ///   play and stop sounds
+    /// * `muted` - Keeps the tone from playing, since it would only come out
+    ///   as noise when frames do not last 1/60th of a second
     ///
     /// # Example
     ///
-    /// ```
-    /// let cpu = new Cpu::new();
-    /// let sdl_context = sdl2::init().unwrap();
-    /// let mut core = core::Core::new(&sdl_context);
-    /// cpu.update_timers(&mut core);
+    /// ```ignore
+    /// let mut cpu = Cpu::new();
+    /// let mut frontend = NullFrontend;
+    /// cpu.update_timers(&mut frontend, false);
     /// ```
     /// NOTE: This example uses the function as a public method, but the
     /// function is in fact private and should be used within the context
     /// of the Cpu object
-    fn update_timers(&mut self, core: &mut Core) {
-        if self.counter == 10 {
-            if self.delay_timer > 0 { self.delay_timer -= 1; }
-            if self.sound_timer > 0 {
-                if self.sound_timer == 1 { core.play_sound(); }
-                self.sound_timer -= 1;
+    fn update_timers<F>(&mut self, frontend: &mut F, muted: bool)
+        where F: Frontend + ?Sized
+    {
+        if self.audio_changed {
+            let rate = self.playback_rate();
+            frontend.set_audio_pattern(&self.audio_pattern, rate);
+            self.audio_changed = false;
+        }
+        if self.sound_active() && !muted {
+            frontend.play_sound();
+        }
+        else {
+            frontend.stop_sound();
+        }
+        self.tick_timers();
+    }
+
+    /// Shows the frame that just ended and waits for the next one
+    /// Frontends that synchronize to vsync draw every frame and do the
+    /// waiting themselves, while the others only redraw when the display
+    /// changed and leave the waiting to the clock
+    /// Slow motion always waits for the clock, and turbo never waits at all,
+    /// drawing only once per 1/60th of a second of real time
+    ///
+    /// # Arguments
+    ///
+    /// * `frontend` - The frontend to draw the display with
+    /// * `clock` - Keeps time for frontends that do not use vsync
+    /// * `pace` - The speed chosen by the user
+    ///
+    fn present<F>(
+        &mut self,
+        frontend: &mut F,
+        clock: &mut FrameClock,
+        pace: Pace
+    )
+        where F: Frontend + ?Sized
+    {
+        if pace.is_turbo() && !pace.is_paused() {
+            if self.draw_screen && clock.frame_due() {
+                frontend.draw(self);
+                self.draw_screen = false;
+            }
+            return;
+        }
+        let vsync = frontend.syncs_to_vblank();
+        if vsync || self.draw_screen {
+            frontend.draw(self);
+            self.draw_screen = false;
+        }
+        if !vsync || pace.slowdown() > 1 {
+            clock.wait();
+        }
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu::new()
+    }
+}
+
+/// Lists the registers between two registers, inclusive, walking backwards
+/// when the first register comes after the second one
+fn register_range(first: u16, second: u16) -> Box<dyn Iterator<Item = usize>> {
+    let (first, second) = (first as usize, second as usize);
+    if first <= second {
+        Box::new(first..=second)
+    }
+    else {
+        Box::new((second..=first).rev())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{KeyState, Pixel};
+
+    /// Builds a Cpu for a platform with a program at 0x200 and the fontsets
+    /// loaded
+    fn program_on(
+        platform: Platform,
+        quirks: Quirks,
+        opcodes: &[u16]
+    ) -> Cpu {
+        let mut cpu = Cpu::with_platform(platform, quirks);
+        cpu.seed_rng(0);
+        cpu.load_fontset();
+        let bytes: Vec<u8> = opcodes.iter()
+            .flat_map(|opcode| opcode.to_be_bytes().to_vec())
+            .collect();
+        cpu.set_memory(PROGRAM_START, &bytes);
+        cpu
+    }
+
+    /// Builds a Chip-8 Cpu with the VIP quirks and a program at 0x200
+    fn program(opcodes: &[u16]) -> Cpu {
+        program_on(Platform::Chip8, Quirks::vip(), opcodes)
+    }
+
+    /// Runs a single instruction with VX and VY set beforehand
+    fn run_xy(opcode: u16, x: u8, y: u8) -> Cpu {
+        let mut cpu = program(&[opcode]);
+        cpu.set_register(((opcode >> 8) & 0xF) as usize, x);
+        cpu.set_register(((opcode >> 4) & 0xF) as usize, y);
+        cpu.step().unwrap();
+        cpu
+    }
+
+    /// Returns the positions of the pixels that have any plane set
+    fn lit(cpu: &Cpu) -> Vec<(u32, u32)> {
+        let width = cpu.width();
+        cpu.framebuffer().iter()
+            .enumerate()
+            .filter(|&(_, &pixel)| pixel != Pixel::Black)
+            .map(|(i, _)| (i as u32 % width, i as u32 / width))
+            .collect()
+    }
+
+    #[test]
+    fn clear_display() {
+        let mut cpu = program(&[0x00E0]);
+        cpu.display[5] = Pixel::White;
+        cpu.step().unwrap();
+        assert!(lit(&cpu).is_empty());
+        assert!(cpu.draw_screen);
+        assert_eq!(cpu.pc(), 0x202);
+    }
+
+    #[test]
+    fn call_and_return() {
+        let mut cpu = program(&[0x2300]);
+        cpu.set_memory(0x300, &[0x00, 0xEE]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x300);
+        assert_eq!(cpu.stack(), &[0x202]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x202);
+        assert!(cpu.stack().is_empty());
+    }
+
+    #[test]
+    fn call_fills_the_stack() {
+        // Every subroutine calls the next one, 16 deep
+        let mut cpu = program(&[0x2300]);
+        for depth in 0..16u16 {
+            let addr = 0x300 + depth * 2;
+            let next = 0x2000 | (addr + 2);
+            cpu.set_memory(addr, &next.to_be_bytes());
+        }
+        for _ in 0..16 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.stack().len(), 16);
+        assert_eq!(cpu.stack()[0], 0x202);
+        assert_eq!(cpu.stack()[15], 0x31E);
+        assert_eq!(cpu.pc(), 0x31E);
+
+        // Returning all the way unwinds the stack in order
+        let mut cpu = program(&[]);
+        cpu.stack = (0..16).map(|i| 0x400 + i * 2).collect();
+        cpu.sp = 16;
+        for i in (0..16).rev() {
+            cpu.set_memory(cpu.pc(), &[0x00, 0xEE]);
+            cpu.step().unwrap();
+            assert_eq!(cpu.pc(), 0x400 + i * 2);
+        }
+        assert!(cpu.stack().is_empty());
+    }
+
+    #[test]
+    fn jump() {
+        let mut cpu = program(&[0x1ABC]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0xABC);
+    }
+
+    #[test]
+    fn skips() {
+        let cases = [
+            (0x3A42, 0x42, 0x00, true),
+            (0x3A42, 0x41, 0x00, false),
+            (0x4A42, 0x42, 0x00, false),
+            (0x4A42, 0x41, 0x00, true),
+            (0x5AB0, 0x07, 0x07, true),
+            (0x5AB0, 0x07, 0x08, false),
+            (0x9AB0, 0x07, 0x07, false),
+            (0x9AB0, 0x07, 0x08, true),
+        ];
+        for &(opcode, x, y, skipped) in cases.iter() {
+            let mut cpu = program(&[opcode]);
+            cpu.set_register(0xA, x);
+            cpu.set_register(0xB, y);
+            cpu.step().unwrap();
+            let expected = if skipped { 0x204 } else { 0x202 };
+            assert_eq!(cpu.pc(), expected, "{:04x}", opcode);
+        }
+    }
+
+    #[test]
+    fn skip_over_long_load_on_xo_chip() {
+        let mut cpu = program_on(
+            Platform::XoChip,
+            Quirks::modern(),
+            &[0x3000, 0xF000, 0x1234]
+        );
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x206);
+
+        // F000 is an ordinary instruction elsewhere
+        let mut cpu = program(&[0x3000, 0xF000]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn set_and_add_register() {
+        let mut cpu = program(&[0x6AFE, 0x7A03, 0x7F01]);
+        cpu.set_register(0xF, 0x20);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers()[0xA], 0xFE);
+        // 7XNN wraps around without touching VF
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers()[0xA], 0x01);
+        assert_eq!(cpu.registers()[0xF], 0x20);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers()[0xF], 0x21);
+    }
+
+    #[test]
+    fn assign_and_logic() {
+        let cpu = run_xy(0x8120, 0x0F, 0x3C);
+        assert_eq!(cpu.registers()[0x1], 0x3C);
+        for &(opcode, expected) in [
+            (0x8121, 0x3F),
+            (0x8122, 0x0C),
+            (0x8123, 0x33),
+        ].iter() {
+            for &quirks in [Quirks::vip(), Quirks::modern()].iter() {
+                let mut cpu = program_on(Platform::Chip8, quirks, &[opcode]);
+                cpu.set_register(0x1, 0x0F);
+                cpu.set_register(0x2, 0x3C);
+                cpu.set_register(0xF, 0x55);
+                cpu.step().unwrap();
+                assert_eq!(cpu.registers()[0x1], expected);
+                let vf = if quirks.logic_resets_vf { 0 } else { 0x55 };
+                assert_eq!(cpu.registers()[0xF], vf, "{:04x}", opcode);
             }
-            else {
-                core.stop_sound();
+        }
+    }
+
+    #[test]
+    fn add_registers_sets_carry() {
+        let cpu = run_xy(0x8124, 0x10, 0x20);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x30, 0));
+        let cpu = run_xy(0x8124, 0xFF, 0x01);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x00, 1));
+        let cpu = run_xy(0x8124, 0x80, 0xFF);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x7F, 1));
+    }
+
+    #[test]
+    fn subtract_registers_sets_no_borrow() {
+        let cpu = run_xy(0x8125, 0x30, 0x10);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x20, 1));
+        // Equal values do not borrow
+        let cpu = run_xy(0x8125, 0x10, 0x10);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x00, 1));
+        let cpu = run_xy(0x8125, 0x10, 0x11);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0xFF, 0));
+    }
+
+    #[test]
+    fn subtract_first_register_sets_no_borrow() {
+        let cpu = run_xy(0x8127, 0x10, 0x30);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x20, 1));
+        let cpu = run_xy(0x8127, 0x10, 0x10);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x00, 1));
+        let cpu = run_xy(0x8127, 0x11, 0x10);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0xFF, 0));
+    }
+
+    #[test]
+    fn arithmetic_flag_wins_over_vf_result() {
+        // When X is VF the flag is written after the result
+        assert_eq!(run_xy(0x8F14, 0xFF, 0x02).registers()[0xF], 1);
+        assert_eq!(run_xy(0x8F14, 0x01, 0x02).registers()[0xF], 0);
+        assert_eq!(run_xy(0x8F15, 0x05, 0x02).registers()[0xF], 1);
+        assert_eq!(run_xy(0x8F15, 0x01, 0x02).registers()[0xF], 0);
+        assert_eq!(run_xy(0x8F17, 0x02, 0x05).registers()[0xF], 1);
+        assert_eq!(run_xy(0x8F17, 0x02, 0x01).registers()[0xF], 0);
+    }
+
+    #[test]
+    fn shifts_use_vy_or_vx() {
+        // VIP shifts VY into VX
+        let cpu = run_xy(0x8126, 0x00, 0x03);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x01, 1));
+        let cpu = run_xy(0x812E, 0x00, 0x81);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x02, 1));
+        let cpu = run_xy(0x812E, 0x00, 0x41);
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0x82, 0));
+
+        // SUPER-CHIP shifts VX in place
+        for &(opcode, expected, flag) in [
+            (0x8126, 0x01, 0),
+            (0x812E, 0x04, 0),
+        ].iter() {
+            let mut cpu = program_on(
+                Platform::SuperChip,
+                Quirks::superchip(),
+                &[opcode]
+            );
+            cpu.set_register(0x1, 0x02);
+            cpu.set_register(0x2, 0xFF);
+            cpu.step().unwrap();
+            assert_eq!(cpu.registers()[0x1], expected);
+            assert_eq!(cpu.registers()[0xF], flag);
+        }
+    }
+
+    #[test]
+    fn shift_flag_wins_over_vf_result() {
+        assert_eq!(run_xy(0x8F16, 0x00, 0xFE).registers()[0xF], 0);
+        assert_eq!(run_xy(0x8F16, 0x00, 0x01).registers()[0xF], 1);
+        assert_eq!(run_xy(0x8F1E, 0x00, 0x7F).registers()[0xF], 0);
+        assert_eq!(run_xy(0x8F1E, 0x00, 0x80).registers()[0xF], 1);
+    }
+
+    #[test]
+    fn set_index_register() {
+        let mut cpu = program(&[0xA123]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.index_register(), 0x123);
+    }
+
+    #[test]
+    fn jump_with_offset() {
+        let mut cpu = program(&[0xB320]);
+        cpu.set_register(0x0, 0x04);
+        cpu.set_register(0x3, 0x10);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x324);
+
+        let mut cpu = program_on(
+            Platform::SuperChip,
+            Quirks::superchip(),
+            &[0xB320]
+        );
+        cpu.set_register(0x0, 0x04);
+        cpu.set_register(0x3, 0x10);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x330);
+    }
+
+    #[test]
+    fn random_is_masked() {
+        let mut cpu = program(&[0xC100, 0xC20F]);
+        cpu.execute(2).unwrap();
+        assert_eq!(cpu.registers()[0x1], 0);
+        assert_eq!(cpu.registers()[0x2] & 0xF0, 0);
+    }
+
+    #[test]
+    fn draw_sets_collision_flag() {
+        let mut cpu = program(&[0xD011, 0xD011]);
+        cpu.set_index_register(0x300);
+        cpu.set_memory(0x300, &[0b1100_0000]);
+        cpu.set_register(0x0, 3);
+        cpu.set_register(0x1, 2);
+        cpu.step().unwrap();
+        assert_eq!(lit(&cpu), vec![(3, 2), (4, 2)]);
+        assert_eq!(cpu.registers()[0xF], 0);
+
+        // Drawing the same sprite again erases it
+        cpu.tick_timers();
+        cpu.step().unwrap();
+        assert!(lit(&cpu).is_empty());
+        assert_eq!(cpu.registers()[0xF], 1);
+    }
+
+    #[test]
+    fn draw_clears_flag_without_collision() {
+        let mut cpu = program(&[0xD011]);
+        cpu.set_index_register(0x300);
+        cpu.set_memory(0x300, &[0x80]);
+        cpu.set_register(0xF, 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers()[0xF], 0);
+    }
+
+    #[test]
+    fn draw_clips_or_wraps() {
+        // The position wraps, but the sprite is clipped at the edges
+        let mut cpu = program(&[0xD012]);
+        cpu.set_index_register(0x300);
+        cpu.set_memory(0x300, &[0xC0, 0xC0]);
+        cpu.set_register(0x0, 64 + 63);
+        cpu.set_register(0x1, 31);
+        cpu.step().unwrap();
+        assert_eq!(lit(&cpu), vec![(63, 31)]);
+
+        let mut cpu = program_on(Platform::XoChip, Quirks::modern(), &[0xD012]);
+        cpu.set_index_register(0x300);
+        cpu.set_memory(0x300, &[0xC0, 0xC0]);
+        cpu.set_register(0x0, 63);
+        cpu.set_register(0x1, 31);
+        cpu.step().unwrap();
+        assert_eq!(lit(&cpu), vec![(0, 0), (63, 0), (0, 31), (63, 31)]);
+    }
+
+    #[test]
+    fn draw_waits_for_vblank() {
+        let mut cpu = program(&[0xD011, 0xD011]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x202);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x202);
+        cpu.tick_timers();
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn draw_big_sprite() {
+        let mut cpu = program_on(
+            Platform::SuperChip,
+            Quirks::superchip(),
+            &[0xD000]
+        );
+        cpu.set_index_register(0x300);
+        cpu.set_memory(0x300, &[0x80, 0x01]);
+        cpu.step().unwrap();
+        assert_eq!(lit(&cpu), vec![(0, 0), (15, 0)]);
+    }
+
+    #[test]
+    fn skip_on_key() {
+        for &state in [KeyState::Pressed, KeyState::Released].iter() {
+            let pressed = state == KeyState::Pressed;
+            for &(opcode, skipped) in [(0xE59E, pressed), (0xE5A1, !pressed)]
+                .iter()
+            {
+                let mut cpu = program(&[opcode]);
+                cpu.set_register(0x5, 0xB);
+                cpu.set_key(0xB, state);
+                cpu.step().unwrap();
+                let expected = if skipped { 0x204 } else { 0x202 };
+                assert_eq!(cpu.pc(), expected, "{:04x}", opcode);
             }
-            self.counter = 0;
         }
-        else {
-            self.counter += 1;
+    }
+
+    #[test]
+    fn wait_for_key() {
+        let mut cpu = program(&[0xF30A]);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x200);
+        cpu.set_key(0xC, KeyState::Pressed);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x202);
+        assert_eq!(cpu.registers()[0x3], 0xC);
+    }
+
+    #[test]
+    fn timers() {
+        let mut cpu = program(&[0xF115, 0xF218, 0xF307]);
+        cpu.set_register(0x1, 5);
+        cpu.set_register(0x2, 1);
+        cpu.execute(2).unwrap();
+        assert_eq!(cpu.delay_timer(), 5);
+        assert!(cpu.sound_active());
+        cpu.tick_timers();
+        assert!(!cpu.sound_active());
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers()[0x3], 4);
+    }
+
+    #[test]
+    fn add_to_index_register_wraps() {
+        let mut cpu = program(&[0xF11E, 0xF11E]);
+        cpu.set_index_register(0xFFF0);
+        cpu.set_register(0x1, 0x08);
+        cpu.step().unwrap();
+        assert_eq!(cpu.index_register(), 0xFFF8);
+        cpu.set_register(0x1, 0x10);
+        cpu.step().unwrap();
+        assert_eq!(cpu.index_register(), 0x0008);
+        // Unlike on the Amiga interpreter, VF is left alone
+        assert_eq!(cpu.registers()[0xF], 0);
+    }
+
+    #[test]
+    fn font_addresses() {
+        let mut cpu = program_on(
+            Platform::SuperChip,
+            Quirks::superchip(),
+            &[0xF129, 0xF130]
+        );
+        // Only the low nibble picks the digit
+        cpu.set_register(0x1, 0x1A);
+        cpu.step().unwrap();
+        assert_eq!(cpu.index_register(), FONT_ADDR + 0xA * 5);
+        assert_eq!(cpu.memory()[cpu.index_register() as usize], 0xF0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.index_register(), BIG_FONT_ADDR + 0xA * 10);
+    }
+
+    #[test]
+    fn binary_coded_decimal() {
+        for &(value, digits) in [
+            (0, [0, 0, 0]),
+            (9, [0, 0, 9]),
+            (255, [2, 5, 5]),
+        ].iter() {
+            let mut cpu = program(&[0xF133]);
+            cpu.set_index_register(0x300);
+            cpu.set_register(0x1, value);
+            cpu.set_memory(0x300, &[0xAA; 3]);
+            cpu.step().unwrap();
+            assert_eq!(&cpu.memory()[0x300..0x303], &digits);
         }
     }
+
+    #[test]
+    fn dump_and_load_registers() {
+        for &quirks in [
+            Quirks::vip(),
+            Quirks::chip48(),
+            Quirks::superchip(),
+        ].iter() {
+            let mut cpu = program_on(Platform::Chip8, quirks, &[0xF255]);
+            cpu.set_index_register(0x300);
+            for x in 0..16 {
+                cpu.set_register(x, x as u8 + 1);
+            }
+            cpu.step().unwrap();
+            assert_eq!(&cpu.memory()[0x300..0x304], &[1, 2, 3, 0]);
+            let index = match quirks.load_store_index {
+                LoadStoreIndex::IncrementByXPlusOne => 0x303,
+                LoadStoreIndex::IncrementByX => 0x302,
+                LoadStoreIndex::Unchanged => 0x300,
+            };
+            assert_eq!(cpu.index_register(), index);
+
+            let mut cpu = program_on(Platform::Chip8, quirks, &[0xF165]);
+            cpu.set_index_register(0x300);
+            cpu.set_memory(0x300, &[7, 8, 9]);
+            cpu.step().unwrap();
+            assert_eq!(&cpu.registers()[..3], &[7, 8, 0]);
+            let index = match quirks.load_store_index {
+                LoadStoreIndex::IncrementByXPlusOne => 0x302,
+                LoadStoreIndex::IncrementByX => 0x301,
+                LoadStoreIndex::Unchanged => 0x300,
+            };
+            assert_eq!(cpu.index_register(), index);
+        }
+    }
+
+    #[test]
+    fn scroll() {
+        let quirks = Quirks::superchip();
+        for &(opcode, expected) in [
+            (0x00C2, (8, 7)),
+            (0x00FB, (12, 5)),
+            (0x00FC, (4, 5)),
+        ].iter() {
+            let mut cpu = program_on(Platform::SuperChip, quirks, &[opcode]);
+            cpu.display[8 + 5 * 64] = Pixel::White;
+            cpu.step().unwrap();
+            assert_eq!(lit(&cpu), vec![expected], "{:04x}", opcode);
+        }
+
+        let mut cpu = program_on(Platform::XoChip, Quirks::modern(), &[0x00D2]);
+        cpu.display[8 + 5 * 64] = Pixel::White;
+        cpu.step().unwrap();
+        assert_eq!(lit(&cpu), vec![(8, 3)]);
+    }
+
+    #[test]
+    fn resolution_and_exit() {
+        let quirks = Quirks::superchip();
+        let mut cpu = program_on(
+            Platform::SuperChip,
+            quirks,
+            &[0x00FF, 0x00FE, 0x00FD, 0x00E0]
+        );
+        cpu.step().unwrap();
+        assert_eq!((cpu.width(), cpu.height()), (128, 64));
+        cpu.step().unwrap();
+        assert_eq!((cpu.width(), cpu.height()), (64, 32));
+        cpu.step().unwrap();
+        assert!(cpu.halted());
+        assert_eq!(cpu.pc(), 0x204);
+        // A halted Cpu does nothing
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn save_and_load_flags() {
+        let mut cpu = program_on(
+            Platform::SuperChip,
+            Quirks::superchip(),
+            &[0xF275, 0xF385]
+        );
+        for x in 0..4 {
+            cpu.set_register(x, 0x10 + x as u8);
+        }
+        cpu.step().unwrap();
+        for x in 0..4 {
+            cpu.set_register(x, 0);
+        }
+        cpu.step().unwrap();
+        assert_eq!(&cpu.registers()[..4], &[0x10, 0x11, 0x12, 0]);
+    }
+
+    #[test]
+    fn register_ranges() {
+        let mut cpu = program_on(
+            Platform::XoChip,
+            Quirks::modern(),
+            &[0x5312, 0x5133]
+        );
+        cpu.set_index_register(0x300);
+        cpu.set_register(0x1, 0xA1);
+        cpu.set_register(0x2, 0xA2);
+        cpu.set_register(0x3, 0xA3);
+        // Walking backwards stores V3 first
+        cpu.step().unwrap();
+        assert_eq!(&cpu.memory()[0x300..0x303], &[0xA3, 0xA2, 0xA1]);
+        assert_eq!(cpu.index_register(), 0x300);
+        cpu.step().unwrap();
+        assert_eq!(&cpu.registers()[1..4], &[0xA3, 0xA2, 0xA1]);
+    }
+
+    #[test]
+    fn xo_chip_extensions() {
+        let mut cpu = program_on(
+            Platform::XoChip,
+            Quirks::modern(),
+            &[0xF000, 0x1234, 0xF201, 0xF002, 0xF13A]
+        );
+        cpu.step().unwrap();
+        assert_eq!(cpu.index_register(), 0x1234);
+        assert_eq!(cpu.pc(), 0x204);
+        cpu.step().unwrap();
+        assert_eq!(cpu.planes, 0b10);
+        cpu.set_memory(0x1234, &[0x0F; 16]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.audio_pattern, [0x0F; 16]);
+        cpu.set_register(0x1, 112);
+        cpu.step().unwrap();
+        assert_eq!(cpu.playback_rate(), 8000.0);
+    }
+
+    #[test]
+    fn select_planes_ignores_missing_planes() {
+        for &(opcode, planes) in [(0xF401, 0b00), (0xFF01, 0b11)].iter() {
+            let mut cpu = program_on(
+                Platform::XoChip,
+                Quirks::modern(),
+                &[opcode]
+            );
+            cpu.step().unwrap();
+            assert_eq!(cpu.planes, planes);
+            assert_eq!(cpu.pc(), 0x202);
+        }
+    }
+
+    #[test]
+    fn clear_selected_planes_only() {
+        let mut cpu = program_on(
+            Platform::XoChip,
+            Quirks::modern(),
+            &[0xF101, 0x00E0]
+        );
+        cpu.display[0] = Pixel::BothPlanes;
+        cpu.execute(2).unwrap();
+        assert_eq!(cpu.display[0], Pixel::SecondPlane);
+    }
+
+    #[test]
+    fn machine_code_routines_are_illegal() {
+        for &opcode in [0x0000, 0x0123].iter() {
+            let mut cpu = program(&[opcode]);
+            assert_eq!(
+                cpu.step(),
+                Err(CpuError::IllegalInstruction { pc: 0x200, opcode })
+            );
+            assert_eq!(cpu.pc(), 0x200);
+        }
+    }
+
+    #[test]
+    fn extensions_are_illegal_on_chip8() {
+        for &opcode in [0x00FF, 0x00C1, 0xF130, 0x5122].iter() {
+            let mut cpu = program(&[opcode]);
+            assert_eq!(
+                cpu.step(),
+                Err(CpuError::IllegalInstruction { pc: 0x200, opcode })
+            );
+        }
+    }
+
+    #[test]
+    fn stack_overflow_and_underflow() {
+        // A subroutine that calls itself overflows on the 17th call
+        let mut cpu = program(&[0x2200]);
+        cpu.execute(16).unwrap();
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::StackOverflow { pc: 0x200, opcode: 0x2200 })
+        );
+        assert_eq!(cpu.stack().len(), 16);
+
+        let mut cpu = program(&[0x00EE]);
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::StackUnderflow { pc: 0x200, opcode: 0x00EE })
+        );
+    }
+
+    #[test]
+    fn pc_out_of_bounds() {
+        let mut cpu = program(&[0x1FFF]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::PcOutOfBounds { pc: 0xFFF }));
+
+        // Running off the last word of memory
+        let mut cpu = program(&[0x1FFE]);
+        cpu.set_memory(0xFFE, &[0x60, 0x00]);
+        cpu.execute(2).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::PcOutOfBounds { pc: 0x1000 }));
+
+        // XO-CHIP needs room for both words of F000 NNNN
+        let mut cpu = program_on(Platform::XoChip, Quirks::modern(), &[]);
+        cpu.set_pc(0xFFFE);
+        cpu.set_memory(0xFFFE, &[0xF0, 0x00]);
+        assert_eq!(cpu.step(), Err(CpuError::PcOutOfBounds { pc: 0xFFFE }));
+    }
+
+    #[test]
+    fn index_out_of_bounds() {
+        for &(opcode, index) in [
+            (0xD015, 0xFFC),
+            (0xF133, 0xFFE),
+            (0xF355, 0xFFD),
+            (0xF365, 0xFFD),
+        ].iter() {
+            let mut cpu = program_on(Platform::Chip8, Quirks::chip48(), &[
+                opcode
+            ]);
+            cpu.set_index_register(index);
+            assert_eq!(
+                cpu.step(),
+                Err(CpuError::IndexOutOfBounds { pc: 0x200, opcode, index })
+            );
+            // Exactly reaching the end of memory is fine
+            let mut cpu = program_on(Platform::Chip8, Quirks::chip48(), &[
+                opcode
+            ]);
+            cpu.set_index_register(index - 1);
+            cpu.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn invalid_key() {
+        for &opcode in [0xE19E, 0xE1A1].iter() {
+            let mut cpu = program(&[opcode]);
+            cpu.set_register(0x1, 0x10);
+            assert_eq!(
+                cpu.step(),
+                Err(CpuError::InvalidKey { pc: 0x200, opcode, key: 0x10 })
+            );
+        }
+    }
+
+    #[test]
+    fn rom_too_large() {
+        let path = std::env::temp_dir().join("chip8-rom-too-large.ch8");
+        std::fs::write(&path, vec![0; 4096 - 0x200 + 1]).unwrap();
+        let mut cpu = Cpu::new();
+        let err = cpu.load_rom(path.to_str().unwrap()).unwrap_err();
+        let inner = err.into_inner().unwrap();
+        assert_eq!(
+            inner.downcast_ref::<CpuError>(),
+            Some(&CpuError::RomTooLarge { size: 3585, max: 3584 })
+        );
+    }
 }
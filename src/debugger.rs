@@ -0,0 +1,540 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+
+use cpu::{AccessKind, Cpu, Opcode};
+use frontend::Command;
+
+const HELP: &str = "\
+Commands:
+  step [n]          (s)   Execute n instructions, 1 by default
+  next              (n)   Execute one instruction, stepping over calls
+  continue          (c)   Run until a breakpoint or watchpoint is hit
+  break <addr>      (b)   Pause before executing the instruction at addr
+  delete <addr>     (d)   Remove the breakpoint at addr
+  watch <addr> [r|w|rw] (w) Pause after addr is read and/or written
+  unwatch <addr>          Remove the watchpoint at addr
+  list              (l)   List breakpoints and watchpoints
+  regs              (r)   Show the registers and timers
+  stack             (bt)  Show the call stack
+  mem <addr> [len]  (x)   Show len bytes of memory from addr, 64 by default
+  quit              (q)   Stop the emulator
+An empty line repeats the last command. Addresses are in hexadecimal.";
+
+/// What the debugger is waiting for before it pauses the Cpu again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Take commands before every instruction
+    Paused,
+    /// Execute this many more instructions before pausing
+    Stepping(usize),
+    /// Run until a subroutine returns to the given address with the stack
+    /// back at the given depth
+    SteppingOver { return_addr: u16, depth: usize },
+    /// Run until a breakpoint or a watchpoint is hit
+    Running,
+}
+
+/// Which kinds of access to an address pause the Cpu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub read: bool,
+    pub write: bool,
+}
+
+/// The result of a command typed at the prompt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    /// Keep taking commands
+    Prompt,
+    /// Let the Cpu execute instructions again
+    Resume,
+    /// Stop the emulator
+    Quit,
+}
+
+/// An interactive debugger driven from stdin
+/// It is handed the Cpu before and after each instruction by
+/// Cpu::run_with_debugger(), and pauses it on breakpoints, watchpoints or
+/// after single steps
+pub struct Debugger {
+    mode: Mode,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: BTreeMap<u16, Watchpoint>,
+    last_command: String,
+}
+
+impl Debugger {
+    /// Creates a new Debugger, which pauses before the first instruction
+    pub fn new() -> Self {
+        Debugger {
+            mode: Mode::Paused,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            last_command: String::new(),
+        }
+    }
+
+    /// Pauses the Cpu before it executes the instruction at an address
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// Removes a breakpoint, returning false if there was none
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    /// Pauses the Cpu after an instruction accesses an address
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address of the byte of memory to watch
+    /// * `watchpoint` - Whether reads, writes or both should pause the Cpu
+    ///
+    pub fn add_watchpoint(&mut self, addr: u16, watchpoint: Watchpoint) {
+        self.watchpoints.insert(addr, watchpoint);
+    }
+
+    /// Removes a watchpoint, returning false if there was none
+    pub fn remove_watchpoint(&mut self, addr: u16) -> bool {
+        self.watchpoints.remove(&addr).is_some()
+    }
+
+    /// Decides whether to pause before the next instruction, and takes
+    /// commands from stdin until the user resumes if so
+    /// Returns Command::Quit if the user asked to stop the emulator
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to the Cpu that is about to execute the
+    ///   instruction at its program counter
+    ///
+    pub fn before_step(&mut self, cpu: &Cpu) -> Option<Command> {
+        if !self.should_pause(cpu) {
+            return None;
+        }
+        self.print_instruction(cpu, cpu.pc());
+        self.prompt(cpu)
+    }
+
+    /// Decides whether to pause before the next instruction, pausing the
+    /// debugger if so
+    fn should_pause(&mut self, cpu: &Cpu) -> bool {
+        let pc = cpu.pc();
+        let pause = match self.mode {
+            Mode::Paused | Mode::Stepping(0) => true,
+            Mode::Stepping(remaining) => {
+                self.mode = Mode::Stepping(remaining - 1);
+                false
+            },
+            Mode::SteppingOver { return_addr, depth } => {
+                pc == return_addr && cpu.stack().len() == depth
+            },
+            Mode::Running => false,
+        };
+        let breakpoint = self.mode != Mode::Paused &&
+            self.breakpoints.contains(&pc);
+        // A single step that lands on a breakpoint would have paused there
+        // anyway, so the breakpoint is only worth a mention otherwise
+        if breakpoint && !pause {
+            println!("Breakpoint at {:#06x}", pc);
+        }
+        if !pause && !breakpoint {
+            return false;
+        }
+        self.mode = Mode::Paused;
+        true
+    }
+
+    /// Checks the memory accessed by the last instruction against the
+    /// watchpoints, and pauses before the next instruction if one was hit
+    pub fn after_step(&mut self, cpu: &Cpu) {
+        for access in cpu.memory_accesses() {
+            let hit = match self.watchpoints.get(&access.addr) {
+                Some(watchpoint) => match access.kind {
+                    AccessKind::Read => watchpoint.read,
+                    AccessKind::Write => watchpoint.write,
+                },
+                None => false,
+            };
+            if hit {
+                let kind = match access.kind {
+                    AccessKind::Read => "read from",
+                    AccessKind::Write => "write to",
+                };
+                println!("Watchpoint: {} {:#06x}", kind, access.addr);
+                self.mode = Mode::Paused;
+            }
+        }
+    }
+
+    /// Reads commands from stdin until one of them resumes the Cpu
+    fn prompt(&mut self, cpu: &Cpu) -> Option<Command> {
+        let stdin = io::stdin();
+        loop {
+            print!("(chip8) ");
+            let _ = io::stdout().flush();
+
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => return Some(Command::Quit),
+                Ok(_) => {},
+            }
+            let line = line.trim();
+            let line = if line.is_empty() {
+                self.last_command.clone()
+            }
+            else {
+                self.last_command = line.to_string();
+                line.to_string()
+            };
+
+            match self.execute(cpu, &line) {
+                Ok(Action::Prompt) => {},
+                Ok(Action::Resume) => return None,
+                Ok(Action::Quit) => return Some(Command::Quit),
+                Err(err) => println!("{}", err),
+            }
+        }
+    }
+
+    /// Carries out a single command typed at the prompt
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to the paused Cpu
+    /// * `line` - The command followed by its arguments
+    ///
+    fn execute(&mut self, cpu: &Cpu, line: &str) -> Result<Action, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(Action::Prompt),
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "help" | "h" | "?" => {
+                println!("{}", HELP);
+            },
+            "step" | "s" => {
+                let count = match args.first() {
+                    Some(count) => count.parse::<usize>()
+                        .map_err(|_| format!("Invalid count: {}", count))?,
+                    None => 1,
+                };
+                if count == 0 {
+                    return Ok(Action::Prompt);
+                }
+                self.mode = Mode::Stepping(count - 1);
+                return Ok(Action::Resume);
+            },
+            "next" | "n" => {
+                self.mode = match cpu.instruction_at(cpu.pc()) {
+                    Ok(Opcode::CallSubroutine(_)) => Mode::SteppingOver {
+                        return_addr: cpu.pc() + 2,
+                        depth: cpu.stack().len(),
+                    },
+                    _ => Mode::Stepping(0),
+                };
+                return Ok(Action::Resume);
+            },
+            "continue" | "c" => {
+                self.mode = Mode::Running;
+                return Ok(Action::Resume);
+            },
+            "break" | "b" => {
+                let addr = parse_addr(args.first())?;
+                self.add_breakpoint(addr);
+                println!("Breakpoint set at {:#06x}", addr);
+            },
+            "delete" | "d" => {
+                let addr = parse_addr(args.first())?;
+                if !self.remove_breakpoint(addr) {
+                    return Err(format!("No breakpoint at {:#06x}", addr));
+                }
+            },
+            "watch" | "w" => {
+                let addr = parse_addr(args.first())?;
+                let watchpoint = match args.get(1).cloned().unwrap_or("rw") {
+                    "r" => Watchpoint { read: true, write: false },
+                    "w" => Watchpoint { read: false, write: true },
+                    "rw" => Watchpoint { read: true, write: true },
+                    kind => {
+                        return Err(format!("Invalid watchpoint kind: {}", kind))
+                    },
+                };
+                self.add_watchpoint(addr, watchpoint);
+                println!("Watchpoint set at {:#06x}", addr);
+            },
+            "unwatch" => {
+                let addr = parse_addr(args.first())?;
+                if !self.remove_watchpoint(addr) {
+                    return Err(format!("No watchpoint at {:#06x}", addr));
+                }
+            },
+            "list" | "l" => {
+                for addr in &self.breakpoints {
+                    println!("Breakpoint at {:#06x}", addr);
+                }
+                for (addr, watchpoint) in &self.watchpoints {
+                    let kind = match (watchpoint.read, watchpoint.write) {
+                        (true, true) => "rw",
+                        (true, false) => "r",
+                        _ => "w",
+                    };
+                    println!("Watchpoint at {:#06x} ({})", addr, kind);
+                }
+            },
+            "regs" | "r" => {
+                print_registers(cpu);
+            },
+            "stack" | "bt" => {
+                print_stack(cpu);
+            },
+            "mem" | "x" => {
+                let addr = parse_addr(args.first())?;
+                let len = match args.get(1) {
+                    Some(len) => len.parse::<usize>()
+                        .map_err(|_| format!("Invalid length: {}", len))?,
+                    None => 64,
+                };
+                print_memory(cpu, addr as usize, len);
+            },
+            "quit" | "q" => {
+                return Ok(Action::Quit);
+            },
+            _ => {
+                return Err(format!(
+                    "Unknown command: {}, type help for a list",
+                    command
+                ));
+            },
+        }
+        Ok(Action::Prompt)
+    }
+
+    /// Shows the instruction the Cpu is paused at
+    fn print_instruction(&self, cpu: &Cpu, pc: u16) {
+        let memory = cpu.memory();
+        if pc as usize + 1 >= memory.len() {
+            println!("{:#06x}: (end of memory)", pc);
+            return;
+        }
+        let opcode =
+            (memory[pc as usize] as u16) << 8 | memory[pc as usize + 1] as u16;
+        match cpu.instruction_at(pc) {
+            Ok(instruction) => {
+                println!("{:#06x}: {:04x}  {}", pc, opcode, instruction)
+            },
+            Err(_) => println!("{:#06x}: {:04x}  (illegal)", pc, opcode),
+        }
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger::new()
+    }
+}
+
+/// Parses a hexadecimal address, with or without a leading 0x
+fn parse_addr(arg: Option<&&str>) -> Result<u16, String> {
+    let arg = match arg {
+        Some(arg) => *arg,
+        None => return Err("Missing address".to_string()),
+    };
+    let digits = arg.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16)
+        .map_err(|_| format!("Invalid address: {}", arg))
+}
+
+/// Shows V0 through VF, I, the stack pointer and the timers
+fn print_registers(cpu: &Cpu) {
+    println!(
+        "PC: {:#06x}  I: {:#06x}  SP: {}  DT: {}  ST: {}",
+        cpu.pc(),
+        cpu.index_register(),
+        cpu.stack().len(),
+        cpu.delay_timer(),
+        cpu.sound_timer()
+    );
+    for row in cpu.registers().chunks(8).enumerate() {
+        let (i, values) = row;
+        let line: Vec<String> = values.iter().enumerate()
+            .map(|(j, value)| format!("V{:X}: {:02x}", i*8 + j, value))
+            .collect();
+        println!("{}", line.join("  "));
+    }
+}
+
+/// Shows the return addresses on the stack, innermost call first
+fn print_stack(cpu: &Cpu) {
+    if cpu.stack().is_empty() {
+        println!("The stack is empty");
+    }
+    for (depth, addr) in cpu.stack().iter().rev().enumerate() {
+        println!("#{} returns to {:#06x}", depth, addr);
+    }
+}
+
+/// Shows a hex dump of memory, 16 bytes per line
+fn print_memory(cpu: &Cpu, addr: usize, len: usize) {
+    let memory = cpu.memory();
+    let end = (addr + len).min(memory.len());
+    if addr >= end {
+        println!("Address out of range");
+        return;
+    }
+    for (i, line) in memory[addr..end].chunks(16).enumerate() {
+        let bytes: Vec<String> = line.iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        println!("{:#06x}: {}", addr + i*16, bytes.join(" "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Cpu that has loaded a program
+    fn program(bytes: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(bytes).unwrap();
+        cpu
+    }
+
+    /// Runs the Cpu until the debugger pauses it, returning the number of
+    /// instructions executed
+    fn run_until_paused(debugger: &mut Debugger, cpu: &mut Cpu) -> usize {
+        let mut steps = 0;
+        while !debugger.should_pause(cpu) {
+            cpu.step().unwrap();
+            debugger.after_step(cpu);
+            steps += 1;
+            assert!(steps < 100, "The debugger never paused");
+        }
+        steps
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        let cpu = program(&[0x12, 0x00]);
+        let mut debugger = Debugger::new();
+        assert_eq!(debugger.execute(&cpu, "step 3"), Ok(Action::Resume));
+        assert_eq!(debugger.mode, Mode::Stepping(2));
+        assert_eq!(debugger.execute(&cpu, "s"), Ok(Action::Resume));
+        assert_eq!(debugger.mode, Mode::Stepping(0));
+        assert_eq!(debugger.execute(&cpu, "step 0"), Ok(Action::Prompt));
+        assert_eq!(debugger.execute(&cpu, "c"), Ok(Action::Resume));
+        assert_eq!(debugger.mode, Mode::Running);
+
+        assert_eq!(debugger.execute(&cpu, "break 0x2A0"), Ok(Action::Prompt));
+        assert!(debugger.breakpoints.contains(&0x2A0));
+        assert_eq!(debugger.execute(&cpu, "d 2a0"), Ok(Action::Prompt));
+        assert!(debugger.breakpoints.is_empty());
+        assert_eq!(debugger.execute(&cpu, "watch 300 r"), Ok(Action::Prompt));
+        assert_eq!(
+            debugger.watchpoints.get(&0x300),
+            Some(&Watchpoint { read: true, write: false })
+        );
+        assert_eq!(debugger.execute(&cpu, "unwatch 300"), Ok(Action::Prompt));
+        assert!(debugger.watchpoints.is_empty());
+        assert_eq!(debugger.execute(&cpu, "mem 200 4"), Ok(Action::Prompt));
+        assert_eq!(debugger.execute(&cpu, ""), Ok(Action::Prompt));
+        assert_eq!(debugger.execute(&cpu, "q"), Ok(Action::Quit));
+    }
+
+    #[test]
+    fn bad_arguments_are_refused() {
+        let cpu = program(&[0x12, 0x00]);
+        let mut debugger = Debugger::new();
+        for line in [
+            "step many",
+            "step -1",
+            "break",
+            "break 0xzz",
+            "break 10000",
+            "delete 300",
+            "watch",
+            "watch 300 x",
+            "unwatch 300",
+            "mem",
+            "mem 300 lots",
+            "launch",
+        ].iter() {
+            assert!(debugger.execute(&cpu, line).is_err(), "{}", line);
+        }
+        assert_eq!(debugger.mode, Mode::Paused);
+    }
+
+    #[test]
+    fn breakpoints_pause_a_running_cpu() {
+        // ADD V0, 1, ADD V1, 1 and JP 0x200, forever
+        let mut cpu = program(&[0x70, 0x01, 0x71, 0x01, 0x12, 0x00]);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        debugger.execute(&cpu, "continue").unwrap();
+        assert_eq!(run_until_paused(&mut debugger, &mut cpu), 1);
+        assert_eq!(cpu.pc(), 0x202);
+        assert_eq!(debugger.mode, Mode::Paused);
+        // Continuing leaves the breakpoint and comes back to it
+        debugger.execute(&cpu, "continue").unwrap();
+        cpu.step().unwrap();
+        assert_eq!(run_until_paused(&mut debugger, &mut cpu), 2);
+        assert_eq!(cpu.registers()[0], 2);
+    }
+
+    #[test]
+    fn watchpoints_pause_after_the_access() {
+        // LD I, 0x300, LD V0, [I], LD I, 0x300, LD [I], V0 and JP 0x208
+        let rom = [
+            0xA3, 0x00, 0xF0, 0x65, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x08,
+        ];
+        let kinds = [("w", 0x208), ("r", 0x204), ("rw", 0x204)];
+        for &(kind, pc) in kinds.iter() {
+            let mut cpu = program(&rom);
+            cpu.set_memory_tracing(true);
+            let mut debugger = Debugger::new();
+            debugger.execute(&cpu, &format!("watch 300 {}", kind)).unwrap();
+            debugger.execute(&cpu, "continue").unwrap();
+            run_until_paused(&mut debugger, &mut cpu);
+            assert_eq!(cpu.pc(), pc, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn next_steps_over_calls() {
+        // CALL 0x206, JP 0x202, then a subroutine of LD V1, 1 and RET
+        let mut cpu = program(&[
+            0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x61, 0x01, 0x00, 0xEE,
+        ]);
+        let mut debugger = Debugger::new();
+        debugger.execute(&cpu, "next").unwrap();
+        assert_eq!(
+            debugger.mode,
+            Mode::SteppingOver { return_addr: 0x202, depth: 0 }
+        );
+        cpu.step().unwrap();
+        assert_eq!(run_until_paused(&mut debugger, &mut cpu), 2);
+        assert_eq!(cpu.pc(), 0x202);
+        assert_eq!(cpu.registers()[1], 1);
+
+        // Anything else is a single step
+        debugger.execute(&cpu, "next").unwrap();
+        cpu.step().unwrap();
+        assert_eq!(run_until_paused(&mut debugger, &mut cpu), 0);
+    }
+
+    #[test]
+    fn steps_pause_on_breakpoints_they_land_on() {
+        let mut cpu = program(&[0x70, 0x01, 0x71, 0x01, 0x12, 0x00]);
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        debugger.execute(&cpu, "step 5").unwrap();
+        cpu.step().unwrap();
+        assert_eq!(run_until_paused(&mut debugger, &mut cpu), 0);
+        assert_eq!(cpu.pc(), 0x202);
+    }
+}
@@ -0,0 +1,672 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use cpu::{Cpu, Opcode, PROGRAM_START};
+use platform::Platform;
+
+/// The assembly language instructions are written in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Syntax {
+    /// The mnemonics of Cowgod's Chip-8 technical reference, e.g. `LD V0, 0x05`
+    #[default]
+    Cowgod,
+    /// The language of the Octo assembler, e.g. `v0 := 0x05`
+    Octo,
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cowgod" => Ok(Syntax::Cowgod),
+            "octo" => Ok(Syntax::Octo),
+            _ => Err(format!(
+                "Unknown syntax '{}', expected one of: cowgod, octo",
+                s
+            )),
+        }
+    }
+}
+
+/// Prints the instruction in Cowgod syntax, or in Octo syntax when the
+/// alternate flag is used
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::cpu::Opcode;
+///
+/// let opcode = Opcode::AddToRegister { addr: 0x3, value: 0x10 };
+/// assert_eq!(format!("{}", opcode), "ADD V3, 0x10");
+/// assert_eq!(format!("{:#}", opcode), "v3 += 0x10");
+/// ```
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let syntax = if f.alternate() {
+            Syntax::Octo
+        }
+        else {
+            Syntax::Cowgod
+        };
+        f.write_str(&mnemonic(self, syntax, &|_| None))
+    }
+}
+
+/// Formats the name of a register
+fn reg(index: u16, syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Cowgod => format!("V{:X}", index),
+        Syntax::Octo => format!("v{:x}", index),
+    }
+}
+
+/// Formats an 8-bit immediate value
+fn byte(value: u16) -> String {
+    format!("{:#04X}", value).replace("0X", "0x")
+}
+
+/// Formats an address, which is 12 bits wide except for the XO-CHIP long
+/// load
+fn address(addr: u16) -> String {
+    format!("{:#05X}", addr).replace("0X", "0x")
+}
+
+/// Renders an instruction as assembly
+///
+/// # Arguments
+///
+/// * `opcode` - The instruction to render
+/// * `syntax` - The assembly language to render it in
+/// * `label` - Looks up the name of the label at an address, so that jumps
+///   and loads can refer to it instead of a plain number
+///
+pub fn mnemonic(
+    opcode: &Opcode,
+    syntax: Syntax,
+    label: &dyn Fn(u16) -> Option<String>
+) -> String {
+    let target = |addr: u16| label(addr).unwrap_or_else(|| address(addr));
+    let long_target = |addr: u16| {
+        label(addr).unwrap_or_else(|| {
+            format!("{:#06X}", addr).replace("0X", "0x")
+        })
+    };
+    let r = |index: u16| reg(index, syntax);
+
+    match syntax {
+        Syntax::Cowgod => match *opcode {
+            Opcode::CallRCAProgram(addr) => format!("SYS {}", target(addr)),
+            Opcode::ClearDisplay => "CLS".to_string(),
+            Opcode::ReturnFromSubroutine => "RET".to_string(),
+            Opcode::JumpToAddr(addr) => format!("JP {}", target(addr)),
+            Opcode::CallSubroutine(addr) => format!("CALL {}", target(addr)),
+            Opcode::SkipIfRegisterSet { addr, value } => {
+                format!("SE {}, {}", r(addr), byte(value))
+            },
+            Opcode::SkipIfRegisterNotSet { addr, value } => {
+                format!("SNE {}, {}", r(addr), byte(value))
+            },
+            Opcode::SkipIfRegistersEqual { first, second } => {
+                format!("SE {}, {}", r(first), r(second))
+            },
+            Opcode::SetRegister { addr, value } => {
+                format!("LD {}, {}", r(addr), byte(value))
+            },
+            Opcode::AddToRegister { addr, value } => {
+                format!("ADD {}, {}", r(addr), byte(value))
+            },
+            Opcode::AssignRegister { first, second } => {
+                format!("LD {}, {}", r(first), r(second))
+            },
+            Opcode::AssignRegisterBitwiseOr { first, second } => {
+                format!("OR {}, {}", r(first), r(second))
+            },
+            Opcode::AssignRegisterBitwiseAnd { first, second } => {
+                format!("AND {}, {}", r(first), r(second))
+            },
+            Opcode::AssignRegisterBitwiseXor { first, second } => {
+                format!("XOR {}, {}", r(first), r(second))
+            },
+            Opcode::AddRegisters { first, second } => {
+                format!("ADD {}, {}", r(first), r(second))
+            },
+            Opcode::SubtractRegisters { first, second } => {
+                format!("SUB {}, {}", r(first), r(second))
+            },
+            Opcode::AssignRegisterBitshiftRight { first, second } => {
+                format!("SHR {}, {}", r(first), r(second))
+            },
+            Opcode::SubtractFirstRegister { first, second } => {
+                format!("SUBN {}, {}", r(first), r(second))
+            },
+            Opcode::AssignRegistersBitshiftLeft { first, second } => {
+                format!("SHL {}, {}", r(first), r(second))
+            },
+            Opcode::SkipIfRegistersNotEqual { first, second } => {
+                format!("SNE {}, {}", r(first), r(second))
+            },
+            Opcode::SetIndexRegister(addr) => format!("LD I, {}", target(addr)),
+            Opcode::SetProgramCounter(addr) => {
+                format!("JP V0, {}", target(addr))
+            },
+            Opcode::AssignRandomValue { addr, value } => {
+                format!("RND {}, {}", r(addr), byte(value))
+            },
+            Opcode::Draw { x, y, height } => {
+                format!("DRW {}, {}, {}", r(x), r(y), height)
+            },
+            Opcode::SkipIfKeyPressed(addr) => format!("SKP {}", r(addr)),
+            Opcode::SkipIfKeyNotPressed(addr) => format!("SKNP {}", r(addr)),
+            Opcode::AssignToDelayTime(addr) => format!("LD {}, DT", r(addr)),
+            Opcode::AssignOnKeyPress(addr) => format!("LD {}, K", r(addr)),
+            Opcode::SetDelayTimer(addr) => format!("LD DT, {}", r(addr)),
+            Opcode::SetSoundTimer(addr) => format!("LD ST, {}", r(addr)),
+            Opcode::AddToIndexRegister(addr) => format!("ADD I, {}", r(addr)),
+            Opcode::SetIndexRegisterToSpriteAddr(addr) => {
+                format!("LD F, {}", r(addr))
+            },
+            Opcode::SetBCD(addr) => format!("LD B, {}", r(addr)),
+            Opcode::DumpRegister(addr) => format!("LD [I], {}", r(addr)),
+            Opcode::LoadRegister(addr) => format!("LD {}, [I]", r(addr)),
+            Opcode::ScrollDown(rows) => format!("SCD {}", rows),
+            Opcode::ScrollRight => "SCR".to_string(),
+            Opcode::ScrollLeft => "SCL".to_string(),
+            Opcode::Exit => "EXIT".to_string(),
+            Opcode::DisableHighRes => "LOW".to_string(),
+            Opcode::EnableHighRes => "HIGH".to_string(),
+            Opcode::SetIndexRegisterToBigSpriteAddr(addr) => {
+                format!("LD HF, {}", r(addr))
+            },
+            Opcode::SaveFlags(addr) => format!("LD R, {}", r(addr)),
+            Opcode::LoadFlags(addr) => format!("LD {}, R", r(addr)),
+            Opcode::ScrollUp(rows) => format!("SCU {}", rows),
+            Opcode::SaveRegisterRange { first, second } => {
+                format!("SAVE {}, {}", r(first), r(second))
+            },
+            Opcode::LoadRegisterRange { first, second } => {
+                format!("LOAD {}, {}", r(first), r(second))
+            },
+            Opcode::SetIndexRegisterLong(addr) => {
+                format!("LD I, LONG {}", long_target(addr))
+            },
+            Opcode::SelectPlanes(planes) => format!("PLANE {}", planes),
+            Opcode::LoadAudioPattern => "AUDIO".to_string(),
+            Opcode::SetPitch(addr) => format!("PITCH {}", r(addr)),
+        },
+        Syntax::Octo => match *opcode {
+            // Octo has no way of calling machine code, so the instruction
+            // is written out as raw bytes
+            Opcode::CallRCAProgram(addr) => {
+                format!("{} {}", byte(addr >> 8), byte(addr & 0xFF))
+            },
+            Opcode::ClearDisplay => "clear".to_string(),
+            Opcode::ReturnFromSubroutine => "return".to_string(),
+            Opcode::JumpToAddr(addr) => format!("jump {}", target(addr)),
+            Opcode::CallSubroutine(addr) => match label(addr) {
+                Some(name) => name,
+                None => format!(":call {}", address(addr)),
+            },
+            Opcode::SkipIfRegisterSet { addr, value } => {
+                format!("if {} != {} then", r(addr), byte(value))
+            },
+            Opcode::SkipIfRegisterNotSet { addr, value } => {
+                format!("if {} == {} then", r(addr), byte(value))
+            },
+            Opcode::SkipIfRegistersEqual { first, second } => {
+                format!("if {} != {} then", r(first), r(second))
+            },
+            Opcode::SetRegister { addr, value } => {
+                format!("{} := {}", r(addr), byte(value))
+            },
+            Opcode::AddToRegister { addr, value } => {
+                format!("{} += {}", r(addr), byte(value))
+            },
+            Opcode::AssignRegister { first, second } => {
+                format!("{} := {}", r(first), r(second))
+            },
+            Opcode::AssignRegisterBitwiseOr { first, second } => {
+                format!("{} |= {}", r(first), r(second))
+            },
+            Opcode::AssignRegisterBitwiseAnd { first, second } => {
+                format!("{} &= {}", r(first), r(second))
+            },
+            Opcode::AssignRegisterBitwiseXor { first, second } => {
+                format!("{} ^= {}", r(first), r(second))
+            },
+            Opcode::AddRegisters { first, second } => {
+                format!("{} += {}", r(first), r(second))
+            },
+            Opcode::SubtractRegisters { first, second } => {
+                format!("{} -= {}", r(first), r(second))
+            },
+            Opcode::AssignRegisterBitshiftRight { first, second } => {
+                format!("{} >>= {}", r(first), r(second))
+            },
+            Opcode::SubtractFirstRegister { first, second } => {
+                format!("{} =- {}", r(first), r(second))
+            },
+            Opcode::AssignRegistersBitshiftLeft { first, second } => {
+                format!("{} <<= {}", r(first), r(second))
+            },
+            Opcode::SkipIfRegistersNotEqual { first, second } => {
+                format!("if {} == {} then", r(first), r(second))
+            },
+            Opcode::SetIndexRegister(addr) => format!("i := {}", target(addr)),
+            Opcode::SetProgramCounter(addr) => {
+                format!("jump0 {}", target(addr))
+            },
+            Opcode::AssignRandomValue { addr, value } => {
+                format!("{} := random {}", r(addr), byte(value))
+            },
+            Opcode::Draw { x, y, height } => {
+                format!("sprite {} {} {}", r(x), r(y), height)
+            },
+            Opcode::SkipIfKeyPressed(addr) => {
+                format!("if {} -key then", r(addr))
+            },
+            Opcode::SkipIfKeyNotPressed(addr) => {
+                format!("if {} key then", r(addr))
+            },
+            Opcode::AssignToDelayTime(addr) => format!("{} := delay", r(addr)),
+            Opcode::AssignOnKeyPress(addr) => format!("{} := key", r(addr)),
+            Opcode::SetDelayTimer(addr) => format!("delay := {}", r(addr)),
+            Opcode::SetSoundTimer(addr) => format!("buzzer := {}", r(addr)),
+            Opcode::AddToIndexRegister(addr) => format!("i += {}", r(addr)),
+            Opcode::SetIndexRegisterToSpriteAddr(addr) => {
+                format!("i := hex {}", r(addr))
+            },
+            Opcode::SetBCD(addr) => format!("bcd {}", r(addr)),
+            Opcode::DumpRegister(addr) => format!("save {}", r(addr)),
+            Opcode::LoadRegister(addr) => format!("load {}", r(addr)),
+            Opcode::ScrollDown(rows) => format!("scroll-down {}", rows),
+            Opcode::ScrollRight => "scroll-right".to_string(),
+            Opcode::ScrollLeft => "scroll-left".to_string(),
+            Opcode::Exit => "exit".to_string(),
+            Opcode::DisableHighRes => "lores".to_string(),
+            Opcode::EnableHighRes => "hires".to_string(),
+            Opcode::SetIndexRegisterToBigSpriteAddr(addr) => {
+                format!("i := bighex {}", r(addr))
+            },
+            Opcode::SaveFlags(addr) => format!("saveflags {}", r(addr)),
+            Opcode::LoadFlags(addr) => format!("loadflags {}", r(addr)),
+            Opcode::ScrollUp(rows) => format!("scroll-up {}", rows),
+            Opcode::SaveRegisterRange { first, second } => {
+                format!("save {} - {}", r(first), r(second))
+            },
+            Opcode::LoadRegisterRange { first, second } => {
+                format!("load {} - {}", r(first), r(second))
+            },
+            Opcode::SetIndexRegisterLong(addr) => {
+                format!("i := long {}", long_target(addr))
+            },
+            Opcode::SelectPlanes(planes) => format!("plane {}", planes),
+            Opcode::LoadAudioPattern => "audio".to_string(),
+            Opcode::SetPitch(addr) => format!("pitch := {}", r(addr)),
+        },
+    }
+}
+
+/// What an address is used for, as far as the disassembler can tell
+/// Later kinds take precedence when an address is used in several ways
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    /// I is pointed at the address
+    Data,
+    /// A jump lands at the address
+    Code,
+    /// A subroutine starts at the address
+    Subroutine,
+}
+
+/// The instructions found by following the control flow of a rom, and the
+/// addresses that deserve a label
+struct Analysis {
+    code: BTreeMap<u16, Opcode>,
+    labels: BTreeMap<u16, LabelKind>,
+}
+
+/// Decodes the instruction at an address of the rom, if there is a legal
+/// one for the platform
+/// Addresses are wider than 16 bits, so that those past the end of memory
+/// can be asked for and turned down
+fn decode_at(rom: &[u8], platform: Platform, addr: u32) -> Option<Opcode> {
+    let word = |addr: u32| -> Option<u16> {
+        let offset = addr.checked_sub(PROGRAM_START as u32)? as usize;
+        if offset + 1 >= rom.len() {
+            return None;
+        }
+        Some((rom[offset] as u16) << 8 | rom[offset + 1] as u16)
+    };
+    let opcode = word(addr)?;
+    if opcode == 0xF000 && platform == Platform::XoChip {
+        return Some(Opcode::SetIndexRegisterLong(word(addr + 2)?));
+    }
+    match Cpu::decode(opcode) {
+        Ok(instruction) if platform.supports(&instruction) => Some(instruction),
+        _ => None,
+    }
+}
+
+/// Walks every path the program counter can take, starting at 0x200
+/// Anything that is never reached is assumed to be data
+fn analyze(rom: &[u8], platform: Platform) -> Analysis {
+    let end = PROGRAM_START as u32 + rom.len() as u32;
+    let mut code = BTreeMap::new();
+    let mut labels = BTreeMap::new();
+    let mut pending = vec![PROGRAM_START as u32];
+
+    {
+        let mut label = |addr: u16, kind: LabelKind| {
+            if addr >= PROGRAM_START && (addr as u32) < end {
+                let entry = labels.entry(addr).or_insert(kind);
+                *entry = (*entry).max(kind);
+            }
+        };
+
+        while let Some(addr) = pending.pop() {
+            // The program counter cannot go past the end of memory, nor
+            // past the end of the rom into memory that holds nothing
+            if addr >= end || code.contains_key(&(addr as u16)) {
+                continue;
+            }
+            let instruction = match decode_at(rom, platform, addr) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            code.insert(addr as u16, instruction);
+            let next = addr + instruction.size() as u32;
+
+            match instruction {
+                Opcode::JumpToAddr(target) |
+                Opcode::SetProgramCounter(target) => {
+                    label(target, LabelKind::Code);
+                    pending.push(target as u32);
+                },
+                Opcode::CallSubroutine(target) => {
+                    label(target, LabelKind::Subroutine);
+                    pending.push(target as u32);
+                    pending.push(next);
+                },
+                Opcode::ReturnFromSubroutine | Opcode::Exit => {},
+                Opcode::SkipIfRegisterSet { .. } |
+                Opcode::SkipIfRegisterNotSet { .. } |
+                Opcode::SkipIfRegistersEqual { .. } |
+                Opcode::SkipIfRegistersNotEqual { .. } |
+                Opcode::SkipIfKeyPressed(_) |
+                Opcode::SkipIfKeyNotPressed(_) => {
+                    let skipped = decode_at(rom, platform, next)
+                        .map(|instruction| instruction.size() as u32)
+                        .unwrap_or(2);
+                    pending.push(next);
+                    pending.push(next + skipped);
+                },
+                Opcode::SetIndexRegister(target) |
+                Opcode::SetIndexRegisterLong(target) => {
+                    label(target, LabelKind::Data);
+                    pending.push(next);
+                },
+                _ => pending.push(next),
+            }
+        }
+    }
+
+    Analysis { code, labels }
+}
+
+/// A line of the disassembly
+enum Item {
+    Code(Opcode),
+    Data(Vec<u8>),
+}
+
+/// Disassembles a rom, separating code from data by following jumps,
+/// calls and skips from 0x200, and naming every jump target with a label
+/// The output can be fed back to the assembler to rebuild the same rom
+///
+/// # Arguments
+///
+/// * `rom` - The contents of the rom, as loaded at 0x200
+/// * `platform` - Decides which instructions are legal, and thus code
+/// * `syntax` - The assembly language to write the output in
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::disasm::{disassemble, Syntax};
+/// use chip8_rust::platform::Platform;
+///
+/// let rom = [0x00, 0xE0, 0x12, 0x02];
+/// let listing = disassemble(&rom, Platform::Chip8, Syntax::Cowgod);
+/// assert!(listing.contains("JP label_0202"));
+/// ```
+pub fn disassemble(rom: &[u8], platform: Platform, syntax: Syntax) -> String {
+    let analysis = analyze(rom, platform);
+    let end = PROGRAM_START as u32 + rom.len() as u32;
+
+    // Lay out the lines first, so that labels which point into the middle
+    // of an instruction can be dropped
+    let mut items: Vec<(u16, Item)> = vec![];
+    let mut covered = HashSet::new();
+    let mut addr = PROGRAM_START as u32;
+    while addr < end {
+        let here = addr as u16;
+        let instruction = analysis.code.get(&here).cloned().filter(|op| {
+            (1..op.size()).all(|i| !analysis.code.contains_key(&(here + i)))
+        });
+        match instruction {
+            Some(instruction) => {
+                for i in 1..instruction.size() {
+                    covered.insert(here + i);
+                }
+                items.push((here, Item::Code(instruction)));
+                addr += instruction.size() as u32;
+            },
+            None => {
+                let value = rom[(addr - PROGRAM_START as u32) as usize];
+                let extend = match items.last() {
+                    Some(&(start, Item::Data(ref bytes))) => {
+                        let next = start as u32 + bytes.len() as u32;
+                        bytes.len() < 8 && next == addr
+                            && !analysis.labels.contains_key(&here)
+                    },
+                    _ => false,
+                };
+                if extend {
+                    if let Some(&mut (_, Item::Data(ref mut bytes))) =
+                        items.last_mut()
+                    {
+                        bytes.push(value);
+                    }
+                }
+                else {
+                    items.push((here, Item::Data(vec![value])));
+                }
+                addr += 1;
+            },
+        }
+    }
+
+    let labels: BTreeMap<u16, String> = analysis.labels.iter()
+        .filter(|&(addr, _)| !covered.contains(addr))
+        .map(|(&addr, &kind)| {
+            let name = if addr == PROGRAM_START && syntax == Syntax::Octo {
+                "main".to_string()
+            }
+            else {
+                let prefix = match kind {
+                    LabelKind::Data => "data",
+                    LabelKind::Code => "label",
+                    LabelKind::Subroutine => "sub",
+                };
+                format!("{}_{:04X}", prefix, addr)
+            };
+            (addr, name)
+        })
+        .collect();
+    let lookup = |addr: u16| labels.get(&addr).cloned();
+
+    let comment = match syntax {
+        Syntax::Cowgod => ";",
+        Syntax::Octo => "#",
+    };
+    let mut out = format!("{} Disassembled for {}\n", comment, platform);
+    for (addr, item) in items {
+        if let Some(name) = labels.get(&addr) {
+            match syntax {
+                Syntax::Cowgod => out.push_str(&format!("\n{}:\n", name)),
+                Syntax::Octo => out.push_str(&format!("\n: {}\n", name)),
+            }
+        }
+        let (text, bytes) = match item {
+            Item::Code(instruction) => {
+                let text = mnemonic(&instruction, syntax, &lookup);
+                let offset = (addr - PROGRAM_START) as usize;
+                let size = instruction.size() as usize;
+                (text, rom[offset..offset + size].to_vec())
+            },
+            Item::Data(bytes) => {
+                let values: Vec<String> = bytes.iter()
+                    .map(|&value| byte(value as u16))
+                    .collect();
+                let text = match syntax {
+                    Syntax::Cowgod => format!("db {}", values.join(", ")),
+                    Syntax::Octo => values.join(" "),
+                };
+                (text, bytes)
+            },
+        };
+        let hex: Vec<String> = bytes.iter()
+            .map(|value| format!("{:02X}", value))
+            .collect();
+        out.push_str(&format!(
+            "    {:<32}{} {:04X}: {}\n",
+            text,
+            comment,
+            addr,
+            hex.join(" ")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asm;
+
+    /// Jumps, calls, a skip, a jump table and data that would decode as an
+    /// instruction
+    const SOURCE: &str = "\
+        LD I, sprite\n\
+        CALL draw\n\
+        SE V0, 1\n\
+        JP skipped\n\
+        JP V0, table\n\
+        draw: DRW V0, V1, 3\n\
+        RET\n\
+        skipped: JP skipped\n\
+        table: JP draw\n\
+        JP skipped\n\
+        sprite: db 0x00, 0xE0, 0xF0\n\
+    ";
+
+    #[test]
+    fn code_is_told_apart_from_data() {
+        let rom = asm::assemble(SOURCE).unwrap();
+        assert_eq!(
+            disassemble(&rom, Platform::Chip8, Syntax::Cowgod),
+            "\
+            ; Disassembled for chip8\n    \
+                LD I, data_0214                 ; 0200: A2 14\n    \
+                CALL sub_020A                   ; 0202: 22 0A\n    \
+                SE V0, 0x01                     ; 0204: 30 01\n    \
+                JP label_020E                   ; 0206: 12 0E\n    \
+                JP V0, label_0210               ; 0208: B2 10\n\
+            \n\
+            sub_020A:\n    \
+                DRW V0, V1, 3                   ; 020A: D0 13\n    \
+                RET                             ; 020C: 00 EE\n\
+            \n\
+            label_020E:\n    \
+                JP label_020E                   ; 020E: 12 0E\n\
+            \n\
+            label_0210:\n    \
+                JP sub_020A                     ; 0210: 12 0A\n    \
+                db 0x12, 0x0E                   ; 0212: 12 0E\n\
+            \n\
+            data_0214:\n    \
+                db 0x00, 0xE0, 0xF0             ; 0214: 00 E0 F0\n\
+            "
+        );
+    }
+
+    #[test]
+    fn jump_tables_are_only_followed_to_their_start() {
+        let rom = asm::assemble(SOURCE).unwrap();
+        let analysis = analyze(&rom, Platform::Chip8);
+        // BNNN lands wherever V0 says, which cannot be known without
+        // running the rom, so only its first entry counts as code
+        assert!(analysis.code.contains_key(&0x210));
+        assert!(!analysis.code.contains_key(&0x212));
+        assert_eq!(analysis.labels.get(&0x210), Some(&LabelKind::Code));
+        // Both sides of the skip are followed
+        assert!(analysis.code.contains_key(&0x206));
+        assert!(analysis.code.contains_key(&0x208));
+    }
+
+    #[test]
+    fn labels_take_the_strongest_use() {
+        // 0x206 is both jumped to and called, and I points at 0x200
+        let rom = [0xA2, 0x00, 0x12, 0x06, 0x00, 0x00, 0x22, 0x06];
+        let analysis = analyze(&rom, Platform::Chip8);
+        assert_eq!(analysis.labels.get(&0x200), Some(&LabelKind::Data));
+        assert_eq!(
+            analysis.labels.get(&0x206),
+            Some(&LabelKind::Subroutine)
+        );
+        let listing = disassemble(&rom, Platform::Chip8, Syntax::Octo);
+        assert!(listing.contains("\n: main\n"), "{}", listing);
+        assert!(listing.contains("\n: sub_0206\n"), "{}", listing);
+        assert!(listing.contains("jump sub_0206"), "{}", listing);
+    }
+
+    #[test]
+    fn roms_may_run_to_the_end_of_memory() {
+        // The largest rom there is, cleared the screen over and over
+        let mut rom = [0x00, 0xE0].repeat(0x7F00);
+        assert_eq!(PROGRAM_START as usize + rom.len(), 0x10000);
+        let listing = disassemble(&rom, Platform::XoChip, Syntax::Cowgod);
+        assert!(listing.ends_with("; FFFE: 00 E0\n"), "{}", &listing[..80]);
+
+        // A skip and a long load that would go past the end
+        rom[0xFDFC..].copy_from_slice(&[0x30, 0x00, 0xF0, 0x00]);
+        let analysis = analyze(&rom, Platform::XoChip);
+        assert!(analysis.code.contains_key(&0xFFFC));
+        assert!(!analysis.code.contains_key(&0xFFFE));
+        let listing = disassemble(&rom, Platform::XoChip, Syntax::Cowgod);
+        assert!(listing.ends_with("; FFFE: F0 00\n"), "{}", &listing[..80]);
+    }
+
+    #[test]
+    fn listings_assemble_back_into_the_same_rom() {
+        let source = asm::assemble(SOURCE).unwrap();
+        let opcodes: &[u8] = include_bytes!("../tests/roms/opcodes.ch8");
+        let quirks: &[u8] = include_bytes!("../tests/roms/quirks.ch8");
+        let schip: &[u8] = include_bytes!("../tests/roms/schip.ch8");
+        let xochip: &[u8] = include_bytes!("../tests/roms/xochip.ch8");
+        for &(rom, platform) in [
+            (&source[..], Platform::Chip8),
+            (opcodes, Platform::Chip8),
+            (quirks, Platform::Chip8),
+            (schip, Platform::SuperChip),
+            (xochip, Platform::XoChip),
+        ].iter() {
+            let listing = disassemble(rom, platform, Syntax::Cowgod);
+            let assembled = asm::assemble(&listing)
+                .unwrap_or_else(|err| panic!("{}\n{}", err, listing));
+            assert_eq!(&assembled[..], rom, "{}", listing);
+        }
+    }
+}
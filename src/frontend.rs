@@ -0,0 +1,162 @@
+use cpu::Cpu;
+use scheduler::Pace;
+
+/// A request made by the user through a frontend, outside of the Chip-8
+/// keypad itself
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Quit,
+    /// Save the state of the machine to a numbered slot
+    SaveState(u8),
+    /// Restore the state of the machine from a numbered slot
+    LoadState(u8),
+    /// Step back one frame in time, sent for as long as the user wants to
+    /// keep going back
+    Rewind,
+    /// Stop the emulator, or let it carry on
+    Pause,
+    /// Run a single frame while paused, or pause while running
+    FrameAdvance,
+    /// Switch between running unthrottled and running at the chosen speed
+    Turbo,
+    /// Halve the speed, for slow motion
+    SlowDown,
+    /// Double the speed, up to full speed
+    SpeedUp,
+    /// Start the rom over from the state it was in when the emulator
+    /// started running it
+    Reset,
+}
+
+/// Everything the Cpu needs from the outside world in order to run
+/// Implementations provide the display, the sound and the keypad, which
+/// lets the same Cpu be driven by SDL, a terminal or nothing at all
+pub trait Frontend {
+    /// Processes any pending input, updating the keypad of the Cpu
+    /// Returns a command if the user asked for one
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   set the values of the key states
+    ///
+    fn poll_input(&mut self, cpu: &mut Cpu) -> Option<Command>;
+
+    /// Redraws the screen from the display of the Cpu
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A reference to a Cpu object, which is used to be able to
+    ///   read from the display values
+    ///
+    fn draw(&mut self, cpu: &Cpu);
+
+    /// Whether draw() blocks until the vertical blank of a 60 Hz display
+    /// If so, the Cpu draws every frame and lets the display pace it;
+    /// otherwise the Cpu keeps time with the clock of the host
+    fn syncs_to_vblank(&self) -> bool {
+        false
+    }
+
+    /// Tells the frontend how fast the emulator runs whenever that changes,
+    /// so that it can show it on screen
+    /// The display is redrawn right after
+    ///
+    /// # Arguments
+    ///
+    /// * `pace` - The new speed, which prints the way it should be shown
+    ///
+    fn set_pace(&mut self, _pace: Pace) {}
+
+    /// Starts playing the tone of the sound timer
+    fn play_sound(&mut self);
+
+    /// Stops playing the tone of the sound timer
+    fn stop_sound(&mut self);
+
+    /// Changes the waveform played while the sound timer is active
+    /// Frontends that can only beep are free to ignore it
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - 128 samples of 1-bit audio, most significant bit first
+    /// * `rate` - The number of samples to play each second
+    ///
+    fn set_audio_pattern(&mut self, _pattern: &[u8; 16], _rate: f32) {}
+}
+
+/// A frontend that ignores all output and never produces any input
+/// Useful for running a rom without a window, e.g. in tests
+#[derive(Default)]
+pub struct NullFrontend;
+
+impl Frontend for NullFrontend {
+    fn poll_input(&mut self, _cpu: &mut Cpu) -> Option<Command> {
+        None
+    }
+
+    fn draw(&mut self, _cpu: &Cpu) {}
+
+    fn play_sound(&mut self) {}
+
+    fn stop_sound(&mut self) {}
+}
+
+/// Handles a batch of input events in order, returning the first command
+/// any of them asked for
+/// Every event is handled even after a command, since a key released in
+/// the same batch would otherwise stay held down
+///
+/// # Arguments
+///
+/// * `events` - The events, oldest first
+/// * `handle` - Handles an event, returning a command if it asked for one
+///
+pub fn handle_batch<E, I, F>(events: I, mut handle: F) -> Option<Command>
+    where I: IntoIterator<Item = E>,
+          F: FnMut(E) -> Option<Command>
+{
+    let mut command = None;
+    for event in events {
+        let next = handle(event);
+        command = command.or(next);
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Event {
+        Hotkey(Command),
+        KeyDown(usize),
+        KeyUp(usize),
+    }
+
+    #[test]
+    fn keys_released_after_a_hotkey_are_not_lost() {
+        let mut keys = [false; 16];
+        keys[5] = true;
+        let events = vec![
+            Event::Hotkey(Command::SaveState(1)),
+            Event::KeyUp(5),
+            Event::Hotkey(Command::Quit),
+            Event::KeyDown(7),
+        ];
+        let command = handle_batch(events, |event| match event {
+            Event::Hotkey(command) => Some(command),
+            Event::KeyDown(key) => {
+                keys[key] = true;
+                None
+            },
+            Event::KeyUp(key) => {
+                keys[key] = false;
+                None
+            },
+        });
+        assert_eq!(command, Some(Command::SaveState(1)));
+        assert!(!keys[5]);
+        assert!(keys[7]);
+    }
+}
@@ -0,0 +1,390 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use core::KeyState;
+use cpu::{Cpu, CpuError, Opcode};
+
+/// Something to watch for while running without a frontend, which stops
+/// the run as soon as it holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// The next instruction to execute is at this address
+    Pc(u16),
+    /// A register holds a value
+    Register { index: u8, value: u8 },
+    /// The next instruction is a 1NNN that jumps to itself, which is how
+    /// most test roms end
+    SelfJump,
+}
+
+impl Condition {
+    /// Checks whether the condition holds for the Cpu as it is now
+    pub fn holds(self, cpu: &Cpu) -> bool {
+        match self {
+            Condition::Pc(addr) => cpu.pc() == addr,
+            Condition::Register { index, value } => {
+                cpu.registers()[index as usize] == value
+            },
+            Condition::SelfJump => {
+                let next = cpu.instruction_at(cpu.pc());
+                next == Ok(Opcode::JumpToAddr(cpu.pc()))
+            },
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    /// Parses `pc=<addr>`, `v<x>=<value>` or `self-jump`, with numbers in
+    /// decimal or in hexadecimal with a 0x prefix
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "self-jump" {
+            return Ok(Condition::SelfJump);
+        }
+        let invalid = || format!(
+            "Invalid condition '{}', expected pc=<addr>, v<x>=<value> or \
+             self-jump",
+            s
+        );
+        let mut parts = s.splitn(2, '=');
+        let name = parts.next().unwrap_or("").to_lowercase();
+        let value = parts.next().ok_or_else(invalid)?;
+        if name == "pc" {
+            return parse_number(value)
+                .ok()
+                .and_then(|addr| u16::try_from(addr).ok())
+                .map(Condition::Pc)
+                .ok_or_else(invalid);
+        }
+        let index = name.strip_prefix('v')
+            .and_then(|index| u8::from_str_radix(index, 16).ok())
+            .filter(|&index| index < 16 && name.len() == 2)
+            .ok_or_else(invalid)?;
+        let value = parse_number(value)
+            .ok()
+            .and_then(|value| u8::try_from(value).ok())
+            .ok_or_else(invalid)?;
+        Ok(Condition::Register { index, value })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Condition::Pc(addr) => write!(f, "pc={:#05x}", addr),
+            Condition::Register { index, value } => {
+                write!(f, "v{:x}={:#04x}", index, value)
+            },
+            Condition::SelfJump => write!(f, "self-jump"),
+        }
+    }
+}
+
+/// A key of the keypad to press or release at the start of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptedKey {
+    /// The number of frames run before the change
+    pub frame: u64,
+    /// The index of the key on the keypad, from 0x0 to 0xF
+    pub key: u8,
+    pub state: KeyState,
+}
+
+impl FromStr for ScriptedKey {
+    type Err = String;
+
+    /// Parses `<frame>:<key>:down|up`, with the key in hexadecimal, e.g.
+    /// `30:a:down`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!(
+            "Invalid key '{}', expected <frame>:<key>:down|up",
+            s
+        );
+        let parts: Vec<&str> = s.split([':', ' '])
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.len() != 3 {
+            return Err(invalid());
+        }
+        let frame = parts[0].parse().map_err(|_| invalid())?;
+        let key = u8::from_str_radix(parts[1], 16)
+            .ok()
+            .filter(|&key| key < 16)
+            .ok_or_else(invalid)?;
+        let state = match parts[2] {
+            "down" => KeyState::Pressed,
+            "up" => KeyState::Released,
+            _ => return Err(invalid()),
+        };
+        Ok(ScriptedKey { frame, key, state })
+    }
+}
+
+/// Parses a script of key changes, one `<frame> <key> down|up` or
+/// `<frame>:<key>:down|up` per line, with # starting a comment
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::headless;
+///
+/// let keys = headless::parse_key_script("# jump\n30 5 down\n32 5 up\n");
+/// assert_eq!(keys.unwrap().len(), 2);
+/// ```
+pub fn parse_key_script(text: &str) -> Result<Vec<ScriptedKey>, String> {
+    let mut keys = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let key = line.parse()
+            .map_err(|err| format!("Line {}: {}", i + 1, err))?;
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+/// Why a run without a frontend stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// All the frames asked for were run
+    Frames,
+    /// One of the conditions held
+    Condition(Condition),
+    /// The rom executed the SUPER-CHIP exit instruction
+    Exit,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Stop::Frames => write!(f, "frames"),
+            Stop::Condition(condition) => write!(f, "{}", condition),
+            Stop::Exit => write!(f, "exit"),
+        }
+    }
+}
+
+/// The end of a run without a frontend
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    /// The number of frames run, including the one the run stopped in
+    pub frames: u64,
+    pub stop: Stop,
+}
+
+/// Runs a rom without a frontend, a frame at a time, as fast as possible
+/// The conditions are checked before the first instruction and after
+/// every instruction from then on
+///
+/// # Arguments
+///
+/// * `cpu` - A Cpu that has loaded its rom
+/// * `frames` - The number of frames to run at most
+/// * `conditions` - Stops the run as soon as any of them holds
+/// * `keys` - The key changes to make, ordered by frame
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::cpu::Cpu;
+/// use chip8_rust::headless::{self, Condition, Stop};
+///
+/// // The condition holds before the first instruction is executed
+/// let mut cpu = Cpu::new();
+/// let condition = Condition::Pc(0x200);
+/// let outcome = headless::run(&mut cpu, 60, &[condition], &[]).unwrap();
+/// assert_eq!(outcome.stop, Stop::Condition(condition));
+/// ```
+pub fn run(
+    cpu: &mut Cpu,
+    frames: u64,
+    conditions: &[Condition],
+    keys: &[ScriptedKey]
+) -> Result<Outcome, CpuError> {
+    let check = |cpu: &Cpu| -> Option<Stop> {
+        if cpu.halted() {
+            return Some(Stop::Exit);
+        }
+        conditions.iter()
+            .find(|condition| condition.holds(cpu))
+            .map(|&condition| Stop::Condition(condition))
+    };
+    if let Some(stop) = check(cpu) {
+        return Ok(Outcome { frames: 0, stop });
+    }
+
+    let mut next_key = 0;
+    for frame in 0..frames {
+        while let Some(key) = keys.get(next_key) {
+            if key.frame > frame {
+                break;
+            }
+            cpu.set_key(key.key as usize, key.state);
+            next_key += 1;
+        }
+        for _ in 0..cpu.speed() {
+            cpu.step()?;
+            if let Some(stop) = check(cpu) {
+                return Ok(Outcome { frames: frame + 1, stop });
+            }
+        }
+        cpu.tick_timers();
+    }
+    Ok(Outcome { frames, stop: Stop::Frames })
+}
+
+/// Describes the Cpu at the end of a run as JSON: why the run stopped,
+/// the registers, the stack, the timers and the whole memory, the latter
+/// as a string of hexadecimal digits
+///
+/// # Arguments
+///
+/// * `cpu` - The Cpu the run ended with
+/// * `outcome` - What run() returned
+///
+pub fn dump_json(cpu: &Cpu, outcome: &Outcome) -> String {
+    let list = |values: Vec<String>| values.join(", ");
+    let registers = cpu.registers().iter().map(|v| v.to_string()).collect();
+    let stack = cpu.stack().iter().map(|addr| addr.to_string()).collect();
+    let mut memory = String::with_capacity(cpu.memory().len() * 2);
+    for byte in cpu.memory() {
+        let _ = write!(memory, "{:02x}", byte);
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{{");
+    let _ = writeln!(out, "  \"stop\": \"{}\",", outcome.stop);
+    let _ = writeln!(out, "  \"frames\": {},", outcome.frames);
+    let _ = writeln!(out, "  \"cycles\": {},", cpu.cycles());
+    let _ = writeln!(out, "  \"platform\": \"{}\",", cpu.platform());
+    let _ = writeln!(out, "  \"pc\": {},", cpu.pc());
+    let _ = writeln!(out, "  \"i\": {},", cpu.index_register());
+    let _ = writeln!(out, "  \"v\": [{}],", list(registers));
+    let _ = writeln!(out, "  \"stack\": [{}],", list(stack));
+    let _ = writeln!(out, "  \"delay_timer\": {},", cpu.delay_timer());
+    let _ = writeln!(out, "  \"sound_timer\": {},", cpu.sound_timer());
+    let _ = writeln!(out, "  \"width\": {},", cpu.width());
+    let _ = writeln!(out, "  \"height\": {},", cpu.height());
+    let _ = writeln!(out, "  \"memory\": \"{}\"", memory);
+    let _ = writeln!(out, "}}");
+    out
+}
+
+/// Parses a number in decimal, or in hexadecimal with a 0x prefix
+fn parse_number(s: &str) -> Result<u32, String> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|err| format!("Invalid number '{}': {}", s, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions_are_parsed() {
+        assert_eq!("self-jump".parse(), Ok(Condition::SelfJump));
+        assert_eq!("pc=0x2a0".parse(), Ok(Condition::Pc(0x2A0)));
+        assert_eq!("PC=512".parse(), Ok(Condition::Pc(0x200)));
+        assert_eq!(
+            "vA=0x10".parse(),
+            Ok(Condition::Register { index: 0xA, value: 0x10 })
+        );
+        assert_eq!(
+            "v0=255".parse(),
+            Ok(Condition::Register { index: 0, value: 255 })
+        );
+        for condition in [
+            "v10=1",
+            "vg=1",
+            "v1=256",
+            "pc=0x10000",
+            "pc=-1",
+            "pc",
+            "v1",
+            "sp=0",
+            "",
+        ].iter() {
+            assert!(
+                condition.parse::<Condition>().is_err(),
+                "'{}' was accepted",
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn conditions_print_the_way_they_are_parsed() {
+        for condition in [
+            Condition::SelfJump,
+            Condition::Pc(0x2A0),
+            Condition::Register { index: 0xF, value: 1 },
+        ].iter() {
+            assert_eq!(condition.to_string().parse(), Ok(*condition));
+        }
+    }
+
+    #[test]
+    fn key_scripts_take_both_syntaxes_and_comments() {
+        let keys = parse_key_script("\
+            # Hold 5 for two frames\n\
+            30 5 down\n\
+            32:5:up   # then let go\n\
+            \n\
+            40:A:down\n\
+        ").unwrap();
+        assert_eq!(keys, vec![
+            ScriptedKey { frame: 30, key: 5, state: KeyState::Pressed },
+            ScriptedKey { frame: 32, key: 5, state: KeyState::Released },
+            ScriptedKey { frame: 40, key: 0xA, state: KeyState::Pressed },
+        ]);
+        assert_eq!(
+            parse_key_script("30 5 down\n31 5 held\n"),
+            Err("Line 2: Invalid key '31 5 held', expected \
+                 <frame>:<key>:down|up".to_string())
+        );
+        for key in ["30:10:down", "-1:5:up", "30:5", "30:5:up:now"].iter() {
+            assert!(key.parse::<ScriptedKey>().is_err(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn json_holds_the_state_of_the_cpu() {
+        let mut cpu = Cpu::new();
+        // LD V3, 0x2A, then CALL 0x206, which jumps to itself
+        cpu.load_rom_bytes(&[0x63, 0x2A, 0x22, 0x06, 0x00, 0x00, 0x12, 0x06])
+            .unwrap();
+        let outcome = run(&mut cpu, 10, &[Condition::SelfJump], &[]).unwrap();
+        assert_eq!(outcome, Outcome {
+            frames: 1,
+            stop: Stop::Condition(Condition::SelfJump),
+        });
+
+        let json = dump_json(&cpu, &outcome);
+        let keys: Vec<&str> = json.lines()
+            .filter_map(|line| line.trim().split('"').nth(1))
+            .collect();
+        assert_eq!(keys, [
+            "stop", "frames", "cycles", "platform", "pc", "i", "v", "stack",
+            "delay_timer", "sound_timer", "width", "height", "memory",
+        ]);
+        assert!(json.starts_with("{\n") && json.ends_with("}\n"));
+        assert!(json.contains("  \"stop\": \"self-jump\",\n"));
+        assert!(json.contains("  \"cycles\": 2,\n"));
+        assert!(json.contains("  \"pc\": 518,\n"));
+        assert!(json.contains("  \"v\": [0, 0, 0, 42, 0,"));
+        assert!(json.contains("  \"stack\": [516],\n"));
+        let memory = json.lines()
+            .find(|line| line.contains("\"memory\""))
+            .unwrap();
+        assert_eq!(memory.len(), "  \"memory\": \"\"".len() + 4096 * 2);
+        assert!(memory.contains("632a22060000120"));
+    }
+}
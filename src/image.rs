@@ -0,0 +1,125 @@
+use cpu::Cpu;
+use core::Pixel;
+use palette::Palette;
+
+/// Encodes the display as a plain PBM image, one character per pixel
+/// PBM images are black and white only, so every pixel with any plane set
+/// is shown as white
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::cpu::Cpu;
+/// use chip8_rust::image;
+///
+/// let cpu = Cpu::new();
+/// let pbm = image::to_pbm(&cpu);
+/// assert!(pbm.starts_with(b"P1\n64 32\n"));
+/// ```
+pub fn to_pbm(cpu: &Cpu) -> Vec<u8> {
+    let mut out = format!("P1\n{} {}\n", cpu.width(), cpu.height());
+    for row in cpu.display.chunks(cpu.width() as usize) {
+        let bits: Vec<&str> = row.iter()
+            // In PBM images 1 is black
+            .map(|&pixel| if pixel == Pixel::Black { "1" } else { "0" })
+            .collect();
+        out.push_str(&bits.join(" "));
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+/// Encodes the display as a PNG image with one image pixel per pixel, in
+/// the four colors of the planes of the classic palette
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::cpu::Cpu;
+/// use chip8_rust::image;
+///
+/// let cpu = Cpu::new();
+/// let png = image::to_png(&cpu);
+/// assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+/// ```
+pub fn to_png(cpu: &Cpu) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&cpu.width().to_be_bytes());
+    header.extend_from_slice(&cpu.height().to_be_bytes());
+    // 8 bits per pixel, indexed colors, default compression, filtering
+    // and no interlacing
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    let palette: Vec<u8> = Palette::default().colors.iter()
+        .flat_map(|color| color.iter())
+        .cloned()
+        .collect();
+
+    let mut scanlines = vec![];
+    for row in cpu.display.chunks(cpu.width() as usize) {
+        // Each scanline starts with the filter type, none
+        scanlines.push(0);
+        scanlines.extend(row.iter().map(|pixel| pixel.planes()));
+    }
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"PLTE", &palette);
+    write_chunk(&mut out, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Appends a PNG chunk: its length, type, data and checksum
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps data in a zlib stream without compressing it, which is all PNG
+/// needs and keeps the images readable by any decoder
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KiB window and no preset dictionary
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// The checksum of every PNG chunk, which gzip and zip archives use too
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            }
+            else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// The checksum that ends a zlib stream
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    b << 16 | a
+}
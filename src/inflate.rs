@@ -0,0 +1,283 @@
+/// The longest code in a deflate stream, in bits
+const MAX_BITS: usize = 15;
+
+/// The base lengths of the length symbols 257 through 285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+
+/// The number of extra bits read after each length symbol
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The base distances of the distance symbols 0 through 29
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+
+/// The number of extra bits read after each distance symbol
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// The order in which the lengths of the code length code are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads a deflate stream a bit at a time, least significant bit first
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Bits { data, pos: 0, bit: 0 }
+    }
+
+    /// Reads a number stored in the next bits, least significant bit first
+    fn read(&mut self, count: u8) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos)
+                .ok_or_else(|| "Compressed data ends early".to_string())?;
+            value |= ((byte >> self.bit) as u32 & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Skips to the start of the next byte
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+
+    /// Reads whole bytes, which must start on a byte boundary
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.pos..self.pos + count)
+            .ok_or_else(|| "Compressed data ends early".to_string())?;
+        self.pos += count;
+        Ok(bytes)
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length
+/// and the symbols ordered by code
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the code from the length of the code of every symbol, where
+    /// a length of 0 leaves the symbol out
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length] as usize;
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1]];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize]] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    /// Reads the next symbol
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        // The codes of each length follow on from the codes one bit
+        // shorter, so the symbol is found by counting through them
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_BITS {
+            code |= bits.read(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid Huffman code in compressed data".to_string())
+    }
+}
+
+/// Decompresses a raw deflate stream, the compression used by gzip and
+/// zip archives
+///
+/// # Arguments
+///
+/// * `data` - The compressed data
+/// * `limit` - The most bytes to decompress before giving up, which keeps
+///   a small archive from expanding to fill memory
+///
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut bits = Bits::new(data);
+    let mut out = vec![];
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let header = bits.bytes(4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let check = u16::from_le_bytes([header[2], header[3]]);
+                if len != !check {
+                    return Err("Corrupt stored block".to_string());
+                }
+                out.extend_from_slice(bits.bytes(len as usize)?);
+            },
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                let codes = (&lengths, &distances);
+                inflate_block(&mut bits, &mut out, codes, limit)?;
+            },
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                let codes = (&lengths, &distances);
+                inflate_block(&mut bits, &mut out, codes, limit)?;
+            },
+            _ => return Err("Invalid block type".to_string()),
+        }
+        if out.len() > limit {
+            return Err(format!("Decompresses to more than {} bytes", limit));
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Returns the codes that blocks compressed with fixed codes use
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+/// Reads the codes stored at the start of a block compressed with
+/// dynamic codes
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.read(5)? as usize + 257;
+    let distances = bits.read(5)? as usize + 1;
+    let code_lengths = bits.read(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err("Too many codes in dynamic block".to_string());
+    }
+
+    let mut lengths = [0; 19];
+    for &symbol in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[symbol] = bits.read(3)? as u8;
+    }
+    let code = Huffman::new(&lengths);
+
+    // The lengths of both codes are stored as a single run, in which a
+    // repeat may carry over from one code into the other
+    let mut lengths = vec![];
+    while lengths.len() < literals + distances {
+        let symbol = code.decode(bits)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last()
+                    .ok_or_else(|| "Repeat with no length".to_string())?;
+                (previous, 3 + bits.read(2)?)
+            },
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        lengths.extend((0..repeat).map(|_| length));
+    }
+    if lengths.len() > literals + distances {
+        return Err("Too many code lengths in dynamic block".to_string());
+    }
+    if lengths[256] == 0 {
+        return Err("Dynamic block cannot end".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+/// Decompresses the symbols of a block until its end
+///
+/// # Arguments
+///
+/// * `bits` - The stream, just past the header of the block
+/// * `out` - The data decompressed so far, which the block adds to
+/// * `codes` - The codes of the lengths and literals, and of the distances
+/// * `limit` - The most bytes `out` may hold
+///
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    codes: (&Huffman, &Huffman),
+    limit: usize
+) -> Result<(), String> {
+    let (lengths, distances) = codes;
+    loop {
+        if out.len() > limit {
+            return Err(format!("Decompresses to more than {} bytes", limit));
+        }
+        let symbol = lengths.decode(bits)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err("Invalid length in compressed data".to_string());
+        }
+        let length = LENGTH_BASE[symbol] as usize +
+            bits.read(LENGTH_EXTRA[symbol])? as usize;
+        let symbol = distances.decode(bits)? as usize;
+        if symbol >= DISTANCE_BASE.len() {
+            return Err("Invalid distance in compressed data".to_string());
+        }
+        let distance = DISTANCE_BASE[symbol] as usize +
+            bits.read(DISTANCE_EXTRA[symbol])? as usize;
+        if distance > out.len() {
+            return Err("Distance too far back in compressed data".to_string());
+        }
+        // The copy may overlap what it produces, so it goes byte by byte
+        let start = out.len() - distance;
+        for i in 0..length {
+            let byte = out[start + i];
+            out.push(byte);
+        }
+    }
+}
@@ -0,0 +1,584 @@
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use frontend::Command;
+use settings;
+
+/// The names of the presets, in the order they are listed in messages
+pub const PRESETS: [&str; 2] = ["qwerty", "numpad"];
+
+/// The host keys of the qwerty preset, in the order of the Chip-8 keys
+/// 0 to F, which puts the keypad on the four rows starting at 1
+const QWERTY_KEYS: [&str; 16] = [
+    "X", "1", "2", "3", "Q", "W", "E", "A",
+    "S", "D", "Z", "C", "4", "R", "F", "V",
+];
+
+/// The host keys of the numpad preset, in the order of the Chip-8 keys
+/// 0 to F, which gives every digit its own key and the letters the keys
+/// around them
+const NUMPAD_KEYS: [&str; 16] = [
+    "Keypad 0", "Keypad 1", "Keypad 2", "Keypad 3",
+    "Keypad 4", "Keypad 5", "Keypad 6", "Keypad 7",
+    "Keypad 8", "Keypad 9", "Keypad /", "Keypad *",
+    "Keypad -", "Keypad +", "Keypad Enter", "Keypad .",
+];
+
+/// The number of game controllers that can be played with at once
+pub const PADS: u8 = 2;
+
+/// The inputs of the first game controller in every preset, along with the
+/// Chip-8 keys they press, which put the directions on 5, 7, 8 and 9 like
+/// W, A, S and D
+const PAD_KEYS: [(&str, u8); 10] = [
+    ("dpup", 0x5),
+    ("lefty-", 0x5),
+    ("dpleft", 0x7),
+    ("leftx-", 0x7),
+    ("dpdown", 0x8),
+    ("lefty+", 0x8),
+    ("dpright", 0x9),
+    ("leftx+", 0x9),
+    ("a", 0x6),
+    ("b", 0x4),
+];
+
+/// The names of the hotkeys that take no slot, along with what they do
+const HOTKEYS: [(&str, Action); 10] = [
+    ("quit", Action::Hotkey(Command::Quit)),
+    ("pause", Action::Hotkey(Command::Pause)),
+    ("frame_advance", Action::Hotkey(Command::FrameAdvance)),
+    ("turbo", Action::Hotkey(Command::Turbo)),
+    ("slow_down", Action::Hotkey(Command::SlowDown)),
+    ("speed_up", Action::Hotkey(Command::SpeedUp)),
+    ("rewind", Action::Hotkey(Command::Rewind)),
+    ("reset", Action::Hotkey(Command::Reset)),
+    ("fullscreen", Action::Fullscreen),
+    ("palette", Action::NextPalette),
+];
+
+/// The hotkeys every preset starts with
+const DEFAULT_HOTKEYS: [(&str, Action); 10] = [
+    ("Escape", Action::Hotkey(Command::Quit)),
+    ("P", Action::Hotkey(Command::Pause)),
+    (".", Action::Hotkey(Command::FrameAdvance)),
+    ("Tab", Action::Hotkey(Command::Turbo)),
+    ("-", Action::Hotkey(Command::SlowDown)),
+    ("=", Action::Hotkey(Command::SpeedUp)),
+    ("Backspace", Action::Hotkey(Command::Rewind)),
+    ("Ctrl+R", Action::Hotkey(Command::Reset)),
+    ("F11", Action::Fullscreen),
+    ("Ctrl+P", Action::NextPalette),
+];
+
+/// How the names of host keys are matched against the keyboard
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// By the symbol the key types in the layout of the host, so that Q
+    /// is the key labelled Q
+    Keycode,
+    /// By the position of the key on a US keyboard, so that Q is the key
+    /// left of W whatever it is labelled
+    Scancode,
+}
+
+/// What pressing a host key does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Holds down a key of the Chip-8 keypad, from 0x0 to 0xF
+    Keypad(u8),
+    /// Sends a command to the emulator
+    Hotkey(Command),
+    /// Switches the window between fullscreen and windowed, which only
+    /// concerns the frontend
+    Fullscreen,
+    /// Draws the display in the next of the palettes
+    NextPalette,
+}
+
+/// A host key, along with the modifiers that have to be held with it
+/// Written as the name of the key, e.g. `Q` or `Keypad 7`, with `Shift+`
+/// or `Ctrl+` in front of it for the modifiers
+/// The inputs of game controllers are chords too, without modifiers
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Chord {
+    /// The name SDL gives the key, or the input of a game controller
+    pub key: String,
+    /// The game controller the input belongs to, from 1 to PADS, or None
+    /// for the keyboard
+    pub pad: Option<u8>,
+    /// Whether either shift key has to be held
+    pub shift: bool,
+    /// Whether either control key has to be held
+    pub ctrl: bool,
+}
+
+impl Chord {
+    /// Creates a chord of a key without modifiers
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name SDL gives the key
+    ///
+    pub fn new(key: &str) -> Self {
+        Chord {
+            key: key.to_string(),
+            pad: None,
+            shift: false,
+            ctrl: false,
+        }
+    }
+
+    /// Creates a chord of an input of a game controller
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - The number of the game controller, from 1 to PADS
+    /// * `input` - The name SDL gives a button, e.g. `a` or `dpup`, or
+    ///   that of an axis followed by the direction it is pushed in, e.g.
+    ///   `leftx-`, or `lefttrigger`
+    ///
+    pub fn pad(pad: u8, input: &str) -> Self {
+        Chord {
+            pad: Some(pad),
+            ..Chord::new(input)
+        }
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chord = Chord::new("");
+        let mut rest = s.trim();
+        // The names of some keys hold a + of their own, e.g. Keypad +, so
+        // only the modifiers are split off
+        loop {
+            let lower = rest.to_lowercase();
+            if lower.starts_with("shift+") {
+                chord.shift = true;
+                rest = &rest[6..];
+            }
+            else if lower.starts_with("ctrl+") {
+                chord.ctrl = true;
+                rest = &rest[5..];
+            }
+            else {
+                break;
+            }
+        }
+        if rest.is_empty() {
+            return Err(format!("Missing key in '{}'", s.trim()));
+        }
+        chord.key = rest.to_string();
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(pad) = self.pad {
+            write!(f, "pad{}.", pad)?;
+        }
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Which host keys drive the Chip-8 keypad and the emulator hotkeys
+/// Any number of host keys may stand for the same Chip-8 key or hotkey,
+/// but each host key does a single thing
+///
+/// A keymap file holds one `name = keys` pair per line, where keys is a
+/// comma separated list of host keys, possibly empty, and lines starting
+/// with # are comments, e.g.
+///
+/// ```text
+/// # Start from the keypad of the numeric keypad
+/// preset = numpad
+/// # Move with the arrows as well
+/// 2 = Keypad 2, Up
+/// 8 = Keypad 8, Down
+/// reset = F12
+/// # A second player on a game controller
+/// pad2.C = dpup, lefty-
+/// pad2.D = dpdown, lefty+
+/// ```
+///
+/// The names are `preset`, which replaces everything with one of the
+/// presets, `mode`, which is either `scancode` or `keycode`, the Chip-8
+/// keys `0` to `F`, and the hotkeys `quit`, `pause`, `frame_advance`,
+/// `turbo`, `slow_down`, `speed_up`, `rewind`, `reset`, `fullscreen`,
+/// `palette`, `save1` to `save9` and `load1` to `load9`
+/// With `pad1.` or `pad2.` in front, the names bind the inputs of the
+/// first or second game controller instead of keys
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::keymap::{Action, Chord, Keymap};
+///
+/// let keymap: Keymap = "5 = W, Up".parse().unwrap();
+/// assert_eq!(keymap.action(&Chord::new("Up")), Some(Action::Keypad(5)));
+/// assert_eq!(keymap.action(&Chord::new("Q")), Some(Action::Keypad(4)));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    mode: Mode,
+    bindings: Vec<(Chord, Action)>,
+}
+
+impl Keymap {
+    /// Returns one of the presets by name
+    /// `qwerty` lays the keypad out on the keys 1 to 4, Q to R, A to F and
+    /// Z to V by their position, so that it keeps its shape on any layout,
+    /// and `numpad` puts it on the numeric keypad
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the preset
+    ///
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "qwerty" => Some(Keymap::with_keypad(&QWERTY_KEYS)),
+            "numpad" => Some(Keymap::with_keypad(&NUMPAD_KEYS)),
+            _ => None,
+        }
+    }
+
+    /// Builds a preset out of the keys of its keypad and the default
+    /// hotkeys
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The names of the host keys of the Chip-8 keys 0 to F
+    ///
+    fn with_keypad(keys: &[&str; 16]) -> Self {
+        let mut bindings = vec![];
+        for (key, name) in keys.iter().enumerate() {
+            bindings.push((Chord::new(name), Action::Keypad(key as u8)));
+        }
+        for &(name, action) in DEFAULT_HOTKEYS.iter() {
+            let chord = name.parse().unwrap_or_else(|_| Chord::new(name));
+            bindings.push((chord, action));
+        }
+        // F1 to F9 load the state in the slot of that number, and save it
+        // while shift is held
+        for slot in 1..10 {
+            let mut chord = Chord::new(&format!("F{}", slot));
+            bindings.push((chord.clone(), Action::Hotkey(
+                Command::LoadState(slot)
+            )));
+            chord.shift = true;
+            bindings.push((chord, Action::Hotkey(Command::SaveState(slot))));
+        }
+        for &(input, key) in PAD_KEYS.iter() {
+            bindings.push((Chord::pad(1, input), Action::Keypad(key)));
+        }
+        bindings.push((Chord::pad(1, "start"), Action::Hotkey(Command::Pause)));
+        Keymap {
+            mode: Mode::Scancode,
+            bindings,
+        }
+    }
+
+    /// Reads a keymap
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of a preset, or else the path of a keymap file
+    ///
+    pub fn load(name: &str) -> Result<Self, String> {
+        if let Some(keymap) = Keymap::preset(name) {
+            return Ok(keymap);
+        }
+        let mut text = String::new();
+        File::open(name)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|err| format!("{}: {}", name, err))?;
+        text.parse().map_err(|err| format!("{}: {}", name, err))
+    }
+
+    /// Returns the path of the keymap file of the user, which is used
+    /// when neither the command line nor the settings of the rom pick one
+    /// It lives in the configuration directory of the user, e.g.
+    /// ~/.config/chip8-rust/keymap
+    pub fn user_path() -> Option<PathBuf> {
+        settings::config_dir().map(|dir| dir.join("keymap"))
+    }
+
+    /// Returns how the names of the host keys are matched
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns every host key along with what it does
+    pub fn bindings(&self) -> &[(Chord, Action)] {
+        &self.bindings
+    }
+
+    /// Returns what a host key does, if anything
+    /// Names are compared without regard to case, as SDL does
+    ///
+    /// # Arguments
+    ///
+    /// * `chord` - The host key along with the modifiers held with it
+    ///
+    pub fn action(&self, chord: &Chord) -> Option<Action> {
+        self.bindings.iter()
+            .find(|&(bound, _)| same_chord(bound, chord))
+            .map(|&(_, action)| action)
+    }
+
+    /// Makes a list of host keys the only ones of a device that do
+    /// something
+    /// Any other keys of the device doing it are unbound, as is anything
+    /// the given keys did before
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - What the keys are to do
+    /// * `pad` - The game controller the keys belong to, or None for the
+    ///   keyboard
+    /// * `chords` - The host keys, which may be none to unbind the action
+    ///
+    pub fn bind(&mut self, action: Action, pad: Option<u8>, chords: &[Chord]) {
+        self.bindings.retain(|&(ref bound, bound_action)| {
+            (bound_action != action || bound.pad != pad) &&
+                !chords.iter().any(|chord| same_chord(bound, chord))
+        });
+        for chord in chords {
+            self.bindings.push((chord.clone(), action));
+        }
+    }
+}
+
+impl Default for Keymap {
+    /// The qwerty preset
+    fn default() -> Self {
+        Keymap::with_keypad(&QWERTY_KEYS)
+    }
+}
+
+impl FromStr for Keymap {
+    type Err = String;
+
+    /// Parses a keymap file, whose bindings change those of the qwerty
+    /// preset unless it starts from another one
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keymap = Keymap::default();
+        for (i, line) in s.lines().enumerate() {
+            let number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let value = parts.next()
+                .ok_or_else(|| {
+                    format!("Line {}: expected 'name = keys'", number)
+                })?
+                .trim();
+            match name {
+                "preset" => {
+                    keymap = Keymap::preset(value).ok_or_else(|| format!(
+                        "Line {}: unknown preset '{}', expected one of: {}",
+                        number,
+                        value,
+                        PRESETS.join(", ")
+                    ))?;
+                },
+                "mode" => {
+                    keymap.mode = match value {
+                        "keycode" => Mode::Keycode,
+                        "scancode" => Mode::Scancode,
+                        _ => {
+                            return Err(format!(
+                                "Line {}: unknown mode '{}', expected \
+                                 keycode or scancode",
+                                number,
+                                value
+                            ))
+                        },
+                    };
+                },
+                _ => {
+                    let (pad, action) = parse_name(name).ok_or_else(|| {
+                        format!("Line {}: unknown key '{}'", number, name)
+                    })?;
+                    let mut chords = vec![];
+                    for key in value.split(',').filter(|key| {
+                        !key.trim().is_empty()
+                    }) {
+                        chords.push(match pad {
+                            Some(pad) => Chord::pad(pad, key.trim()),
+                            None => key.parse().map_err(|err| {
+                                format!("Line {}: {}", number, err)
+                            })?,
+                        });
+                    }
+                    keymap.bind(action, pad, &chords);
+                },
+            }
+        }
+        Ok(keymap)
+    }
+}
+
+/// Checks whether two chords are the same, ignoring the case of the keys
+fn same_chord(a: &Chord, b: &Chord) -> bool {
+    a.pad == b.pad && a.shift == b.shift && a.ctrl == b.ctrl &&
+        a.key.to_lowercase() == b.key.to_lowercase()
+}
+
+/// Returns the action a name on the left of a keymap file stands for,
+/// along with the game controller it is bound on, if any
+fn parse_name(name: &str) -> Option<(Option<u8>, Action)> {
+    if !name.starts_with("pad") {
+        return parse_action(name).map(|action| (None, action));
+    }
+    let mut parts = name[3..].splitn(2, '.');
+    let pad = parts.next()?.parse().ok()
+        .filter(|pad| (1..=PADS).contains(pad))?;
+    Some((Some(pad), parse_action(parts.next()?)?))
+}
+
+/// Returns the action a name on the left of a keymap file stands for
+fn parse_action(name: &str) -> Option<Action> {
+    if name.len() == 1 {
+        return u8::from_str_radix(name, 16).ok().map(Action::Keypad);
+    }
+    if let Some(&(_, action)) = HOTKEYS.iter().find(|&&(n, _)| n == name) {
+        return Some(action);
+    }
+    let slot = |prefix: &str| {
+        if !name.starts_with(prefix) {
+            return None;
+        }
+        match name[prefix.len()..].parse() {
+            Ok(slot) if (1..=9).contains(&slot) => Some(slot),
+            _ => None,
+        }
+    };
+    if let Some(slot) = slot("save") {
+        return Some(Action::Hotkey(Command::SaveState(slot)));
+    }
+    slot("load").map(|slot| Action::Hotkey(Command::LoadState(slot)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qwerty_is_the_default() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.mode(), Mode::Scancode);
+        assert_eq!(keymap.action(&Chord::new("1")), Some(Action::Keypad(1)));
+        assert_eq!(keymap.action(&Chord::new("x")), Some(Action::Keypad(0)));
+        assert_eq!(
+            keymap.action(&Chord::new("V")),
+            Some(Action::Keypad(0xF))
+        );
+        assert_eq!(
+            keymap.action(&"Shift+F3".parse().unwrap()),
+            Some(Action::Hotkey(Command::SaveState(3)))
+        );
+        assert_eq!(
+            keymap.action(&"Ctrl+R".parse().unwrap()),
+            Some(Action::Hotkey(Command::Reset))
+        );
+        assert_eq!(
+            keymap.action(&Chord::new("F11")),
+            Some(Action::Fullscreen)
+        );
+    }
+
+    #[test]
+    fn keys_can_be_added_and_taken_away() {
+        let keymap: Keymap = "\
+            # Arrows as well as the keypad\n\
+            preset = numpad\n\
+            mode = keycode\n\
+            8 = Keypad 8, Up\n\
+            turbo =\n\
+            quit = Ctrl+Q, Escape\n\
+        ".parse().unwrap();
+        assert_eq!(keymap.mode(), Mode::Keycode);
+        let up = Some(Action::Keypad(8));
+        assert_eq!(keymap.action(&Chord::new("Up")), up);
+        assert_eq!(keymap.action(&Chord::new("keypad 8")), up);
+        assert_eq!(keymap.action(&Chord::new("Q")), None);
+        assert_eq!(keymap.action(&Chord::new("Tab")), None);
+        let quit = Some(Action::Hotkey(Command::Quit));
+        assert_eq!(keymap.action(&"ctrl+q".parse().unwrap()), quit);
+        assert_eq!(keymap.action(&Chord::new("Escape")), quit);
+    }
+
+    #[test]
+    fn a_key_does_a_single_thing() {
+        let keymap: Keymap = "pause = W\nsave1 = F1".parse().unwrap();
+        assert_eq!(
+            keymap.action(&Chord::new("W")),
+            Some(Action::Hotkey(Command::Pause))
+        );
+        assert_eq!(keymap.action(&Chord::new("P")), None);
+        assert_eq!(
+            keymap.action(&Chord::new("F1")),
+            Some(Action::Hotkey(Command::SaveState(1)))
+        );
+        assert_eq!(keymap.action(&"Shift+F1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn game_controllers_are_bound_on_their_own() {
+        let keymap: Keymap = "\
+            5 = W, Up\n\
+            pad2.C = dpup\n\
+            pad2.pause = start, back\n\
+        ".parse().unwrap();
+        let up = Some(Action::Keypad(5));
+        assert_eq!(keymap.action(&Chord::new("Up")), up);
+        assert_eq!(keymap.action(&Chord::pad(1, "DPUP")), up);
+        assert_eq!(keymap.action(&Chord::new("dpup")), None);
+        assert_eq!(
+            keymap.action(&Chord::pad(2, "dpup")),
+            Some(Action::Keypad(0xC))
+        );
+        let pause = Some(Action::Hotkey(Command::Pause));
+        assert_eq!(keymap.action(&Chord::pad(1, "start")), pause);
+        assert_eq!(keymap.action(&Chord::pad(2, "back")), pause);
+        assert_eq!(keymap.action(&Chord::new("P")), pause);
+        assert!("pad3.5 = a".parse::<Keymap>().is_err());
+        assert!("pad1.g = a".parse::<Keymap>().is_err());
+    }
+
+    #[test]
+    fn chords_keep_plus_in_key_names() {
+        let chord: Chord = "Shift+Keypad +".parse().unwrap();
+        assert!(chord.shift && !chord.ctrl);
+        assert_eq!(chord.key, "Keypad +");
+        assert_eq!(chord.to_string(), "Shift+Keypad +");
+        assert!("Ctrl+".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn mistakes_are_refused() {
+        assert!("g = Q".parse::<Keymap>().is_err());
+        assert!("save0 = F10".parse::<Keymap>().is_err());
+        assert!("preset = dvorak".parse::<Keymap>().is_err());
+        assert!("mode = position".parse::<Keymap>().is_err());
+        assert!("quit Escape".parse::<Keymap>().is_err());
+        assert!(Keymap::load("qwerty").is_ok());
+    }
+}
@@ -0,0 +1,36 @@
+#[cfg(feature = "tui")]
+extern crate libc;
+extern crate rand;
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+pub mod asm;
+pub mod cpu;
+pub mod core;
+pub mod debugger;
+pub mod disasm;
+pub mod frontend;
+pub mod headless;
+pub mod image;
+mod inflate;
+pub mod keymap;
+pub mod loader;
+pub mod movie;
+pub mod options;
+pub mod palette;
+pub mod phosphor;
+pub mod platform;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod savestate;
+pub mod scheduler;
+pub mod settings;
+
+pub use cpu::{Cpu, CpuError, Opcode};
+pub use debugger::Debugger;
+pub use frontend::{Command, Frontend, NullFrontend};
+pub use platform::Platform;
+pub use quirks::{Profile, Quirks};
+pub use savestate::StateError;
+pub use core::{KeyState, Pixel, DISPLAY_HEIGHT, DISPLAY_WIDTH};
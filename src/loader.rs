@@ -0,0 +1,356 @@
+use std::io;
+use std::io::prelude::*;
+use std::fs::File;
+
+use image::crc32;
+use inflate::inflate;
+
+/// The most bytes an archive may unpack to, the memory of the largest
+/// platform
+const MAX_UNPACKED: usize = 0x10000;
+
+/// The extensions of the files in a zip archive that are taken for roms
+const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+/// Reads a rom from a file, or from standard input if the path is `-`
+/// Roms compressed with gzip or packed in a zip archive are unpacked; see
+/// unpack()
+///
+/// # Arguments
+///
+/// * `path` - The path of the rom or archive, or `-`
+///
+pub fn read_rom(path: &str) -> io::Result<Vec<u8>> {
+    if path == "-" {
+        let stdin = io::stdin();
+        let mut handle = stdin.lock();
+        return read_rom_from(&mut handle);
+    }
+    read_rom_from(&mut File::open(path)?)
+}
+
+/// Reads a rom from a stream until it ends, unpacking it if it is an
+/// archive
+///
+/// # Arguments
+///
+/// * `reader` - A file, standard input or anything else that reads
+///
+pub fn read_rom_from<R: Read + ?Sized>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    unpack(data)
+}
+
+/// Returns the rom held in data read from a file
+/// Data compressed with gzip is decompressed, and a zip archive gives up
+/// the only rom inside it, as told by the .ch8, .c8, .sc8 or .xo8
+/// extension, or else its only file
+/// Anything else is taken to be the rom itself
+///
+/// # Arguments
+///
+/// * `data` - The whole contents of the file
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::loader;
+///
+/// // 00E0 stored uncompressed in a gzip stream
+/// let gzip = [
+///     0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+///     0x01, 0x02, 0x00, 0xFD, 0xFF, 0x00, 0xE0,
+///     0x87, 0xF0, 0xD3, 0xE1, 0x02, 0x00, 0x00, 0x00,
+/// ];
+/// assert_eq!(loader::unpack(gzip.to_vec()).unwrap(), vec![0x00, 0xE0]);
+/// ```
+pub fn unpack(data: Vec<u8>) -> io::Result<Vec<u8>> {
+    let unpacked = if data.starts_with(&[0x1F, 0x8B]) {
+        gunzip(&data)
+    }
+    else if data.starts_with(b"PK\x03\x04") {
+        unzip(&data)
+    }
+    else {
+        return Ok(data);
+    };
+    unpacked.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Parses an address to load a rom at, in decimal or in hexadecimal with
+/// a 0x prefix, e.g. `0x600` for ETI-660 roms
+pub fn parse_address(s: &str) -> Result<u16, String> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|err| format!("Invalid address '{}': {}", s, err))
+}
+
+/// Decompresses a gzip stream
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    let truncated = || "The gzip stream is truncated".to_string();
+    if data.len() < 18 || data[2] != 8 {
+        return Err("Not a gzip stream compressed with deflate".to_string());
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    // Optional extra field, file name, comment and header checksum
+    if flags & 0x04 != 0 {
+        let len = read_u16(data, pos).ok_or_else(truncated)? as usize;
+        pos += 2 + len;
+    }
+    for &flag in [0x08, 0x10].iter() {
+        if flags & flag != 0 {
+            let end = data.get(pos..)
+                .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                .ok_or_else(truncated)?;
+            pos += end + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+    let body = data.get(pos..data.len() - 8).ok_or_else(truncated)?;
+    let out = inflate(body, MAX_UNPACKED)?;
+
+    let trailer = data.len() - 8;
+    let crc = read_u32(data, trailer).ok_or_else(truncated)?;
+    if crc != crc32(&out) {
+        return Err("The gzip stream is corrupt".to_string());
+    }
+    Ok(out)
+}
+
+/// A file in a zip archive, as listed in its central directory
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    header_offset: usize,
+}
+
+/// Extracts the rom from a zip archive
+fn unzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    let entries = zip_entries(data)?;
+    let files: Vec<&ZipEntry> = entries.iter()
+        .filter(|entry| !entry.name.ends_with('/'))
+        .collect();
+    let roms: Vec<&ZipEntry> = files.iter()
+        .cloned()
+        .filter(|entry| {
+            let name = entry.name.to_lowercase();
+            ROM_EXTENSIONS.iter()
+                .any(|ext| name.ends_with(&format!(".{}", ext)))
+        })
+        .collect();
+    let candidates = if roms.is_empty() { files } else { roms };
+    let entry = match candidates.len() {
+        0 => return Err("The zip archive is empty".to_string()),
+        1 => candidates[0],
+        _ => {
+            let names: Vec<&str> = candidates.iter()
+                .map(|entry| entry.name.as_str())
+                .collect();
+            return Err(format!(
+                "The zip archive holds more than one rom: {}",
+                names.join(", ")
+            ));
+        },
+    };
+
+    let corrupt = || format!("The zip archive is corrupt at {}", entry.name);
+    let header = entry.header_offset;
+    if read_u32(data, header) != Some(0x0403_4B50) {
+        return Err(corrupt());
+    }
+    let name_len = read_u16(data, header + 26).ok_or_else(corrupt)? as usize;
+    let extra_len = read_u16(data, header + 28).ok_or_else(corrupt)? as usize;
+    let start = header + 30 + name_len + extra_len;
+    let body = data.get(start..start + entry.compressed_size)
+        .ok_or_else(corrupt)?;
+    let out = match entry.method {
+        0 if body.len() > MAX_UNPACKED => {
+            return Err(format!("{} is too large", entry.name));
+        },
+        0 => body.to_vec(),
+        8 => inflate(body, MAX_UNPACKED)
+            .map_err(|err| format!("{}: {}", entry.name, err))?,
+        method => {
+            return Err(format!(
+                "{} is compressed with unsupported method {}",
+                entry.name,
+                method
+            ));
+        },
+    };
+    if crc32(&out) != entry.crc {
+        return Err(corrupt());
+    }
+    Ok(out)
+}
+
+/// Lists the files in a zip archive by reading its central directory
+fn zip_entries(data: &[u8]) -> Result<Vec<ZipEntry>, String> {
+    let corrupt = || "The zip archive is corrupt".to_string();
+    // The end of central directory record sits at the very end, followed
+    // only by a comment of up to 64 KiB
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .take(0x10000 + 22)
+        .find(|&pos| read_u32(data, pos) == Some(0x0605_4B50))
+        .ok_or_else(corrupt)?;
+    let count = read_u16(data, end + 10).ok_or_else(corrupt)?;
+    let mut pos = read_u32(data, end + 16).ok_or_else(corrupt)? as usize;
+
+    let mut entries = vec![];
+    for _ in 0..count {
+        if read_u32(data, pos) != Some(0x0201_4B50) {
+            return Err(corrupt());
+        }
+        let field = |offset: usize| read_u16(data, pos + offset);
+        let name_len = field(28).ok_or_else(corrupt)? as usize;
+        let extra_len = field(30).ok_or_else(corrupt)? as usize;
+        let comment_len = field(32).ok_or_else(corrupt)? as usize;
+        let name = data.get(pos + 46..pos + 46 + name_len)
+            .ok_or_else(corrupt)?;
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: field(10).ok_or_else(corrupt)?,
+            crc: read_u32(data, pos + 16).ok_or_else(corrupt)?,
+            compressed_size: read_u32(data, pos + 20)
+                .ok_or_else(corrupt)? as usize,
+            header_offset: read_u32(data, pos + 42)
+                .ok_or_else(corrupt)? as usize,
+        });
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+/// Reads a little-endian 16-bit number, if the data is long enough
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Reads a little-endian 32-bit number, if the data is long enough
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4).map(|bytes| {
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100 bytes drawn from a few values, compressed with dynamic codes
+    const GZIP: [u8; 72] = [
+        0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x25, 0x8B,
+        0xC1, 0x0D, 0x00, 0x30, 0x08, 0x02, 0x99, 0x81, 0xFD, 0x5F, 0x4E, 0xC6,
+        0x26, 0xF4, 0x48, 0x4D, 0x54, 0x3C, 0x44, 0x27, 0x2B, 0x94, 0x14, 0xF6,
+        0x29, 0xD6, 0x90, 0xD4, 0x81, 0xCD, 0x74, 0x37, 0x1C, 0x81, 0x72, 0x07,
+        0x7C, 0xE9, 0xBE, 0xB1, 0x21, 0xDF, 0x6B, 0xBC, 0x88, 0xEF, 0x2E, 0xCB,
+        0xD3, 0xC4, 0xEB, 0x07, 0xD3, 0x24, 0x6C, 0x47, 0x64, 0x00, 0x00, 0x00,
+    ];
+
+    /// Builds a zip archive holding files stored without compression
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut out = vec![];
+        let mut directory = vec![];
+        for &(name, data) in files {
+            let offset = out.len() as u32;
+            let crc = crc32(data).to_le_bytes();
+            let size = (data.len() as u32).to_le_bytes();
+            let name_len = (name.len() as u16).to_le_bytes();
+
+            out.extend_from_slice(b"PK\x03\x04");
+            out.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            out.extend_from_slice(&crc);
+            out.extend_from_slice(&size);
+            out.extend_from_slice(&size);
+            out.extend_from_slice(&name_len);
+            out.extend_from_slice(&[0, 0]);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(data);
+
+            directory.extend_from_slice(b"PK\x01\x02");
+            directory.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0]);
+            directory.extend_from_slice(&[0, 0, 0, 0]);
+            directory.extend_from_slice(&crc);
+            directory.extend_from_slice(&size);
+            directory.extend_from_slice(&size);
+            directory.extend_from_slice(&name_len);
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let start = out.len() as u32;
+        let count = (files.len() as u16).to_le_bytes();
+        out.extend_from_slice(&directory);
+        out.extend_from_slice(b"PK\x05\x06");
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend_from_slice(&count);
+        out.extend_from_slice(&count);
+        out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&start.to_le_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out
+    }
+
+    #[test]
+    fn plain_roms_pass_through() {
+        let rom = vec![0x00, 0xE0, 0x12, 0x00];
+        assert_eq!(unpack(rom.clone()).unwrap(), rom);
+        assert_eq!(read_rom_from(&mut &rom[..]).unwrap(), rom);
+    }
+
+    #[test]
+    fn gzip_with_dynamic_codes() {
+        // The checksum in the trailer has to match what comes out
+        let rom = unpack(GZIP.to_vec()).unwrap();
+        assert_eq!(rom.len(), 100);
+        assert_eq!(rom.iter().filter(|&&byte| byte == 0).count(), 38);
+    }
+
+    #[test]
+    fn corrupt_gzip_is_refused() {
+        let mut gzip = GZIP.to_vec();
+        gzip[64] ^= 1;
+        let err = unpack(gzip).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn zip_gives_up_its_rom() {
+        let archive = zip(&[
+            ("docs/", b""),
+            ("readme.txt", b"Press 5 to jump"),
+            ("docs/GAME.CH8", &[0x00, 0xE0]),
+        ]);
+        assert_eq!(unpack(archive).unwrap(), vec![0x00, 0xE0]);
+
+        // Without a rom extension, the only file is taken
+        let archive = zip(&[("game", &[0x12, 0x00])]);
+        assert_eq!(unpack(archive).unwrap(), vec![0x12, 0x00]);
+    }
+
+    #[test]
+    fn zip_with_several_roms_is_refused() {
+        let archive = zip(&[("a.ch8", &[0x00, 0xE0]), ("b.ch8", &[0x00])]);
+        let err = unpack(archive).unwrap_err();
+        assert!(err.to_string().contains("a.ch8, b.ch8"), "{}", err);
+
+        let archive = zip(&[("a.txt", b"a"), ("b.txt", b"b")]);
+        assert!(unpack(archive).is_err());
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(parse_address("0x600"), Ok(0x600));
+        assert_eq!(parse_address("512"), Ok(0x200));
+        assert!(parse_address("0x10000").is_err());
+    }
+}
@@ -1,28 +1,263 @@
-extern crate rand;
+extern crate chip8_rust;
 extern crate sdl2;
 
 use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
 
-mod cpu;
-mod core;
+use chip8_rust::cpu;
+use chip8_rust::core::{self, Core, Scaling};
+use chip8_rust::debugger::Debugger;
+use chip8_rust::frontend::Frontend;
+use chip8_rust::keymap::Keymap;
+use chip8_rust::movie::{Movie, Player, Recorder};
+use chip8_rust::options::MachineOptions;
+use chip8_rust::palette::Palette;
+use chip8_rust::rewind::{self, Rewind};
+use chip8_rust::settings::RomSettings;
+
+/// Prints how the emulator is meant to be invoked and exits
+fn usage() -> ! {
+    eprintln!(
+        "Usage: chip8_rust [--platform chip8|schip|xochip] \
+         [--quirks vip|chip48|schip|modern] [--speed <n>] [--vsync] \
+         [--scale <n>] [--scaling integer|fit] [--palette <name>] \
+         [--persistence off|or|<decay>] \
+         [--seed <n>] [--load-address <addr>] [--keymap <name>] [--debug] \
+         [--record <movie> | --replay <movie>] [--rewind <frames>] \
+         [--rewind-memory <MiB>] <rom>|-"
+    );
+    process::exit(2);
+}
 
 fn main() {
-    let mut cpu = cpu::Cpu::new();
+    let mut options = MachineOptions::default();
+    let mut vsync = false;
+    let mut scale = core::DEFAULT_SCALE;
+    let mut scaling = Scaling::default();
+    let mut debug = false;
+    let mut keymap = None;
+    let mut palette = None;
+    let mut persistence = None;
+    let mut record = None;
+    let mut replay = None;
+    let mut rewind_frames = rewind::DEFAULT_HISTORY;
+    let mut rewind_budget = rewind::DEFAULT_BUDGET;
+    let mut rom = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match options.parse_arg(&arg, &mut args) {
+            Ok(true) => continue,
+            Ok(false) => (),
+            Err(err) => {
+                eprintln!("{}", err);
+                usage()
+            },
+        }
+        match arg.as_str() {
+            "--vsync" => vsync = true,
+            "--scale" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                scale = match value.parse::<u32>() {
+                    Ok(scale) if scale > 0 => scale,
+                    _ => {
+                        eprintln!("Invalid scale '{}'", value);
+                        usage()
+                    },
+                };
+            },
+            "--scaling" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                scaling = name.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                });
+            },
+            "--debug" => debug = true,
+            "--rewind" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                rewind_frames = value.parse().unwrap_or_else(|err| {
+                    eprintln!("Invalid number of frames '{}': {}", value, err);
+                    usage()
+                });
+            },
+            "--rewind-memory" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                let mebibytes: usize = value.parse().unwrap_or_else(|err| {
+                    eprintln!("Invalid amount of memory '{}': {}", value, err);
+                    usage()
+                });
+                rewind_budget = mebibytes * 1024 * 1024;
+            },
+            "--record" if replay.is_none() => {
+                record = Some(args.next().unwrap_or_else(|| usage()));
+            },
+            "--replay" if record.is_none() => {
+                replay = Some(args.next().unwrap_or_else(|| usage()));
+            },
+            "--palette" => {
+                palette = Some(args.next().unwrap_or_else(|| usage()));
+            },
+            "--persistence" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                persistence = Some(value.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                }));
+            },
+            "--keymap" => {
+                keymap = Some(args.next().unwrap_or_else(|| usage()));
+            },
+            _ if rom.is_none() && !arg.starts_with("--") => rom = Some(arg),
+            _ => usage(),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+    let settings = RomSettings::load(&rom).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    });
+    let keymap = choose_keymap(keymap, &settings, &rom);
+    // The palette given on the command line wins over the one in the
+    // settings of the rom
+    let mut palettes = Palette::builtin();
+    palettes.extend(Palette::load_user().unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }));
+    let palette = palette.or_else(|| settings.palette.clone()).map(|name| {
+        Palette::find(&palettes, &name).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        })
+    });
+
+    // A replay starts the Cpu the way the movie was recorded, whatever
+    // the other options say
+    let movie = replay.map(|path| read_movie(&path));
+    let mut cpu = match movie {
+        Some(ref movie) => movie.start(),
+        None => options.cpu(&settings),
+    };
     cpu.load_fontset();
+    if let Err(err) = cpu.load_rom(&rom) {
+        eprintln!("Error: could not load {}: {}", rom, err);
+        process::exit(1);
+    }
+    if let Some(ref movie) = movie {
+        if movie.rom_hash != cpu.rom_hash() {
+            eprintln!("Error: the movie was recorded with a different rom");
+            process::exit(1);
+        }
+    }
+
+    if rewind_frames > 0 {
+        cpu.set_rewind(Some(Rewind::new(rewind_frames, rewind_budget)));
+    }
+
+    let sdl_context = sdl2::init().unwrap_or_else(|err| {
+        eprintln!("Error: SDL cannot be initialized: {}", err);
+        process::exit(1);
+    });
+    let mut core = Core::with_scale(&sdl_context, vsync, scale);
+    core.set_scaling(scaling);
+    core.set_palettes(palettes);
+    if let Some(palette) = palette {
+        core.set_palette(palette);
+    }
+    if let Some(persistence) = persistence.or(settings.persistence) {
+        core.set_persistence(persistence);
+    }
+    if let Err(err) = core.set_keymap(&keymap) {
+        eprintln!("Error: the keymap cannot be used: {}", err);
+        process::exit(1);
+    }
+    let result = if let Some(movie) = movie {
+        run(&mut cpu, &mut Player::new(core, movie), debug)
+    }
+    else if let Some(path) = record {
+        let mut recorder = Recorder::new(core, &cpu);
+        let result = run(&mut cpu, &mut recorder, debug);
+        let written = File::create(&path).and_then(|mut file| {
+            file.write_all(recorder.finish().to_string().as_bytes())
+        });
+        if let Err(err) = written {
+            eprintln!("Error: could not write {}: {}", path, err);
+        }
+        result
+    }
+    else {
+        run(&mut cpu, &mut core, debug)
+    };
+    // Errors raised by the rom get a status of their own, so that
+    // scripts can tell them from a bad invocation
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        process::exit(3);
+    }
+}
 
-    let args: Vec<String> = env::args().collect();
-    assert!(args.len() == 2);
-    let rom = &args[1];
-    match cpu.load_rom(rom) {
-        Ok(()) => {},
-        Err(err) => { panic!("Error: {}", err) },
+/// Runs the rom until it exits or the user quits
+fn run<F: Frontend>(
+    cpu: &mut cpu::Cpu,
+    frontend: &mut F,
+    debug: bool
+) -> Result<(), cpu::CpuError> {
+    if debug {
+        cpu.run_with_debugger(frontend, &mut Debugger::new())
     }
+    else {
+        cpu.run(frontend)
+    }
+}
+
+/// Picks the keymap to play with, exiting if it cannot be read
+/// The keymap given on the command line wins over the one in the settings
+/// of the rom, which wins over the keymap file of the user
+fn choose_keymap(
+    name: Option<String>,
+    settings: &RomSettings,
+    rom: &str
+) -> Keymap {
+    let name = name.or_else(|| {
+        // A keymap file named by the settings of a rom sits next to it
+        settings.keymap.as_ref().map(|name| {
+            match Path::new(rom).parent() {
+                Some(dir) if Keymap::preset(name).is_none() => {
+                    dir.join(name).to_string_lossy().into_owned()
+                },
+                _ => name.clone(),
+            }
+        })
+    }).or_else(|| {
+        Keymap::user_path()
+            .filter(|path| path.exists())
+            .map(|path| path.to_string_lossy().into_owned())
+    });
+    match name {
+        Some(name) => Keymap::load(&name).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }),
+        None => Keymap::default(),
+    }
+}
 
-    let sdl_context = sdl2::init().unwrap();
-    match cpu.run(&sdl_context) {
-        Ok(()) => {},
-        Err(cpu::CpuError::IllegalInstruction(opcode)) => {
-            panic!("Illegal CPU instruction: {:x}", opcode)
-        },
+/// Reads a movie to replay, exiting if it cannot be used
+fn read_movie(path: &str) -> Movie {
+    let mut text = String::new();
+    let read = File::open(path).and_then(|mut file| {
+        file.read_to_string(&mut text)
+    });
+    if let Err(err) = read {
+        eprintln!("Error: could not read {}: {}", path, err);
+        process::exit(1);
     }
+    text.parse().unwrap_or_else(|err| {
+        eprintln!("Error: {}: {}", path, err);
+        process::exit(1);
+    })
 }
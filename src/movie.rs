@@ -0,0 +1,456 @@
+use std::fmt;
+use std::str::FromStr;
+
+use core::KeyState;
+use cpu::Cpu;
+use frontend::{Command, Frontend};
+use platform::Platform;
+use quirks::Quirks;
+use savestate;
+use scheduler::Pace;
+
+/// The first line of every movie file
+const HEADER: &str = "chip8-movie 1";
+
+/// A key of the keypad changing state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The number of instructions executed before the change, as returned
+    /// by Cpu::cycles()
+    pub cycle: u64,
+    /// The index of the key on the keypad, from 0x0 to 0xF
+    pub key: u8,
+    pub state: KeyState,
+}
+
+/// A recording of everything that was pressed while a rom ran, along with
+/// what is needed to start the rom the same way again
+/// Replaying a movie on a Cpu created by start() reproduces the original
+/// run exactly, since the only other source of change, the random number
+/// generator, is seeded from the movie as well
+///
+/// Movies are stored as text, e.g.
+///
+/// ```text
+/// chip8-movie 1
+/// platform chip8
+/// quirks 73
+/// speed 10
+/// load 200
+/// seed 1234
+/// rom 9ae27c06a2e5ff51
+/// 1520 5 down
+/// 1618 5 up
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub platform: Platform,
+    pub quirks: Quirks,
+    /// The number of instructions executed in every frame
+    pub speed: u32,
+    /// The address the rom was loaded at
+    pub load_address: u16,
+    /// The seed of the random number generator
+    pub seed: u64,
+    /// The hash of the rom the movie was recorded with
+    pub rom_hash: u64,
+    /// The key changes, ordered by the cycle they happened at
+    pub events: Vec<KeyEvent>,
+}
+
+impl Movie {
+    /// Creates an empty movie that starts from the state of a freshly
+    /// loaded Cpu
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - A Cpu that has loaded its rom, but not run it yet
+    ///
+    pub fn new(cpu: &Cpu) -> Self {
+        Movie {
+            platform: cpu.platform(),
+            quirks: cpu.quirks(),
+            speed: cpu.speed(),
+            load_address: cpu.load_address(),
+            seed: cpu.rng().state(),
+            rom_hash: cpu.rom_hash(),
+            events: vec![],
+        }
+    }
+
+    /// Creates a Cpu that is ready to replay the movie, once the rom has
+    /// been loaded into it
+    pub fn start(&self) -> Cpu {
+        let mut cpu = Cpu::with_platform(self.platform, self.quirks);
+        cpu.set_speed(self.speed);
+        cpu.set_load_address(self.load_address);
+        cpu.seed_rng(self.seed);
+        cpu
+    }
+}
+
+impl FromStr for Movie {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|&(_, line)| !line.is_empty());
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(format!("Not a movie, expected '{}'", HEADER));
+        }
+
+        let mut field = |name: &str| -> Result<String, String> {
+            let (number, line) = lines.next().ok_or_else(|| {
+                format!("The movie ends before its '{}' line", name)
+            })?;
+            let mut words = line.splitn(2, ' ');
+            if words.next() != Some(name) {
+                return Err(format!("Line {}: expected '{}'", number, name));
+            }
+            Ok(words.next().unwrap_or("").trim().to_string())
+        };
+        let platform = field("platform")?.parse()?;
+        let quirks = field("quirks")?;
+        let quirks = u8::from_str_radix(&quirks, 16)
+            .ok()
+            .and_then(|bits| savestate::quirks_from_bits(bits).ok())
+            .ok_or_else(|| format!("Invalid quirks: {}", quirks))?;
+        let speed = field("speed")?.parse()
+            .map_err(|err| format!("Invalid speed: {}", err))?;
+        let load_address = u16::from_str_radix(&field("load")?, 16)
+            .map_err(|err| format!("Invalid load address: {}", err))?;
+        let seed = field("seed")?.parse()
+            .map_err(|err| format!("Invalid seed: {}", err))?;
+        let rom_hash = u64::from_str_radix(&field("rom")?, 16)
+            .map_err(|err| format!("Invalid rom hash: {}", err))?;
+
+        let mut events: Vec<KeyEvent> = vec![];
+        for (number, line) in lines {
+            let invalid = || format!("Line {}: invalid key event", number);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() != 3 {
+                return Err(invalid());
+            }
+            let cycle = words[0].parse().map_err(|_| invalid())?;
+            let key = u8::from_str_radix(words[1], 16)
+                .ok()
+                .filter(|&key| key < 16)
+                .ok_or_else(invalid)?;
+            let state = match words[2] {
+                "down" => KeyState::Pressed,
+                "up" => KeyState::Released,
+                _ => return Err(invalid()),
+            };
+            if events.last().is_some_and(|last| last.cycle > cycle) {
+                return Err(format!(
+                    "Line {}: key events must be in order",
+                    number
+                ));
+            }
+            events.push(KeyEvent { cycle, key, state });
+        }
+
+        Ok(Movie {
+            platform,
+            quirks,
+            speed,
+            load_address,
+            seed,
+            rom_hash,
+            events,
+        })
+    }
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "platform {}", self.platform)?;
+        writeln!(f, "quirks {:02x}", savestate::quirks_to_bits(self.quirks))?;
+        writeln!(f, "speed {}", self.speed)?;
+        writeln!(f, "load {:x}", self.load_address)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "rom {:016x}", self.rom_hash)?;
+        for event in &self.events {
+            let state = match event.state {
+                KeyState::Pressed => "down",
+                KeyState::Released => "up",
+            };
+            writeln!(f, "{} {:x} {}", event.cycle, event.key, state)?;
+        }
+        Ok(())
+    }
+}
+
+/// A frontend that records every change to the keypad made through
+/// another frontend
+/// Loading save states and rewinding are refused while recording, since
+/// the movie could not reproduce them
+pub struct Recorder<F: Frontend> {
+    inner: F,
+    movie: Movie,
+    keys: Vec<KeyState>,
+}
+
+impl<F: Frontend> Recorder<F> {
+    /// Starts recording
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The frontend that provides the input to record, and
+    ///   that everything else is passed on to
+    /// * `cpu` - A Cpu that has loaded its rom, but not run it yet
+    ///
+    pub fn new(inner: F, cpu: &Cpu) -> Self {
+        Recorder {
+            inner,
+            movie: Movie::new(cpu),
+            keys: cpu.keys.clone(),
+        }
+    }
+
+    /// Stops recording and returns the movie
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+impl<F: Frontend> Frontend for Recorder<F> {
+    fn poll_input(&mut self, cpu: &mut Cpu) -> Option<Command> {
+        let command = self.inner.poll_input(cpu);
+        for (key, state) in cpu.keys.iter().enumerate() {
+            if *state != self.keys[key] {
+                self.movie.events.push(KeyEvent {
+                    cycle: cpu.cycles(),
+                    key: key as u8,
+                    state: *state,
+                });
+                self.keys[key] = *state;
+            }
+        }
+        match command {
+            Some(Command::LoadState(_)) => {
+                eprintln!("Save states cannot be loaded while recording");
+                None
+            },
+            Some(Command::Reset) => {
+                eprintln!("The rom cannot be reset while recording");
+                None
+            },
+            Some(Command::Rewind) => None,
+            _ => command,
+        }
+    }
+
+    fn draw(&mut self, cpu: &Cpu) {
+        self.inner.draw(cpu);
+    }
+
+    fn syncs_to_vblank(&self) -> bool {
+        self.inner.syncs_to_vblank()
+    }
+
+    fn set_pace(&mut self, pace: Pace) {
+        self.inner.set_pace(pace);
+    }
+
+    fn play_sound(&mut self) {
+        self.inner.play_sound();
+    }
+
+    fn stop_sound(&mut self) {
+        self.inner.stop_sound();
+    }
+
+    fn set_audio_pattern(&mut self, pattern: &[u8; 16], rate: f32) {
+        self.inner.set_audio_pattern(pattern, rate);
+    }
+}
+
+/// A frontend that feeds the keypad from a movie instead of from another
+/// frontend, which still does the drawing and the sound and may still quit
+pub struct Player<F: Frontend> {
+    inner: F,
+    events: Vec<KeyEvent>,
+    next: usize,
+    keys: Vec<KeyState>,
+}
+
+impl<F: Frontend> Player<F> {
+    /// Starts replaying
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The frontend to pass everything but the keypad on to
+    /// * `movie` - The movie to replay, on a Cpu created by Movie::start()
+    ///
+    pub fn new(inner: F, movie: Movie) -> Self {
+        Player {
+            inner,
+            events: movie.events,
+            next: 0,
+            keys: vec![KeyState::Released; 16],
+        }
+    }
+
+    /// Checks whether every key change of the movie has been replayed
+    pub fn finished(&self) -> bool {
+        self.next >= self.events.len()
+    }
+}
+
+impl<F: Frontend> Frontend for Player<F> {
+    fn poll_input(&mut self, cpu: &mut Cpu) -> Option<Command> {
+        let command = self.inner.poll_input(cpu);
+        while let Some(event) = self.events.get(self.next) {
+            if event.cycle > cpu.cycles() {
+                break;
+            }
+            self.keys[event.key as usize] = event.state;
+            self.next += 1;
+        }
+        // Whatever the inner frontend did to the keypad is overruled
+        cpu.keys.copy_from_slice(&self.keys);
+        match command {
+            Some(Command::SaveState(_)) | Some(Command::LoadState(_)) => {
+                eprintln!("Save states cannot be used during a replay");
+                None
+            },
+            Some(Command::Reset) => {
+                eprintln!("The rom cannot be reset during a replay");
+                None
+            },
+            Some(Command::Rewind) => None,
+            _ => command,
+        }
+    }
+
+    fn draw(&mut self, cpu: &Cpu) {
+        self.inner.draw(cpu);
+    }
+
+    fn syncs_to_vblank(&self) -> bool {
+        self.inner.syncs_to_vblank()
+    }
+
+    fn set_pace(&mut self, pace: Pace) {
+        self.inner.set_pace(pace);
+    }
+
+    fn play_sound(&mut self) {
+        self.inner.play_sound();
+    }
+
+    fn stop_sound(&mut self) {
+        self.inner.stop_sound();
+    }
+
+    fn set_audio_pattern(&mut self, pattern: &[u8; 16], rate: f32) {
+        self.inner.set_audio_pattern(pattern, rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frontend::NullFrontend;
+
+    const MOVIE: &str = "\
+        chip8-movie 1\n\
+        platform schip\n\
+        quirks 18\n\
+        speed 30\n\
+        load 200\n\
+        seed 1234\n\
+        rom 9ae27c06a2e5ff51\n\
+        5 3 down\n\
+        12 3 up\n\
+        12 f down\n\
+    ";
+
+    /// The movie with one of its lines replaced
+    fn with_line(number: usize, line: &str) -> String {
+        MOVIE.lines()
+            .enumerate()
+            .map(|(i, old)| if i + 1 == number { line } else { old })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn movies_are_parsed() {
+        let movie: Movie = MOVIE.parse().unwrap();
+        assert_eq!(movie.platform, Platform::SuperChip);
+        assert_eq!(movie.quirks, Quirks::superchip());
+        assert_eq!(movie.speed, 30);
+        assert_eq!(movie.load_address, 0x200);
+        assert_eq!(movie.seed, 1234);
+        assert_eq!(movie.rom_hash, 0x9ae2_7c06_a2e5_ff51);
+        assert_eq!(movie.events, vec![
+            KeyEvent { cycle: 5, key: 0x3, state: KeyState::Pressed },
+            KeyEvent { cycle: 12, key: 0x3, state: KeyState::Released },
+            KeyEvent { cycle: 12, key: 0xF, state: KeyState::Pressed },
+        ]);
+    }
+
+    #[test]
+    fn movies_survive_being_written_and_read() {
+        let movie: Movie = MOVIE.parse().unwrap();
+        assert_eq!(movie.to_string().parse(), Ok(movie.clone()));
+        let mut chip48 = movie;
+        chip48.quirks = Quirks::chip48();
+        assert_eq!(chip48.to_string().parse(), Ok(chip48));
+    }
+
+    #[test]
+    fn broken_movies_are_refused() {
+        for &(number, line) in [
+            // A missing header, and one of another version
+            (1, ""),
+            (1, "chip8-movie 2"),
+            // A missing field, and two fields swapped
+            (3, ""),
+            (4, "load 200"),
+            (5, "speed 30"),
+            // Bad values
+            (2, "platform chip9"),
+            (3, "quirks 86"),
+            (4, "speed fast"),
+            (7, "rom nothex"),
+            // Key events out of order, of keys past 0xF or in bad states
+            (8, "13 3 down"),
+            (9, "12 10 up"),
+            (10, "12 f sideways"),
+            (10, "12 f"),
+        ].iter() {
+            let movie = with_line(number, line);
+            assert!(
+                movie.parse::<Movie>().is_err(),
+                "Line {} '{}' was accepted",
+                number,
+                line
+            );
+        }
+        assert!(MOVIE.lines().take(4).collect::<Vec<_>>().join("\n")
+            .parse::<Movie>().is_err());
+    }
+
+    #[test]
+    fn players_press_keys_at_the_recorded_cycles() {
+        let movie: Movie = MOVIE.parse().unwrap();
+        let mut cpu = movie.start();
+        // ADD V0, 1 and JP 0x200, forever
+        cpu.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut player = Player::new(NullFrontend, movie);
+        for cycle in 0..20 {
+            assert_eq!(player.poll_input(&mut cpu), None);
+            let pressed = |key: usize| cpu.keys[key] == KeyState::Pressed;
+            assert_eq!(pressed(0x3), (5..12).contains(&cycle), "{}", cycle);
+            assert_eq!(pressed(0xF), cycle >= 12, "{}", cycle);
+            assert_eq!(player.finished(), cycle >= 12);
+            cpu.step().unwrap();
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use cpu::Cpu;
+use loader;
+use platform::Platform;
+use quirks::Profile;
+use settings::RomSettings;
+
+/// The command line options that set up the machine, which every emulator
+/// takes: `--platform`, `--quirks`, `--speed`, `--seed` and
+/// `--load-address`
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::options::MachineOptions;
+/// use chip8_rust::platform::Platform;
+/// use chip8_rust::settings::RomSettings;
+///
+/// let mut options = MachineOptions::default();
+/// let mut args = vec!["schip".to_string()].into_iter();
+/// assert_eq!(options.parse_arg("--platform", &mut args), Ok(true));
+/// assert_eq!(options.parse_arg("--frames", &mut args), Ok(false));
+///
+/// let cpu = options.cpu(&RomSettings::default());
+/// assert_eq!(cpu.platform(), Platform::SuperChip);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MachineOptions {
+    pub platform: Platform,
+    /// The quirks to use, the usual ones of the platform if not given
+    pub profile: Option<Profile>,
+    /// The number of instructions per frame, which wins over the settings
+    /// of the rom
+    pub speed: Option<u32>,
+    /// The seed of the random number generator
+    pub seed: Option<u64>,
+    /// The address to load the rom at, which wins over the settings of the
+    /// rom
+    pub load_address: Option<u16>,
+}
+
+impl MachineOptions {
+    /// Takes an option from the command line if it is one of these
+    /// Returns false if it is not, so that the caller can handle it, and
+    /// an error if its value is missing or invalid
+    ///
+    /// # Arguments
+    ///
+    /// * `arg` - The option, e.g. `--speed`
+    /// * `args` - The rest of the command line, which the value of the
+    ///   option is taken from
+    ///
+    pub fn parse_arg<I>(&mut self, arg: &str, args: &mut I)
+        -> Result<bool, String>
+        where I: Iterator<Item = String>
+    {
+        let names = [
+            "--platform",
+            "--quirks",
+            "--speed",
+            "--seed",
+            "--load-address",
+        ];
+        if !names.contains(&arg) {
+            return Ok(false);
+        }
+        let value = args.next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        match arg {
+            "--platform" => self.platform = value.parse()?,
+            "--quirks" => self.profile = Some(value.parse()?),
+            "--speed" => {
+                self.speed = match value.parse::<u32>() {
+                    Ok(speed) if speed > 0 => Some(speed),
+                    _ => return Err(format!("Invalid speed '{}'", value)),
+                };
+            },
+            "--seed" => {
+                self.seed = Some(value.parse().map_err(|err| {
+                    format!("Invalid seed '{}': {}", value, err)
+                })?);
+            },
+            _ => self.load_address = Some(loader::parse_address(&value)?),
+        }
+        Ok(true)
+    }
+
+    /// Creates a Cpu set up as the options say, falling back on the
+    /// settings of the rom and then on the defaults of the platform
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The settings of the rom that is about to be loaded
+    ///
+    pub fn cpu(&self, settings: &RomSettings) -> Cpu {
+        let profile = self.profile.unwrap_or_else(|| {
+            self.platform.default_profile()
+        });
+        let mut cpu = Cpu::with_platform(self.platform, profile.quirks());
+        if let Some(seed) = self.seed {
+            cpu.seed_rng(seed);
+        }
+        if let Some(speed) = self.speed.or(settings.speed) {
+            cpu.set_speed(speed);
+        }
+        if let Some(addr) = self.load_address.or(settings.load_address) {
+            cpu.set_load_address(addr);
+        }
+        cpu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a command line, returning the options and what was left
+    fn parse(line: &str) -> Result<(MachineOptions, Vec<String>), String> {
+        let mut options = MachineOptions::default();
+        let mut rest = vec![];
+        let mut args = line.split_whitespace().map(String::from);
+        while let Some(arg) = args.next() {
+            if !options.parse_arg(&arg, &mut args)? {
+                rest.push(arg);
+            }
+        }
+        Ok((options, rest))
+    }
+
+    #[test]
+    fn machine_options_are_parsed() {
+        let (options, rest) = parse(
+            "--platform xochip --quirks vip --speed 30 --frames 5 \
+             --seed 7 --load-address 0x600 rom"
+        ).unwrap();
+        assert_eq!(options, MachineOptions {
+            platform: Platform::XoChip,
+            profile: Some(Profile::Vip),
+            speed: Some(30),
+            seed: Some(7),
+            load_address: Some(0x600),
+        });
+        assert_eq!(rest, ["--frames", "5", "rom"]);
+    }
+
+    #[test]
+    fn bad_values_are_refused() {
+        for line in [
+            "--platform chip9",
+            "--quirks",
+            "--speed 0",
+            "--speed fast",
+            "--seed -1",
+            "--load-address 0x10000",
+        ].iter() {
+            assert!(parse(line).is_err(), "'{}' was accepted", line);
+        }
+    }
+
+    #[test]
+    fn options_win_over_the_settings_of_the_rom() {
+        let settings = RomSettings {
+            speed: Some(7),
+            load_address: Some(0x600),
+            ..RomSettings::default()
+        };
+        let (options, _) = parse("--platform schip --speed 20").unwrap();
+        let cpu = options.cpu(&settings);
+        assert_eq!(cpu.speed(), 20);
+        assert_eq!(cpu.load_address(), 0x600);
+        assert_eq!(cpu.quirks(), Profile::SuperChip.quirks());
+    }
+}
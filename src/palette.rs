@@ -0,0 +1,244 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+use core::Pixel;
+use settings;
+
+/// The palettes that come with the emulator, the first of which is the
+/// default
+const BUILTIN: [(&str, [[u8; 3]; 4]); 5] = [
+    ("classic", [[0, 0, 0], [255, 255, 255], [85, 85, 85], [170, 170, 170]]),
+    ("green", [[5, 20, 5], [51, 255, 102], [26, 128, 51], [38, 191, 76]]),
+    ("amber", [[20, 10, 0], [255, 176, 0], [128, 88, 0], [191, 132, 0]]),
+    ("lcd", [[155, 188, 15], [15, 56, 15], [139, 172, 15], [48, 98, 48]]),
+    (
+        "high-contrast",
+        [[0, 0, 0], [255, 255, 255], [255, 255, 0], [0, 255, 255]],
+    ),
+];
+
+/// The colors the display is drawn in, one for each combination of planes
+/// Chip-8 and SUPER-CHIP roms only use the first two, while XO-CHIP roms
+/// drawing on both planes use all four
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::core::Pixel;
+/// use chip8_rust::palette::Palette;
+///
+/// let palette = Palette::new("paper", "#f0f0e0 #202020").unwrap();
+/// assert_eq!(palette.color(Pixel::White), [0x20, 0x20, 0x20]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// The name the palette is chosen by
+    pub name: String,
+    /// The colors of the pixels, indexed by their planes
+    pub colors: [[u8; 3]; 4],
+}
+
+impl Palette {
+    /// Creates a palette from a list of colors written as `#rrggbb`
+    /// Two colors give the background and the first plane, and the colors
+    /// of the second plane and of both planes are mixed from them, while
+    /// four colors give the pixels in the order of their planes
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the palette is chosen by
+    /// * `colors` - The colors, separated by spaces or commas
+    ///
+    pub fn new(name: &str, colors: &str) -> Result<Self, String> {
+        let parsed = colors.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|color| !color.is_empty())
+            .map(parse_color)
+            .collect::<Result<Vec<_>, _>>()?;
+        let colors = match parsed.len() {
+            2 => {
+                let (background, first) = (parsed[0], parsed[1]);
+                [background, first, mix(background, first, 1),
+                    mix(background, first, 2)]
+            },
+            4 => [parsed[0], parsed[1], parsed[2], parsed[3]],
+            count => {
+                return Err(format!(
+                    "Palette '{}' has {} colors, expected 2 or 4",
+                    name,
+                    count
+                ))
+            },
+        };
+        Ok(Palette {
+            name: name.to_string(),
+            colors,
+        })
+    }
+
+    /// Returns the palettes that come with the emulator
+    pub fn builtin() -> Vec<Self> {
+        BUILTIN.iter()
+            .map(|&(name, colors)| Palette {
+                name: name.to_string(),
+                colors,
+            })
+            .collect()
+    }
+
+    /// Returns the path of the palette file of the user, which adds
+    /// palettes of its own to those that come with the emulator
+    /// It holds one `name = colors` pair per line, and lines starting with
+    /// # are comments
+    pub fn user_path() -> Option<PathBuf> {
+        settings::config_dir().map(|dir| dir.join("palettes"))
+    }
+
+    /// Reads the palettes of the user, if there are any
+    pub fn load_user() -> Result<Vec<Self>, String> {
+        let path = match Palette::user_path() {
+            Some(path) => path,
+            None => return Ok(vec![]),
+        };
+        let mut text = String::new();
+        let read = File::open(&path).and_then(|mut file| {
+            file.read_to_string(&mut text)
+        });
+        match read {
+            Ok(_) => parse_list(&text).map_err(|err| {
+                format!("{}: {}", path.display(), err)
+            }),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(vec![])
+            },
+            Err(err) => Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    /// Picks a palette
+    ///
+    /// # Arguments
+    ///
+    /// * `palettes` - The palettes that can be picked by name
+    /// * `spec` - The name of one of them, or else a list of colors as
+    ///   taken by new()
+    ///
+    pub fn find(palettes: &[Palette], spec: &str) -> Result<Self, String> {
+        if let Some(palette) = palettes.iter().find(|p| p.name == spec) {
+            return Ok(palette.clone());
+        }
+        if !spec.starts_with('#') {
+            let names: Vec<&str> = palettes.iter()
+                .map(|palette| palette.name.as_str())
+                .collect();
+            return Err(format!(
+                "Unknown palette '{}', expected one of: {}, or a list of \
+                 colors",
+                spec,
+                names.join(", ")
+            ));
+        }
+        Palette::new("custom", spec)
+    }
+
+    /// Returns the color a pixel is drawn in
+    pub fn color(&self, pixel: Pixel) -> [u8; 3] {
+        self.colors[pixel.planes() as usize]
+    }
+}
+
+impl Default for Palette {
+    /// The classic palette, white on black
+    fn default() -> Self {
+        let (name, colors) = BUILTIN[0];
+        Palette {
+            name: name.to_string(),
+            colors,
+        }
+    }
+}
+
+/// Parses the palettes of a palette file
+fn parse_list(s: &str) -> Result<Vec<Palette>, String> {
+    let mut palettes = vec![];
+    for (i, line) in s.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        let colors = parts.next()
+            .ok_or_else(|| {
+                format!("Line {}: expected 'name = colors'", number)
+            })?
+            .trim();
+        let palette = Palette::new(name, colors)
+            .map_err(|err| format!("Line {}: {}", number, err))?;
+        palettes.push(palette);
+    }
+    Ok(palettes)
+}
+
+/// Parses a color written as `#rrggbb`
+fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid color '{}', expected #rrggbb", s));
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+    match (channel(0), channel(2), channel(4)) {
+        (Ok(r), Ok(g), Ok(b)) => Ok([r, g, b]),
+        _ => Err(format!("Invalid color '{}', expected #rrggbb", s)),
+    }
+}
+
+/// Returns a color a number of thirds of the way from one color to another
+fn mix(from: [u8; 3], to: [u8; 3], thirds: i32) -> [u8; 3] {
+    let mut color = [0; 3];
+    for i in 0..3 {
+        let (a, b) = (from[i] as i32, to[i] as i32);
+        color[i] = (a + (b - a) * thirds / 3) as u8;
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_colors_are_mixed_for_the_second_plane() {
+        let palette = Palette::new("grey", "#000000, #ffffff").unwrap();
+        assert_eq!(palette.colors, Palette::default().colors);
+        assert_eq!(palette.color(Pixel::SecondPlane), [85, 85, 85]);
+        assert_eq!(palette.color(Pixel::BothPlanes), [170, 170, 170]);
+    }
+
+    #[test]
+    fn palettes_are_found_by_name_or_colors() {
+        let mut palettes = Palette::builtin();
+        palettes.extend(parse_list("\
+            # Paper white\n\
+            paper = #f0f0e0 #202020 #a0a0a0 #606060\n\
+        ").unwrap());
+        let amber = Palette::find(&palettes, "amber").unwrap();
+        assert_eq!(amber.color(Pixel::White), [255, 176, 0]);
+        let paper = Palette::find(&palettes, "paper").unwrap();
+        assert_eq!(paper.color(Pixel::BothPlanes), [0x60, 0x60, 0x60]);
+        let custom = Palette::find(&palettes, "#102030 #405060").unwrap();
+        assert_eq!(custom.color(Pixel::Black), [0x10, 0x20, 0x30]);
+        assert!(Palette::find(&palettes, "sepia").is_err());
+    }
+
+    #[test]
+    fn mistakes_are_refused() {
+        assert!(Palette::new("one", "#000000").is_err());
+        assert!(Palette::new("three", "#000000 #111111 #222222").is_err());
+        assert!(Palette::new("short", "#000 #fff").is_err());
+        assert!(Palette::new("bad", "#00000g #ffffff").is_err());
+        assert!(parse_list("paper #ffffff #000000").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::fmt;
+use std::str::FromStr;
+
+use core::Pixel;
+use palette::Palette;
+
+/// How long pixels stay lit on screen after the rom clears them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Persistence {
+    /// Pixels go out as soon as they are cleared
+    #[default]
+    Off,
+    /// Pixels fade out, keeping a fraction of their brightness from one
+    /// frame to the next, from 0 for none to 1 for all of it
+    Decay(f32),
+    /// Pixels stay lit for one frame after they are cleared, so that a
+    /// sprite erased in one frame and drawn again in the next never
+    /// disappears
+    Or,
+}
+
+impl FromStr for Persistence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Persistence::Off),
+            "or" => Ok(Persistence::Or),
+            _ => match s.parse::<f32>() {
+                Ok(decay) if (0.0..1.0).contains(&decay) => {
+                    Ok(Persistence::Decay(decay))
+                },
+                _ => Err(format!(
+                    "Unknown persistence '{}', expected off, or, or a \
+                     fraction of brightness kept every frame from 0 to 1",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Persistence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Persistence::Off => write!(f, "off"),
+            Persistence::Decay(decay) => write!(f, "{}", decay),
+            Persistence::Or => write!(f, "or"),
+        }
+    }
+}
+
+/// A filter that imitates the afterglow of a phosphor screen, which hides
+/// the flicker of roms that erase their sprites and draw them again
+/// It only changes the colors the display is shown in, never the display
+/// of the Cpu
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::core::Pixel;
+/// use chip8_rust::palette::Palette;
+/// use chip8_rust::phosphor::{Persistence, Phosphor};
+///
+/// let palette = Palette::default();
+/// let mut phosphor = Phosphor::new(Persistence::Or);
+/// phosphor.apply(&[Pixel::White], &palette);
+/// let colors = phosphor.apply(&[Pixel::Black], &palette);
+/// assert_eq!(colors, vec![[255, 255, 255]]);
+/// assert!(!phosphor.settled());
+/// ```
+pub struct Phosphor {
+    persistence: Persistence,
+    /// The colors shown in the last frame, one for each pixel
+    shown: Vec<[f32; 3]>,
+    /// The planes of the pixels in the last frame
+    previous: Vec<u8>,
+    /// Whether showing the display again would look the same as the last
+    /// frame did
+    settled: bool,
+}
+
+impl Phosphor {
+    /// Creates a filter with nothing lit yet
+    ///
+    /// # Arguments
+    ///
+    /// * `persistence` - How long pixels stay lit
+    ///
+    pub fn new(persistence: Persistence) -> Self {
+        Phosphor {
+            persistence,
+            shown: vec![],
+            previous: vec![],
+            settled: true,
+        }
+    }
+
+    /// Returns how long pixels stay lit
+    pub fn persistence(&self) -> Persistence {
+        self.persistence
+    }
+
+    /// Works out the colors to show the display in for the next frame
+    /// Every call counts as a frame, so the display has to be shown once
+    /// per frame for as long as settled() says otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * `display` - The pixels of the display, row by row
+    /// * `palette` - The colors of the pixels
+    ///
+    pub fn apply(
+        &mut self,
+        display: &[Pixel],
+        palette: &Palette
+    ) -> Vec<[u8; 3]> {
+        // A change of resolution starts over with nothing lit
+        if self.previous.len() != display.len() {
+            self.previous = vec![0; display.len()];
+            self.shown = display.iter()
+                .map(|_| to_float(palette.colors[0]))
+                .collect();
+        }
+        let mut colors = Vec::with_capacity(display.len());
+        self.settled = true;
+        for (i, pixel) in display.iter().enumerate() {
+            let planes = pixel.planes();
+            let color = match self.persistence {
+                Persistence::Off => palette.color(*pixel),
+                Persistence::Or => {
+                    if planes != self.previous[i] {
+                        self.settled = false;
+                    }
+                    palette.colors[(planes | self.previous[i]) as usize]
+                },
+                Persistence::Decay(decay) => {
+                    let target = to_float(palette.color(*pixel));
+                    let mut shown = target;
+                    // Lit pixels light up at once and only the cleared
+                    // ones fade, towards the color of the background
+                    if planes == 0 {
+                        for c in 0..3 {
+                            let glow = self.shown[i][c] - target[c];
+                            shown[c] += glow * decay;
+                            if (shown[c] - target[c]).abs() >= 0.5 {
+                                self.settled = false;
+                            }
+                        }
+                    }
+                    self.shown[i] = shown;
+                    to_bytes(shown)
+                },
+            };
+            self.previous[i] = planes;
+            colors.push(color);
+        }
+        colors
+    }
+
+    /// Checks whether showing the display again would look the same as the
+    /// last frame did, in which case it only has to be shown when it
+    /// changes
+    pub fn settled(&self) -> bool {
+        self.settled
+    }
+}
+
+/// Turns a color into floating point channels
+fn to_float(color: [u8; 3]) -> [f32; 3] {
+    [color[0] as f32, color[1] as f32, color[2] as f32]
+}
+
+/// Turns floating point channels back into a color
+fn to_bytes(color: [f32; 3]) -> [u8; 3] {
+    [
+        color[0].round() as u8,
+        color[1].round() as u8,
+        color[2].round() as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn off_shows_the_display_as_it_is() {
+        let palette = Palette::default();
+        let mut phosphor = Phosphor::new(Persistence::Off);
+        phosphor.apply(&[Pixel::White, Pixel::BothPlanes], &palette);
+        let colors = phosphor.apply(&[Pixel::Black, Pixel::White], &palette);
+        assert_eq!(colors, vec![[0, 0, 0], [255, 255, 255]]);
+        assert!(phosphor.settled());
+    }
+
+    #[test]
+    fn or_keeps_cleared_pixels_for_a_frame() {
+        let palette = Palette::default();
+        let mut phosphor = Phosphor::new(Persistence::Or);
+        let lit = [Pixel::White, Pixel::SecondPlane];
+        let clear = [Pixel::Black, Pixel::White];
+        phosphor.apply(&lit, &palette);
+        assert_eq!(
+            phosphor.apply(&clear, &palette),
+            vec![[255, 255, 255], [170, 170, 170]]
+        );
+        assert!(!phosphor.settled());
+        assert_eq!(
+            phosphor.apply(&clear, &palette),
+            vec![[0, 0, 0], [255, 255, 255]]
+        );
+        assert!(phosphor.settled());
+    }
+
+    #[test]
+    fn decay_fades_towards_the_background() {
+        let palette = Palette::default();
+        let mut phosphor = Phosphor::new(Persistence::Decay(0.5));
+        let (lit, clear) = ([Pixel::White], [Pixel::Black]);
+        assert_eq!(phosphor.apply(&lit, &palette), vec![[255; 3]]);
+        assert_eq!(phosphor.apply(&clear, &palette), vec![[128; 3]]);
+        assert_eq!(phosphor.apply(&clear, &palette), vec![[64; 3]]);
+        assert!(!phosphor.settled());
+        // Lighting the pixel again brings it straight back
+        assert_eq!(phosphor.apply(&lit, &palette), vec![[255; 3]]);
+        for _ in 0..10 {
+            phosphor.apply(&clear, &palette);
+        }
+        assert!(phosphor.settled());
+        assert_eq!(phosphor.apply(&clear, &palette), vec![[0; 3]]);
+    }
+
+    #[test]
+    fn persistence_is_parsed() {
+        assert_eq!("off".parse(), Ok(Persistence::Off));
+        assert_eq!("or".parse(), Ok(Persistence::Or));
+        assert_eq!("0.75".parse(), Ok(Persistence::Decay(0.75)));
+        assert!("1.5".parse::<Persistence>().is_err());
+        assert!("glow".parse::<Persistence>().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+use std::str::FromStr;
+
+use cpu::Opcode;
+use quirks::Profile;
+
+/// The family of interpreters a rom was written for
+/// The platform decides which instructions exist, while the quirks decide
+/// how the shared instructions behave
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    /// Returns the quirk profile roms for the platform usually expect
+    pub fn default_profile(self) -> Profile {
+        match self {
+            Platform::Chip8 => Profile::Vip,
+            Platform::SuperChip => Profile::SuperChip,
+            Platform::XoChip => Profile::Modern,
+        }
+    }
+
+    /// Returns the number of instructions roms for the platform usually
+    /// expect to run in every 60 Hz frame
+    pub fn default_speed(self) -> u32 {
+        match self {
+            Platform::Chip8 => 10,
+            Platform::SuperChip => 30,
+            Platform::XoChip => 1000,
+        }
+    }
+
+    /// Returns the number of bytes of memory the platform can address
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => 4096,
+            Platform::XoChip => 65536,
+        }
+    }
+
+    /// Checks whether an instruction exists on the platform
+    ///
+    /// # Arguments
+    ///
+    /// * `opcode` - A decoded instruction, which may belong to an extension
+    ///   of the original instruction set
+    ///
+    pub fn supports(self, opcode: &Opcode) -> bool {
+        match *opcode {
+            Opcode::ScrollDown(_) |
+            Opcode::ScrollRight |
+            Opcode::ScrollLeft |
+            Opcode::Exit |
+            Opcode::DisableHighRes |
+            Opcode::EnableHighRes |
+            Opcode::SetIndexRegisterToBigSpriteAddr(_) |
+            Opcode::SaveFlags(_) |
+            Opcode::LoadFlags(_) => self != Platform::Chip8,
+            Opcode::ScrollUp(_) |
+            Opcode::SaveRegisterRange { .. } |
+            Opcode::LoadRegisterRange { .. } |
+            Opcode::SetIndexRegisterLong(_) |
+            Opcode::SelectPlanes(_) |
+            Opcode::LoadAudioPattern |
+            Opcode::SetPitch(_) => self == Platform::XoChip,
+            _ => true,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!(
+                "Unknown platform '{}', expected one of: chip8, schip, xochip",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The behaviors that differ between the interpreters that Chip-8 roms were
+/// written for
+/// Each field picks one instruction between its known variants
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    /// Where FX55 and FX65 leave I pointing once they are done
+    pub load_store_index: LoadStoreIndex,
+    /// BNNN jumps to XNN plus VX instead of NNN plus V0
+    pub jump_uses_vx: bool,
+    /// DXYN cuts sprites off at the edges of the screen instead of wrapping
+    /// them around to the other side
+    pub clip_sprites: bool,
+    /// 8XY1, 8XY2 and 8XY3 set VF to 0
+    pub logic_resets_vf: bool,
+    /// DXYN waits for the next 60 Hz frame, so that at most one sprite is
+    /// drawn per frame
+    pub display_wait: bool,
+}
+
+/// How FX55 and FX65 move I after storing or loading V0 to VX
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadStoreIndex {
+    /// I points past the last register, as on the COSMAC VIP
+    IncrementByXPlusOne,
+    /// I points at the last register, as on CHIP-48, which got the
+    /// increment wrong
+    IncrementByX,
+    /// I is left alone, as on SUPER-CHIP 1.1
+    Unchanged,
+}
+
+impl Quirks {
+    /// The original interpreter on the COSMAC VIP
+    pub fn vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_index: LoadStoreIndex::IncrementByXPlusOne,
+            jump_uses_vx: false,
+            clip_sprites: true,
+            logic_resets_vf: true,
+            display_wait: true,
+        }
+    }
+
+    /// The CHIP-48 interpreter for the HP-48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_index: LoadStoreIndex::IncrementByX,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            logic_resets_vf: false,
+            display_wait: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1, which most of the later HP-48 games target
+    pub fn superchip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_index: LoadStoreIndex::Unchanged,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            logic_resets_vf: false,
+            display_wait: false,
+        }
+    }
+
+    /// The behavior of modern interpreters such as Octo, which XO-CHIP and
+    /// most recent roms are written against
+    pub fn modern() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_index: LoadStoreIndex::IncrementByXPlusOne,
+            jump_uses_vx: false,
+            clip_sprites: false,
+            logic_resets_vf: false,
+            display_wait: false,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Profile::default().quirks()
+    }
+}
+
+/// A named set of quirks that can be selected on the command line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Profile {
+    #[default]
+    Vip,
+    Chip48,
+    SuperChip,
+    Modern,
+}
+
+impl Profile {
+    /// Returns the quirks that make up the profile
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Profile::Vip => Quirks::vip(),
+            Profile::Chip48 => Quirks::chip48(),
+            Profile::SuperChip => Quirks::superchip(),
+            Profile::Modern => Quirks::modern(),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vip" => Ok(Profile::Vip),
+            "chip48" => Ok(Profile::Chip48),
+            "schip" => Ok(Profile::SuperChip),
+            "modern" => Ok(Profile::Modern),
+            _ => Err(format!(
+                "Unknown quirk profile '{}', expected one of: \
+                 vip, chip48, schip, modern",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Profile::Vip => "vip",
+            Profile::Chip48 => "chip48",
+            Profile::SuperChip => "schip",
+            Profile::Modern => "modern",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::VecDeque;
+use std::iter;
+
+use cpu::Cpu;
+
+/// The number of frames kept by default, ten seconds at 60 Hz
+pub const DEFAULT_HISTORY: usize = 600;
+/// The number of bytes the history may take up by default
+pub const DEFAULT_BUDGET: usize = 16 * 1024 * 1024;
+
+/// A bounded history of the states of a Cpu, one per frame, that can be
+/// walked backwards
+/// Only the newest state is kept whole; every older one is stored as the
+/// difference from the state that came after it, which is mostly zeros
+/// and compresses to a few bytes per frame
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::cpu::Cpu;
+/// use chip8_rust::rewind::Rewind;
+///
+/// let mut cpu = Cpu::new();
+/// // ADD V0, 1 and JP 0x200, forever
+/// cpu.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+/// let mut rewind = Rewind::new(60, 1024 * 1024);
+/// rewind.push(&cpu);
+/// cpu.execute(10).unwrap();
+/// rewind.push(&cpu);
+///
+/// assert!(rewind.step_back(&mut cpu));
+/// assert_eq!(cpu.cycles(), 0);
+/// ```
+pub struct Rewind {
+    history: usize,
+    budget: usize,
+    newest: Option<Vec<u8>>,
+    /// Turns a state into the one before it, the oldest at the front
+    deltas: VecDeque<Vec<u8>>,
+    used: usize,
+}
+
+impl Rewind {
+    /// Creates an empty history
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The number of frames to keep at most
+    /// * `budget` - The number of bytes the history may take up, after
+    ///   which the oldest frames are dropped
+    ///
+    pub fn new(history: usize, budget: usize) -> Self {
+        Rewind {
+            history,
+            budget,
+            newest: None,
+            deltas: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    /// Returns the number of frames that can be stepped back
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// Checks whether there is nowhere to step back to
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Adds the current state of a Cpu to the history, dropping the oldest
+    /// states if the history has grown too long or too large
+    pub fn push(&mut self, cpu: &Cpu) {
+        let state = cpu.save_state();
+        if let Some(previous) = self.newest.take() {
+            let delta = compress(&previous, &state);
+            self.used += delta.len();
+            self.deltas.push_back(delta);
+            self.used -= previous.len();
+        }
+        self.used += state.len();
+        self.newest = Some(state);
+
+        while self.deltas.len() > self.history || self.used > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Restores the Cpu to the state of the frame before the newest one,
+    /// which becomes the newest
+    /// Returns false if there is no older state to go back to
+    pub fn step_back(&mut self, cpu: &mut Cpu) -> bool {
+        let delta = match self.deltas.pop_back() {
+            Some(delta) => delta,
+            None => return false,
+        };
+        let newest = self.newest.take().unwrap_or_default();
+        let older = decompress(&delta, &newest);
+        self.used = self.used - delta.len() - newest.len() + older.len();
+        // The states were all taken from this Cpu, so they cannot be
+        // refused
+        cpu.load_state(&older).expect("Rewound to an invalid state");
+        self.newest = Some(older);
+        true
+    }
+}
+
+/// Appends a number in as few bytes as it needs, 7 bits at a time
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a number written by write_varint(), advancing past it
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some(&byte) = data.get(*pos) {
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+/// Describes how to turn the newer of two states back into the older one
+/// The states are XORed together, and the result is stored as runs of
+/// zeros followed by literal bytes
+fn compress(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let len = older.len().max(newer.len());
+    let byte = |state: &[u8], i: usize| state.get(i).cloned().unwrap_or(0);
+    let xor = |i: usize| byte(older, i) ^ byte(newer, i);
+
+    let mut out = vec![];
+    write_varint(&mut out, older.len());
+    let mut i = 0;
+    while i < len {
+        let zeros = (i..len).take_while(|&j| xor(j) == 0).count();
+        i += zeros;
+        let literals = (i..len).take_while(|&j| xor(j) != 0).count();
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend((i..i + literals).map(xor));
+        i += literals;
+    }
+    out
+}
+
+/// Rebuilds the older state from the newer one and what compress()
+/// returned for the two
+fn decompress(delta: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos);
+    let mut xor = vec![];
+    while pos < delta.len() {
+        let zeros = read_varint(delta, &mut pos);
+        xor.extend(iter::repeat_n(0, zeros));
+        let literals = read_varint(delta, &mut pos);
+        xor.extend_from_slice(&delta[pos..pos + literals]);
+        pos += literals;
+    }
+    let byte = |state: &[u8], i: usize| state.get(i).cloned().unwrap_or(0);
+    (0..len).map(|i| byte(newer, i) ^ byte(&xor, i)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the older state comes back from the delta
+    fn round_trip(older: &[u8], newer: &[u8]) -> Vec<u8> {
+        let delta = compress(older, newer);
+        assert_eq!(decompress(&delta, newer), older);
+        delta
+    }
+
+    /// A Cpu that adds 1 to V0 forever
+    fn counter() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn identical_states_compress_to_almost_nothing() {
+        let state: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let delta = round_trip(&state, &state);
+        assert!(delta.len() <= 5, "{:?}", delta);
+    }
+
+    #[test]
+    fn changed_bytes_come_back() {
+        let older: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+        let mut newer = older.clone();
+        newer[500] ^= 0x40;
+        let delta = round_trip(&older, &newer);
+        assert!(delta.len() <= 10, "{:?}", delta);
+
+        // The last byte ends on literals, with no zeros after them
+        let mut newer = older.clone();
+        newer[999] = 0;
+        round_trip(&older, &newer);
+        let mut newer = older.clone();
+        newer[0] ^= 1;
+        round_trip(&older, &newer);
+    }
+
+    #[test]
+    fn long_runs_cross_the_varint_boundary() {
+        let older = vec![0x55; 1000];
+        let mut newer = older.clone();
+        // 200 zeros, 300 literals, then 500 zeros, all above 127
+        for byte in newer[200..500].iter_mut() {
+            *byte = 0xAA;
+        }
+        let delta = round_trip(&older, &newer);
+        assert_eq!(&delta[..2], &[0xE8, 0x07]);
+        assert_eq!(&delta[2..6], &[0xC8, 0x01, 0xAC, 0x02]);
+        // Exactly 128 on either side of the boundary
+        let mut newer = older.clone();
+        for byte in newer[128..256].iter_mut() {
+            *byte = 0;
+        }
+        round_trip(&older, &newer);
+        let mut newer = older.clone();
+        newer[127] = 0;
+        round_trip(&older, &newer);
+    }
+
+    #[test]
+    fn states_of_different_lengths_come_back() {
+        let short = vec![1, 2, 3];
+        let long = vec![1, 2, 3, 4, 5, 6];
+        round_trip(&short, &long);
+        round_trip(&long, &short);
+        round_trip(&[], &long);
+    }
+
+    #[test]
+    fn stepping_back_restores_every_frame() {
+        let mut cpu = counter();
+        let mut rewind = Rewind::new(60, DEFAULT_BUDGET);
+        for _ in 0..10 {
+            rewind.push(&cpu);
+            cpu.execute(3).unwrap();
+        }
+        rewind.push(&cpu);
+        for frame in (0..10).rev() {
+            assert!(rewind.step_back(&mut cpu));
+            assert_eq!(cpu.cycles(), frame * 3);
+        }
+        assert!(!rewind.step_back(&mut cpu));
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn oldest_frames_are_dropped() {
+        let mut cpu = counter();
+        let mut rewind = Rewind::new(5, DEFAULT_BUDGET);
+        for _ in 0..20 {
+            rewind.push(&cpu);
+            cpu.execute(1).unwrap();
+        }
+        assert_eq!(rewind.len(), 5);
+
+        // A budget with room for the newest state and a few deltas
+        let budget = cpu.save_state().len() + 100;
+        let mut rewind = Rewind::new(DEFAULT_HISTORY, budget);
+        for _ in 0..50 {
+            rewind.push(&cpu);
+            cpu.execute(1).unwrap();
+            assert!(rewind.used <= budget);
+        }
+        assert!(!rewind.is_empty() && rewind.len() < 49);
+        // What is left still steps back to the frames it came from
+        let len = rewind.len() as u64;
+        let newest = cpu.cycles() - 1;
+        while rewind.step_back(&mut cpu) {}
+        assert_eq!(cpu.cycles(), newest - len);
+    }
+}
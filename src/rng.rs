@@ -0,0 +1,62 @@
+use rand;
+
+/// The random number generator behind CXNN
+/// It is a SplitMix64 generator, whose whole state is a single number, so
+/// that it can be seeded for reproducible runs and stored in save states
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Creates a generator that always produces the same numbers for the
+    /// same seed
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - Any number, including 0
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chip8_rust::rng::Random;
+    ///
+    /// let mut first = Random::with_seed(42);
+    /// let mut second = Random::with_seed(42);
+    /// assert_eq!(first.next_u8(), second.next_u8());
+    /// ```
+    pub fn with_seed(seed: u64) -> Self {
+        Random { state: seed }
+    }
+
+    /// Creates a generator with a seed taken from the operating system
+    pub fn from_entropy() -> Self {
+        Random::with_seed(rand::random())
+    }
+
+    /// Returns the state the generator would need to be seeded with to
+    /// produce the same numbers from here on
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// Returns the next number, any of which are equally likely
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns the next byte, covering the full range from 0 to 255
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Random::from_entropy()
+    }
+}
@@ -0,0 +1,315 @@
+use std::error::Error;
+use std::fmt;
+
+use platform::Platform;
+use quirks::{LoadStoreIndex, Quirks};
+
+/// The first bytes of every save state file
+pub const MAGIC: &[u8; 8] = b"CHIP8SAV";
+/// The version of the save state format written by this build
+/// Bump it whenever the layout of the state changes
+pub const VERSION: u16 = 1;
+
+/// The reasons a save state can be refused
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The data does not start with the save state magic
+    BadMagic,
+    /// The state was written by a different version of the format
+    UnsupportedVersion(u16),
+    /// The state was taken while a different rom was loaded
+    RomMismatch { expected: u64, found: u64 },
+    /// The state ends before all of the machine has been read
+    Truncated,
+    /// A value in the state cannot belong to a real machine
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "Save state version {} is not supported, expected {}",
+                version,
+                VERSION
+            ),
+            StateError::RomMismatch { expected, found } => write!(
+                f,
+                "Save state is for rom {:016x}, but rom {:016x} is loaded",
+                found,
+                expected
+            ),
+            StateError::Truncated => write!(f, "Save state is truncated"),
+            StateError::Invalid(what) => {
+                write!(f, "Save state has an invalid {}", what)
+            },
+        }
+    }
+}
+
+impl Error for StateError {}
+
+/// Hashes the contents of a rom with 64-bit FNV-1a, so that a save state
+/// can tell which rom it belongs to
+///
+/// # Arguments
+///
+/// * `rom` - The bytes of the rom as loaded
+///
+pub fn hash_rom(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Packs the quirks into a byte, one bit per quirk except for how FX55
+/// and FX65 move I, which takes bits 1 and 2
+pub(crate) fn quirks_to_bits(quirks: Quirks) -> u8 {
+    let load_store_index = match quirks.load_store_index {
+        LoadStoreIndex::Unchanged => 0,
+        LoadStoreIndex::IncrementByXPlusOne => 1,
+        LoadStoreIndex::IncrementByX => 2,
+    };
+    [
+        (quirks.shift_uses_vy, 0),
+        (quirks.jump_uses_vx, 3),
+        (quirks.clip_sprites, 4),
+        (quirks.logic_resets_vf, 5),
+        (quirks.display_wait, 6),
+    ].iter()
+        .fold(load_store_index << 1, |bits, &(set, i)| {
+            bits | (set as u8) << i
+        })
+}
+
+/// Unpacks quirks packed by quirks_to_bits()
+pub(crate) fn quirks_from_bits(bits: u8) -> Result<Quirks, StateError> {
+    let set = |i: u8| bits & (1 << i) != 0;
+    let load_store_index = match (bits >> 1) & 0b11 {
+        0 => LoadStoreIndex::Unchanged,
+        1 => LoadStoreIndex::IncrementByXPlusOne,
+        2 => LoadStoreIndex::IncrementByX,
+        _ => return Err(StateError::Invalid("quirks")),
+    };
+    if bits >> 7 != 0 {
+        return Err(StateError::Invalid("quirks"));
+    }
+    Ok(Quirks {
+        shift_uses_vy: set(0),
+        load_store_index,
+        jump_uses_vx: set(3),
+        clip_sprites: set(4),
+        logic_resets_vf: set(5),
+        display_wait: set(6),
+    })
+}
+
+/// Returns the byte a platform is stored as
+pub(crate) fn platform_to_byte(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 0,
+        Platform::SuperChip => 1,
+        Platform::XoChip => 2,
+    }
+}
+
+/// Returns the platform stored as a byte by platform_to_byte()
+pub(crate) fn platform_from_byte(byte: u8) -> Result<Platform, StateError> {
+    match byte {
+        0 => Ok(Platform::Chip8),
+        1 => Ok(Platform::SuperChip),
+        2 => Ok(Platform::XoChip),
+        _ => Err(StateError::Invalid("platform")),
+    }
+}
+
+/// Appends the fields of a save state in big-endian order
+#[derive(Default)]
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn bytes(&mut self, values: &[u8]) {
+        self.bytes.extend_from_slice(values);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads back the fields appended by a StateWriter
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        StateReader { data }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("flag")),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    /// Checks that nothing is left over once the whole state has been read
+    pub fn finish(self) -> Result<(), StateError> {
+        if self.data.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(StateError::Invalid("length"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::Cpu;
+    use quirks::Profile;
+
+    /// A Cpu that has run a rom adding 1 to V0 and drawing a digit forever
+    fn running() -> Cpu {
+        let mut cpu = Cpu::with_platform(Platform::XoChip, Quirks::modern());
+        cpu.load_fontset();
+        cpu.load_rom_bytes(&[
+            0x70, 0x01, // ADD V0, 1
+            0xF0, 0x29, // LD F, V0
+            0xD1, 0x15, // DRW V1, V1, 5
+            0x12, 0x00, // JP 0x200
+        ]).unwrap();
+        cpu.execute(25).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn states_are_restored() {
+        let mut cpu = running();
+        let state = cpu.save_state();
+        let (pc, registers) = (cpu.pc(), cpu.registers().to_vec());
+        cpu.execute(13).unwrap();
+        assert!(cpu.pc() != pc);
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.pc(), pc);
+        assert_eq!(cpu.registers(), &registers[..]);
+        assert_eq!(cpu.cycles(), 25);
+        assert_eq!(cpu.save_state(), state);
+    }
+
+    #[test]
+    fn quirks_survive_being_packed() {
+        for &profile in [
+            Profile::Vip,
+            Profile::Chip48,
+            Profile::SuperChip,
+            Profile::Modern,
+        ].iter() {
+            let quirks = profile.quirks();
+            assert_eq!(quirks_from_bits(quirks_to_bits(quirks)), Ok(quirks));
+        }
+        assert_eq!(quirks_to_bits(Quirks::vip()), 0x73);
+        assert_eq!(quirks_to_bits(Quirks::chip48()), 0x1c);
+        assert_eq!(
+            quirks_from_bits(0x06),
+            Err(StateError::Invalid("quirks"))
+        );
+        assert_eq!(
+            quirks_from_bits(0x80),
+            Err(StateError::Invalid("quirks"))
+        );
+    }
+
+    #[test]
+    fn broken_states_are_refused() {
+        let mut cpu = running();
+        let state = cpu.save_state();
+
+        let mut bad_magic = state.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(cpu.load_state(&bad_magic), Err(StateError::BadMagic));
+        assert_eq!(cpu.load_state(b"CHIP"), Err(StateError::BadMagic));
+
+        let mut newer = state.clone();
+        newer[MAGIC.len()..MAGIC.len() + 2]
+            .copy_from_slice(&(VERSION + 1).to_be_bytes());
+        assert_eq!(
+            cpu.load_state(&newer),
+            Err(StateError::UnsupportedVersion(VERSION + 1))
+        );
+
+        for len in [MAGIC.len() + 1, 40, state.len() - 1].iter() {
+            assert_eq!(
+                cpu.load_state(&state[..*len]),
+                Err(StateError::Truncated)
+            );
+        }
+        let mut longer = state.clone();
+        longer.push(0);
+        assert_eq!(cpu.load_state(&longer), Err(StateError::Invalid("length")));
+
+        // Nothing was changed by the states that were refused
+        assert_eq!(cpu.save_state(), state);
+    }
+
+    #[test]
+    fn states_of_another_rom_are_refused() {
+        let state = running().save_state();
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        assert_eq!(
+            cpu.load_state(&state),
+            Err(StateError::RomMismatch {
+                expected: cpu.rom_hash(),
+                found: running().rom_hash(),
+            })
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The number of frames the Chip-8 runs each second, which is also the
+/// rate its timers count down at
+pub const FRAME_RATE: u32 = 60;
+/// How far the clock may fall behind, e.g. while the debugger is paused,
+/// before it gives up on catching up and starts counting from now
+const MAX_LAG: u32 = 5;
+/// How long before a deadline the clock stops sleeping and starts
+/// spinning, since sleeps may overshoot by about this much
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+/// The slowest slow motion, an eighth of full speed
+const MAX_SLOWDOWN: u32 = 8;
+
+/// Keeps frames 1/60th of a second apart using the high resolution clock
+/// of the host, for frontends that cannot synchronize to vsync
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::scheduler::FrameClock;
+/// use std::time::Instant;
+///
+/// let mut clock = FrameClock::new();
+/// let start = Instant::now();
+/// clock.wait();
+/// clock.wait();
+/// assert!(start.elapsed().as_secs_f64() >= 2.0 / 60.0 * 0.99);
+/// ```
+pub struct FrameClock {
+    frame: Duration,
+    next: Instant,
+}
+
+impl FrameClock {
+    /// Creates a clock whose first frame starts now
+    pub fn new() -> Self {
+        FrameClock {
+            frame: Duration::from_secs(1) / FRAME_RATE,
+            next: Instant::now(),
+        }
+    }
+
+    /// Makes every frame last longer, for slow motion
+    ///
+    /// # Arguments
+    ///
+    /// * `slowdown` - How many times longer than 1/60th of a second a frame
+    ///   should last
+    ///
+    pub fn set_slowdown(&mut self, slowdown: u32) {
+        self.frame = Duration::from_secs(1) * slowdown.max(1) / FRAME_RATE;
+    }
+
+    /// Checks without blocking whether the current frame is over, and if
+    /// so starts the next one
+    /// Used to decide when to draw while running unthrottled
+    pub fn frame_due(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next + self.frame {
+            return false;
+        }
+        self.next = now;
+        true
+    }
+
+    /// Blocks until the current frame is over and the next one can start
+    /// Frames are scheduled from when the previous one was due rather than
+    /// from when it ended, so that small delays do not add up
+    pub fn wait(&mut self) {
+        self.next += self.frame;
+        let now = Instant::now();
+        if now > self.next + self.frame * MAX_LAG {
+            self.next = now;
+            return;
+        }
+        if let Some(remaining) = self.next.checked_duration_since(now) {
+            if remaining > SPIN_MARGIN {
+                thread::sleep(remaining - SPIN_MARGIN);
+            }
+        }
+        while Instant::now() < self.next {
+            thread::yield_now();
+        }
+    }
+
+    /// Starts counting frames from now, e.g. after the emulator was paused
+    pub fn reset(&mut self) {
+        self.next = Instant::now();
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        FrameClock::new()
+    }
+}
+
+/// How fast the emulator runs compared to the real machine, as chosen by
+/// the speed hotkeys
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::scheduler::Pace;
+///
+/// let mut pace = Pace::default();
+/// pace.slow_down();
+/// assert_eq!(pace.to_string(), "1/2x");
+/// pace.toggle_pause();
+/// assert!(pace.is_paused());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pace {
+    paused: bool,
+    turbo: bool,
+    slowdown: u32,
+}
+
+impl Pace {
+    /// Checks whether the emulator is stopped until resumed or advanced
+    /// by a frame
+    pub fn is_paused(self) -> bool {
+        self.paused
+    }
+
+    /// Checks whether the emulator runs as fast as the host allows
+    pub fn is_turbo(self) -> bool {
+        self.turbo
+    }
+
+    /// Returns how many times longer than usual every frame lasts
+    pub fn slowdown(self) -> u32 {
+        self.slowdown
+    }
+
+    /// Checks whether the emulator runs at exactly full speed
+    pub fn is_normal(self) -> bool {
+        self == Pace::default()
+    }
+
+    /// Stops the emulator, or lets it carry on
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Switches between running unthrottled and running at the chosen speed
+    pub fn toggle_turbo(&mut self) {
+        self.turbo = !self.turbo;
+    }
+
+    /// Halves the speed, down to an eighth of full speed
+    pub fn slow_down(&mut self) {
+        self.slowdown = (self.slowdown * 2).min(MAX_SLOWDOWN);
+    }
+
+    /// Doubles the speed, up to full speed
+    pub fn speed_up(&mut self) {
+        self.slowdown = (self.slowdown / 2).max(1);
+    }
+}
+
+impl Default for Pace {
+    fn default() -> Self {
+        Pace {
+            paused: false,
+            turbo: false,
+            slowdown: 1,
+        }
+    }
+}
+
+impl fmt::Display for Pace {
+    /// Writes the speed the way the frontends show it: "||" while paused,
+    /// ">>" while unthrottled and the multiplier otherwise, e.g. "1/4x"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.paused {
+            write!(f, "||")
+        }
+        else if self.turbo {
+            write!(f, ">>")
+        }
+        else if self.slowdown > 1 {
+            write!(f, "1/{}x", self.slowdown)
+        }
+        else {
+            write!(f, "1x")
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use loader;
+use phosphor::Persistence;
+
+/// Settings that belong to a single rom, kept next to it in a file with
+/// the same name and a .cfg extension added, e.g. pong.ch8.cfg
+///
+/// The file holds one `key = value` pair per line, and lines starting
+/// with # are comments, e.g.
+///
+/// ```text
+/// # Pong is unplayable at the default speed
+/// speed = 7
+/// # An ETI-660 rom
+/// load_address = 0x600
+/// # Played with one hand on the numeric keypad
+/// keymap = numpad
+/// palette = amber
+/// # Hides the flicker of the invaders
+/// persistence = 0.6
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use chip8_rust::settings::RomSettings;
+///
+/// let settings: RomSettings = "speed = 20".parse().unwrap();
+/// assert_eq!(settings.speed, Some(20));
+/// assert!("speed = 0".parse::<RomSettings>().is_err());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomSettings {
+    /// The number of instructions to execute in every 60 Hz frame
+    pub speed: Option<u32>,
+    /// The address the rom is loaded at and starts executing from
+    pub load_address: Option<u16>,
+    /// The keymap to play the rom with, either the name of a preset or the
+    /// path of a keymap file relative to the rom
+    pub keymap: Option<String>,
+    /// The palette to draw the rom in, either the name of a palette or a
+    /// list of colors
+    pub palette: Option<String>,
+    /// How long pixels stay lit on screen after the rom clears them
+    pub persistence: Option<Persistence>,
+}
+
+impl RomSettings {
+    /// Returns the path of the settings file of a rom
+    pub fn path(rom: &str) -> String {
+        format!("{}.cfg", rom)
+    }
+
+    /// Reads the settings of a rom
+    /// A rom without a settings file simply has no settings, and neither
+    /// does a rom read from standard input
+    ///
+    /// # Arguments
+    ///
+    /// * `rom` - The path of the rom, not of its settings file
+    ///
+    pub fn load(rom: &str) -> Result<Self, String> {
+        if rom == "-" {
+            return Ok(RomSettings::default());
+        }
+        let path = RomSettings::path(rom);
+        let mut text = String::new();
+        let read = File::open(&path).and_then(|mut file| {
+            file.read_to_string(&mut text)
+        });
+        match read {
+            Ok(_) => text.parse().map_err(|err| format!("{}: {}", path, err)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(RomSettings::default())
+            },
+            Err(err) => Err(format!("{}: {}", path, err)),
+        }
+    }
+}
+
+/// Returns the directory holding the configuration files of the user,
+/// ~/.config/chip8-rust unless XDG_CONFIG_HOME points somewhere else
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+        })
+        .map(|dir| dir.join("chip8-rust"))
+}
+
+impl FromStr for RomSettings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = RomSettings::default();
+        for (i, line) in s.lines().enumerate() {
+            let number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next()
+                .ok_or_else(|| {
+                    format!("Line {}: expected 'key = value'", number)
+                })?
+                .trim();
+            match key {
+                "speed" => match value.parse() {
+                    Ok(speed) if speed > 0 => settings.speed = Some(speed),
+                    _ => {
+                        return Err(format!(
+                            "Line {}: invalid speed '{}'",
+                            number,
+                            value
+                        ))
+                    },
+                },
+                "load_address" => {
+                    let addr = loader::parse_address(value)
+                        .map_err(|err| format!("Line {}: {}", number, err))?;
+                    settings.load_address = Some(addr);
+                },
+                "keymap" => settings.keymap = Some(value.to_string()),
+                "palette" => settings.palette = Some(value.to_string()),
+                "persistence" => {
+                    settings.persistence = Some(value.parse().map_err(|err| {
+                        format!("Line {}: {}", number, err)
+                    })?);
+                },
+                _ => {
+                    return Err(format!(
+                        "Line {}: unknown setting '{}'",
+                        number,
+                        key
+                    ))
+                },
+            }
+        }
+        Ok(settings)
+    }
+}
@@ -0,0 +1,263 @@
+//! Runs the test roms in tests/roms without a frontend and compares the
+//! display they end with against the golden images in tests/golden
+//!
+//! ibm-logo.ch8 is the public domain IBM logo rom that most interpreters
+//! are first tested with. Its golden image was drawn from the sprites in
+//! the rom by a separate script rather than by this emulator, so it is
+//! never overwritten by CHIP8_BLESS. The community test suites, such as
+//! the one by Timendus, are not vendored yet.
+//!
+//! The other roms were written for this repository. Each one is checked
+//! in as the bytes the emulator runs, next to the source it was assembled
+//! from, so that the assembler is not part of what the golden images
+//! test. Every golden image was checked by hand against the display the
+//! source of its rom documents. After a change
+//! that is meant to alter what a rom shows, assemble it again with
+//!
+//! ```text
+//! cargo run --bin chip8-asm -- -o tests/roms/<rom>.ch8 tests/roms/<rom>.8o
+//! ```
+//!
+//! then check the new display and write it over the old golden image with
+//!
+//! ```text
+//! CHIP8_BLESS=1 cargo test --test golden
+//! ```
+
+extern crate chip8_rust;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use chip8_rust::asm;
+use chip8_rust::cpu::Cpu;
+use chip8_rust::headless::{self, Condition, ScriptedKey, Stop};
+use chip8_rust::platform::Platform;
+use chip8_rust::quirks::Profile;
+
+/// The number of frames after which a rom that has not finished fails
+const MAX_FRAMES: u64 = 600;
+
+/// The characters a pixel is written as, indexed by its planes
+const PIXELS: [char; 4] = ['.', '#', '+', '*'];
+
+/// A rom to run and the golden image to compare it against
+struct Case {
+    /// The name of the golden image, without the .txt extension
+    name: &'static str,
+    /// The name of the rom in tests/roms, without the .ch8 extension
+    rom: &'static str,
+    platform: Platform,
+    profile: Profile,
+    /// The keys to press, as `<frame>:<key>:down|up`
+    keys: &'static [&'static str],
+    /// Whether the golden image comes from this emulator, and so may be
+    /// replaced by CHIP8_BLESS
+    blessable: bool,
+}
+
+impl Case {
+    fn new(name: &'static str, rom: &'static str) -> Self {
+        Case {
+            name,
+            rom,
+            platform: Platform::Chip8,
+            profile: Profile::Vip,
+            keys: &[],
+            blessable: true,
+        }
+    }
+
+    /// A rom from elsewhere, whose golden image comes from a reference
+    /// other than this emulator
+    fn vendored(name: &'static str) -> Self {
+        Case {
+            blessable: false,
+            ..Case::new(name, name)
+        }
+    }
+
+    fn platform(mut self, platform: Platform, profile: Profile) -> Self {
+        self.platform = platform;
+        self.profile = profile;
+        self
+    }
+
+    fn keys(mut self, keys: &'static [&'static str]) -> Self {
+        self.keys = keys;
+        self
+    }
+}
+
+/// Returns the path of a file in the tests directory
+fn test_path(dir: &str, name: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push(dir);
+    path.push(name);
+    path
+}
+
+/// Writes the display one row per line, one character per pixel
+fn render(cpu: &Cpu) -> String {
+    let mut out = String::new();
+    for row in cpu.framebuffer().chunks(cpu.width() as usize) {
+        out.extend(row.iter().map(|pixel| PIXELS[pixel.planes() as usize]));
+        out.push('\n');
+    }
+    out
+}
+
+/// Runs a rom until it finishes and compares its display against the
+/// golden image, or replaces the golden image if CHIP8_BLESS is set and
+/// the image may be replaced
+fn check(case: Case) {
+    let path = test_path("roms", &format!("{}.ch8", case.rom));
+    let rom = fs::read(&path).unwrap_or_else(|err| {
+        panic!("Could not read {}: {}", path.display(), err)
+    });
+    let keys: Vec<ScriptedKey> = case.keys.iter()
+        .map(|key| key.parse().unwrap())
+        .collect();
+
+    let mut cpu = Cpu::with_platform(case.platform, case.profile.quirks());
+    cpu.seed_rng(0);
+    cpu.load_fontset();
+    cpu.load_rom_bytes(&rom).unwrap();
+    let outcome = headless::run(
+        &mut cpu,
+        MAX_FRAMES,
+        &[Condition::SelfJump],
+        &keys
+    ).unwrap_or_else(|err| panic!("{}: {}", case.rom, err));
+    assert!(
+        outcome.stop != Stop::Frames,
+        "{} did not finish within {} frames",
+        case.rom,
+        MAX_FRAMES
+    );
+
+    let actual = render(&cpu);
+    let golden = test_path("golden", &format!("{}.txt", case.name));
+    if case.blessable && env::var_os("CHIP8_BLESS").is_some() {
+        fs::write(&golden, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap_or_else(|err| {
+        panic!("Could not read {}: {}", golden.display(), err)
+    });
+    assert!(
+        actual == expected,
+        "The display of {} differs from {}:\n{}",
+        case.rom,
+        golden.display(),
+        actual
+    );
+}
+
+/// The roms in tests/roms, which must match the source they were
+/// assembled from
+const ROMS: [&str; 7] = [
+    "font",
+    "opcodes",
+    "flags",
+    "quirks",
+    "keypad",
+    "schip",
+    "xochip",
+];
+
+#[test]
+fn roms_match_their_source() {
+    for rom in ROMS.iter() {
+        let source = test_path("roms", &format!("{}.8o", rom));
+        let assembled = asm::assemble_file(source.to_str().unwrap())
+            .unwrap_or_else(|err| panic!("{}", err));
+        let path = test_path("roms", &format!("{}.ch8", rom));
+        let checked_in = fs::read(&path).unwrap_or_else(|err| {
+            panic!("Could not read {}: {}", path.display(), err)
+        });
+        assert!(
+            assembled == checked_in,
+            "{} no longer matches {}, assemble it again",
+            path.display(),
+            source.display()
+        );
+    }
+}
+
+#[test]
+fn ibm_logo() {
+    check(Case::vendored("ibm-logo"));
+}
+
+#[test]
+fn font() {
+    check(Case::new("font", "font"));
+}
+
+#[test]
+fn opcodes() {
+    check(Case::new("opcodes", "opcodes"));
+}
+
+#[test]
+fn flags() {
+    check(Case::new("flags", "flags"));
+}
+
+#[test]
+fn quirks_vip() {
+    check(Case::new("quirks-vip", "quirks"));
+}
+
+#[test]
+fn quirks_chip48() {
+    check(
+        Case::new("quirks-chip48", "quirks")
+            .platform(Platform::Chip8, Profile::Chip48)
+    );
+}
+
+#[test]
+fn quirks_schip() {
+    check(
+        Case::new("quirks-schip", "quirks")
+            .platform(Platform::SuperChip, Profile::SuperChip)
+    );
+}
+
+#[test]
+fn quirks_modern() {
+    check(
+        Case::new("quirks-modern", "quirks")
+            .platform(Platform::XoChip, Profile::Modern)
+    );
+}
+
+#[test]
+fn keypad() {
+    check(Case::new("keypad", "keypad").keys(&[
+        "2:5:down", "4:5:up",
+        "6:a:down", "8:a:up",
+        "10:3:down", "12:3:up",
+        "16:c:down", "20:c:up",
+    ]));
+}
+
+#[test]
+fn schip() {
+    check(
+        Case::new("schip", "schip")
+            .platform(Platform::SuperChip, Profile::SuperChip)
+    );
+}
+
+#[test]
+fn xochip() {
+    check(
+        Case::new("xochip", "xochip")
+            .platform(Platform::XoChip, Profile::Modern)
+    );
+}